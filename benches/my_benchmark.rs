@@ -1,9 +1,60 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use module_lib::exercises::world_ex::world_ex::render_world;
+use module_lib::{
+    exercises::world_ex::world_ex::render_world,
+    geometry::vector::{point, vector, Operations, Vector},
+    ray::ray::Ray,
+    shapes::{
+        group::Group,
+        shape::{TShape, TShapeBuilder},
+        triangle::Triangle,
+    },
+};
 
 pub fn benchmark(c: &mut Criterion) {
     c.bench_function("render world", |b| b.iter(|| render_world(75)));
 }
 
-criterion_group!(benches, benchmark);
+// 1000 small triangles spread through a 10x10x10 lattice
+fn triangle_lattice() -> Group {
+    let mut builder = Group::builder();
+    for i in 0..1000 {
+        let corner = point((i % 10) as f64, ((i / 10) % 10) as f64, (i / 100) as f64);
+        builder = builder.with_child(Box::new(Triangle::new(
+            corner,
+            corner.add(vector(0.5, 0.0, 0.0)),
+            corner.add(vector(0.0, 0.5, 0.0)),
+        )));
+    }
+    builder.build()
+}
+
+fn lattice_rays() -> Vec<Ray> {
+    (0..100)
+        .map(|i| {
+            let target = point((i % 10) as f64 + 0.1, (i / 10) as f64 + 0.1, 5.0);
+            let origin = point(4.5, 4.5, -20.0);
+            Ray::new(origin, target.sub(origin).norm())
+        })
+        .collect()
+}
+
+pub fn intersect_benchmark(c: &mut Criterion) {
+    let rays = lattice_rays();
+    let flat = triangle_lattice();
+    c.bench_function("intersect 1000 triangles", |b| {
+        b.iter(|| rays.iter().map(|r| flat.intersect(r).len()).sum::<usize>())
+    });
+
+    let mut divided = triangle_lattice();
+    divided.divide(4);
+    c.bench_function("intersect 1000 triangles divided", |b| {
+        b.iter(|| {
+            rays.iter()
+                .map(|r| divided.intersect(r).len())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, benchmark, intersect_benchmark);
 criterion_main!(benches);
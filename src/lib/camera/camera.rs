@@ -1,14 +1,91 @@
 #![allow(unused)]
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
 use crate::{
     canvas::canvas::Canvas,
     colour::colour::Colour,
-    geometry::vector::{point, Operations, Vector},
-    matrix::matrix::{Axis, Matrix},
+    geometry::vector::{point, vector, Operations, Tup, Vector},
+    matrix::matrix::{Axis, InverseCache, Matrix},
     ray::ray::Ray,
-    world::world::World,
+    shapes::bounds::BoundingBox,
+    world::world::{World, DEFAULT_RECURSION_DEPTH},
 };
 
 use rayon::prelude::*;
+use uuid::Uuid;
+
+// luminance that auto exposure maps the mean of an image to
+const MIDDLE_GREY: f64 = 0.18;
+
+/// Width and height of the tiles rendered by `render_with_progress`
+pub const TILE_SIZE: usize = 32;
+
+/// How pixels on the canvas map to ray directions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Pixels lie on a flat image plane, as with a pinhole camera
+    Perspective,
+    /// Pixels lie on a cylinder around the camera, so the horizontal coordinate maps directly to
+    /// an azimuth angle. The fov is then the horizontal sweep, which may be up to a full turn,
+    /// without the stretching perspective gives at wide angles
+    Cylindrical,
+}
+
+/// A plane bounding the camera's view, in world space. Points where `normal · p + distance` is
+/// positive lie on the visible side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrustumPlane {
+    pub normal: Tup,
+    pub distance: f64,
+}
+
+impl FrustumPlane {
+    /// True when the whole box lies on the hidden side of the plane
+    pub fn excludes(&self, bounds: &BoundingBox) -> bool {
+        // contribution of the box corner furthest along the normal on one axis
+        let reach = |n: f64, min: f64, max: f64| {
+            if n > 0.0 {
+                n * max
+            } else if n < 0.0 {
+                n * min
+            } else {
+                0.0
+            }
+        };
+        let furthest = reach(self.normal.0, bounds.min.0, bounds.max.0)
+            + reach(self.normal.1, bounds.min.1, bounds.max.1)
+            + reach(self.normal.2, bounds.min.2, bounds.max.2);
+        furthest + self.distance < 0.0
+    }
+}
+
+/// Rectangle of pixels on the canvas, with `x` and `y` its top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Per pixel surface data for the first hit of each camera ray, for use by post-processing such
+/// as denoising. Rows are indexed by y then x, as with `Canvas`
+pub struct GBuffer {
+    pub width: usize,
+    pub height: usize,
+    /// Unlit surface colour
+    pub albedo: Canvas,
+    /// World space normal facing back toward the camera, zero where nothing was hit
+    pub normals: Vec<Vec<Tup>>,
+    /// Distance from the camera to the hit, infinite where nothing was hit
+    pub depth: Vec<Vec<f64>>,
+}
 
 pub struct Camera {
     h_size: usize,
@@ -17,7 +94,12 @@ pub struct Camera {
     half_width: f64,
     half_height: f64,
     pub transform: Matrix,
+    pub projection: Projection,
+    /// Slides the image plane right and up, in units of the plane one unit in front of the eye,
+    /// moving the view without tilting it so vertical lines stay parallel
+    pub lens_shift: (f64, f64),
     px_size: f64,
+    inverse: InverseCache,
 }
 
 impl Camera {
@@ -37,73 +119,322 @@ impl Camera {
             v_size,
             fov,
             transform: Matrix::ident(),
+            projection: Projection::Perspective,
+            lens_shift: (0.0, 0.0),
             px_size: pixel_size,
+            inverse: InverseCache::default(),
             half_width,
             half_height,
         }
     }
 
     fn ray_for_pixel(&self, x: f64, y: f64) -> Option<Ray> {
-        // offset from edge of canvas to pixel's center
-        let x_offset = (x + 0.5) * self.px_size;
-        let y_offset = (y + 0.5) * self.px_size;
-
         // untransformed coords of the pixel in world space
-        let world_x = self.half_width - x_offset;
-        let world_y = self.half_height - y_offset;
+        let pixel = match self.projection {
+            Projection::Perspective => {
+                // offset from edge of canvas to pixel's center
+                let x_offset = (x + 0.5) * self.px_size;
+                let y_offset = (y + 0.5) * self.px_size;
+
+                // the camera looks toward -z, so +x is on the left of the image
+                let world_x = self.half_width - x_offset - self.lens_shift.0;
+                let world_y = self.half_height - y_offset + self.lens_shift.1;
+                point(world_x, world_y, -1.0)
+            }
+            Projection::Cylindrical => {
+                // arc length of a pixel on a cylinder of radius one
+                let px_angle = self.fov / self.h_size as f64;
+                let azimuth = (self.h_size as f64 / 2.0 - (x + 0.5)) * px_angle;
+                let world_y = (self.v_size as f64 / 2.0 - (y + 0.5)) * px_angle;
+                point(azimuth.sin(), world_y, -azimuth.cos())
+            }
+        };
 
-        let maybe_px = self
-            .transform
-            .inverse()
-            .map(|m| m.mul_tup(point(world_x, world_y, -1.0)));
+        let inverse = self.inverse.inverse_of(&self.transform)?;
+        let px = inverse.mul_tup(pixel);
+        let orig = inverse.mul_tup(point(0.0, 0.0, 0.0));
+        Ray::try_new(orig, px.sub(orig).norm()).ok()
+    }
 
-        let maybe_orig = self
-            .transform
-            .inverse()
-            .map(|m| m.mul_tup(point(0.0, 0.0, 0.0)));
+    /// The planes bounding what a perspective camera can see. The camera has no far clip, so the
+    /// far plane sits at infinity
+    pub fn frustum_planes(&self) -> [FrustumPlane; 6] {
+        let (hw, hh) = (self.half_width, self.half_height);
+        let (shift_x, shift_y) = self.lens_shift;
+        // extent of the image plane one unit in front of the eye
+        let (min_x, max_x) = (-hw - shift_x, hw - shift_x);
+        let (min_y, max_y) = (-hh + shift_y, hh + shift_y);
+        // a plane (n, d) in camera space is (transpose(T) * (n, d)) in world space
+        let to_world = self.transform.transpose();
+        let plane = |normal: Tup| {
+            let p = to_world.mul_tup(normal);
+            FrustumPlane {
+                normal: vector(p.0, p.1, p.2),
+                distance: p.3,
+            }
+        };
+        let far = plane(vector(0.0, 0.0, 1.0));
 
-        // unwraps maybes to calculate the direction, which is used to form the ray
-        maybe_px
-            .and_then(|px| maybe_orig.map(|orig| px.sub(orig).norm()))
-            .and_then(|dir| maybe_orig.map(|orig| Ray::new(orig, dir)))
+        [
+            plane(vector(1.0, 0.0, min_x)),
+            plane(vector(-1.0, 0.0, -max_x)),
+            plane(vector(0.0, 1.0, min_y)),
+            plane(vector(0.0, -1.0, -max_y)),
+            plane(vector(0.0, 0.0, -1.0)),
+            FrustumPlane {
+                distance: f64::INFINITY,
+                ..far
+            },
+        ]
+    }
+
+    /// Flags which of the world's objects may be visible to the camera. Only perspective
+    /// projections are culled as the others can see beside and behind the camera
+    pub fn visible_objects(&self, world: &World) -> Vec<bool> {
+        match self.projection {
+            Projection::Perspective => {
+                let planes = self.frustum_planes();
+                world
+                    .objects
+                    .iter()
+                    .map(|o| {
+                        let bounds = o.transformed_bounds();
+                        !planes.iter().any(|p| p.excludes(&bounds))
+                    })
+                    .collect()
+            }
+            _ => vec![true; world.objects.len()],
+        }
     }
 
     pub fn render(&self, world: &World) -> Canvas {
-        let mut canvas = Canvas::new(self.h_size, self.v_size);
-        let colours: Vec<Option<(usize, usize, Colour)>> = (0..self.v_size)
-            .into_par_iter()
+        self.render_cancellable(world, &AtomicBool::new(false))
+            .expect("render was cancelled")
+    }
+
+    /// Renders like `render`, but camera rays skip objects outside the view frustum
+    pub fn render_culled(&self, world: &World) -> Canvas {
+        let visible = self.visible_objects(world);
+        self.render_with(
+            |r| world.color_at_visible(r, DEFAULT_RECURSION_DEPTH, &visible),
+            &AtomicBool::new(false),
+        )
+        .expect("render was cancelled")
+    }
+
+    /// Renders direct lighting and shadows only, without reflections, for comparing how much the
+    /// indirect contributions add
+    pub fn render_direct_only(&self, world: &World) -> Canvas {
+        // a limit of one traces the camera ray but spawns no reflected rays
+        self.render_with(|r| world.color_at(r, 1), &AtomicBool::new(false))
+            .expect("render was cancelled")
+    }
+
+    /// Renders like `render`, but stops early and returns `None` once `cancel` is set. The flag
+    /// is checked before each row
+    pub fn render_cancellable(&self, world: &World, cancel: &AtomicBool) -> Option<Canvas> {
+        self.render_with(|r| world.color_at_default(r), cancel)
+    }
+
+    /// Renders like `render`, but in tiles of `TILE_SIZE` pixels square, handing each tile to
+    /// `on_tile` with its colours row by row as soon as it is done, for live previews. Tiles are
+    /// rendered in parallel and in no particular order, but `on_tile` is only called on the
+    /// calling thread so it needn't be thread safe
+    pub fn render_with_progress<F>(&self, world: &World, mut on_tile: F) -> Canvas
+    where
+        F: FnMut(Tile, &[Colour]),
+    {
+        let tiles: Vec<Tile> = (0..self.v_size)
+            .step_by(TILE_SIZE)
             .flat_map(|y| {
-                (0..self.h_size)
-                    .into_par_iter()
-                    .map(|x| {
-                        let maybe_ray = self.ray_for_pixel(x as f64, y as f64);
-                        let result: Option<(usize, usize, Colour)> =
-                            maybe_ray.map(|r| world.color_at(&r, 5)).map(|c| (x, y, c));
-                        result
-                    })
-                    .collect::<Vec<Option<(usize, usize, Colour)>>>()
+                (0..self.h_size).step_by(TILE_SIZE).map(move |x| Tile {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(self.h_size - x),
+                    height: TILE_SIZE.min(self.v_size - y),
+                })
             })
             .collect();
-        colours.into_iter().flatten().for_each(|(x, y, c)| {
-            canvas.set_pixel(x, y, c);
+
+        let mut canvas = Canvas::new(self.h_size, self.v_size);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                tiles.into_par_iter().for_each_with(sender, |sender, tile| {
+                    let colours = self.render_tile(&|r| world.color_at_default(r), tile);
+                    // the receiver outlives every sender, so sending can't fail
+                    sender.send((tile, colours)).unwrap();
+                })
+            });
+            for (tile, colours) in receiver {
+                for (i, colour) in colours.iter().enumerate() {
+                    canvas.set_pixel(tile.x + i % tile.width, tile.y + i / tile.width, *colour);
+                }
+                on_tile(tile, &colours);
+            }
         });
         canvas
     }
+
+    /// Renders only the pixels with `x0 <= x < x1` and `y0 <= y < y1`, leaving the rest of the
+    /// canvas black, to re-render just the part of an image being worked on. The rectangle is
+    /// clamped to the canvas, so parts of it outside the image are ignored
+    pub fn render_region(
+        &self,
+        world: &World,
+        x0: usize,
+        y0: usize,
+        x1: usize,
+        y1: usize,
+    ) -> Canvas {
+        let (x1, y1) = (x1.min(self.h_size), y1.min(self.v_size));
+        let width = x1.saturating_sub(x0);
+        let rows: Vec<Vec<Colour>> = (y0..y1)
+            .into_par_iter()
+            .map(|y| {
+                let row = Tile {
+                    x: x0,
+                    y,
+                    width,
+                    height: 1,
+                };
+                self.render_tile(&|r| world.color_at_default(r), row)
+            })
+            .collect();
+        let mut canvas = Canvas::new(self.h_size, self.v_size);
+        for (y, row) in (y0..y1).zip(rows) {
+            for (x, colour) in (x0..x1).zip(row) {
+                canvas.set_pixel(x, y, colour);
+            }
+        }
+        canvas
+    }
+
+    fn render_tile<F>(&self, colour_for_ray: &F, tile: Tile) -> Vec<Colour>
+    where
+        F: Fn(&Ray) -> Colour,
+    {
+        (tile.y..tile.y + tile.height)
+            .flat_map(|y| (tile.x..tile.x + tile.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                self.ray_for_pixel(x as f64, y as f64)
+                    .map(|r| colour_for_ray(&r))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Colours of row `y` of the image, left to right, as `render` would produce them
+    pub fn render_scanline(&self, world: &World, y: usize) -> Vec<Colour> {
+        self.scanline_with(&|r| world.color_at_default(r), y)
+    }
+
+    fn scanline_with<F>(&self, colour_for_ray: &F, y: usize) -> Vec<Colour>
+    where
+        F: Fn(&Ray) -> Colour + Sync,
+    {
+        (0..self.h_size)
+            .into_par_iter()
+            .map(|x| {
+                self.ray_for_pixel(x as f64, y as f64)
+                    .map(|r| colour_for_ray(&r))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn render_with<F>(&self, colour_for_ray: F, cancel: &AtomicBool) -> Option<Canvas>
+    where
+        F: Fn(&Ray) -> Colour + Sync,
+    {
+        let rows: Vec<Option<Vec<Colour>>> = (0..self.v_size)
+            .into_par_iter()
+            .map(|y| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                Some(self.scanline_with(&colour_for_ray, y))
+            })
+            .collect();
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut canvas = Canvas::new(self.h_size, self.v_size);
+        for (y, row) in rows.into_iter().enumerate() {
+            for (x, colour) in row?.into_iter().enumerate() {
+                canvas.set_pixel(x, y, colour);
+            }
+        }
+        Some(canvas)
+    }
+
+    /// Id of the nearest object seen through the pixel, if any
+    pub fn pick(&self, world: &World, x: usize, y: usize) -> Option<Uuid> {
+        let ray = self.ray_for_pixel(x as f64, y as f64)?;
+        world.hit_comps(&ray).map(|(_, comps)| comps.object.id())
+    }
+
+    pub fn render_gbuffer(&self, world: &World) -> GBuffer {
+        let mut gbuffer = GBuffer {
+            width: self.h_size,
+            height: self.v_size,
+            albedo: Canvas::new(self.h_size, self.v_size),
+            normals: vec![vec![vector(0.0, 0.0, 0.0); self.h_size]; self.v_size],
+            depth: vec![vec![f64::INFINITY; self.h_size]; self.v_size],
+        };
+        for y in 0..self.v_size {
+            for x in 0..self.h_size {
+                let Some(ray) = self.ray_for_pixel(x as f64, y as f64) else {
+                    continue;
+                };
+                if let Some((at, comps)) = world.hit_comps(&ray) {
+                    let albedo = comps
+                        .object
+                        .material()
+                        .colour_at(comps.object.to_trait_ref(), comps.point);
+                    gbuffer.albedo.set_pixel(x, y, albedo);
+                    gbuffer.normals[y][x] = comps.normal();
+                    gbuffer.depth[y][x] = at;
+                }
+            }
+        }
+        gbuffer
+    }
+
+    /// Returns a copy of the canvas scaled so that its mean luminance sits at middle grey
+    pub fn auto_expose(&self, canvas: &Canvas) -> Canvas {
+        let mean = canvas.mean_luminance();
+        let exposure = if mean > 0.0 { MIDDLE_GREY / mean } else { 1.0 };
+
+        let mut exposed = Canvas::new(canvas.width, canvas.height);
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                if let Some(colour) = canvas.get_pixel(x, y) {
+                    exposed.set_pixel(x, y, colour * exposure);
+                }
+            }
+        }
+        exposed
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::f64::consts::PI;
+    use std::{f64::consts::PI, sync::atomic::AtomicBool};
 
     use crate::{
+        canvas::canvas::Canvas,
         colour::colour::Colour,
         geometry::vector::{point, vector},
+        light::light::PointLight,
+        material::material::Material,
         matrix::matrix::{Axis, Matrix},
+        shapes::{plane::Plane, shape::TShapeBuilder, sphere::Sphere},
         utils::test::ApproxEq,
         world::world::World,
     };
 
-    use super::Camera;
+    use super::{Camera, Projection, TILE_SIZE};
 
     #[test]
     fn default_constructor_has_corrector_fields() {
@@ -164,4 +495,287 @@ mod tests {
         let px = image.get_pixel(5, 5).unwrap();
         px.approx_eq(Colour::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn scanlines_make_up_the_full_render() {
+        let w = World::default();
+        let mut c = Camera::new(8, 8, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let image = c.render(&w);
+        for y in 0..8 {
+            let row: Vec<Colour> = (0..8).map(|x| image.get_pixel(x, y).unwrap()).collect();
+            assert_eq!(c.render_scanline(&w, y), row);
+        }
+    }
+
+    #[test]
+    fn progress_tiles_cover_every_pixel_once_and_match_render() {
+        let w = World::default();
+        // not a multiple of the tile size, so the last row and column of tiles are cut short
+        let (width, height) = (TILE_SIZE * 2 + 5, TILE_SIZE + 7);
+        let mut c = Camera::new(width, height, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let expected = c.render(&w);
+
+        let mut coverage = vec![vec![0; width]; height];
+        let mut tiles = 0;
+        let sut = c.render_with_progress(&w, |tile, colours| {
+            tiles += 1;
+            assert_eq!(colours.len(), tile.width * tile.height);
+            for (i, colour) in colours.iter().enumerate() {
+                let (x, y) = (tile.x + i % tile.width, tile.y + i / tile.width);
+                coverage[y][x] += 1;
+                assert_eq!(Some(*colour), expected.get_pixel(x, y));
+            }
+        });
+
+        assert_eq!(tiles, 6);
+        assert!(coverage.iter().flatten().all(|&n| n == 1));
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(sut.get_pixel(x, y), expected.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn region_render_matches_full_render_inside_and_is_black_outside() {
+        let w = World::default();
+        let mut c = Camera::new(20, 16, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let full = c.render(&w);
+        let sut = c.render_region(&w, 6, 4, 15, 12);
+        for y in 0..16 {
+            for x in 0..20 {
+                let expected = if (6..15).contains(&x) && (4..12).contains(&y) {
+                    full.get_pixel(x, y)
+                } else {
+                    Some(Colour::black())
+                };
+                assert_eq!(sut.get_pixel(x, y), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn region_render_is_clamped_to_the_canvas() {
+        let w = World::default();
+        let mut c = Camera::new(10, 8, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -1.2),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let full = c.render(&w);
+        let sut = c.render_region(&w, 5, 6, 100, 100);
+        assert_ne!(full.get_pixel(9, 7), Some(Colour::black()));
+        assert_eq!(sut.get_pixel(9, 7), full.get_pixel(9, 7));
+        assert_eq!(sut.get_pixel(4, 7), Some(Colour::black()));
+
+        let empty = c.render_region(&w, 50, 50, 100, 100);
+        assert_eq!(empty.width, 10);
+        assert_eq!(empty.height, 8);
+    }
+
+    #[test]
+    fn auto_expose_normalises_mean_luminance() {
+        let c = Camera::new(4, 4, PI / 2.0);
+        let mut canvas = Canvas::new(4, 4);
+        for x in 0..4 {
+            for y in 0..4 {
+                canvas.set_pixel(x, y, Colour::new(0.9, 0.9, 0.9));
+            }
+        }
+        let sut = c.auto_expose(&canvas);
+        sut.mean_luminance().approx_eq(0.18);
+    }
+
+    #[test]
+    fn cylindrical_projection_center_ray_points_forward() {
+        let mut c = Camera::new(201, 101, PI);
+        c.projection = Projection::Cylindrical;
+        let ray = c.ray_for_pixel(100.0, 50.0).unwrap();
+        ray.direction.approx_eq(vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn cylindrical_projection_edge_rays_fan_out_by_azimuth() {
+        let mut c = Camera::new(201, 101, PI);
+        c.projection = Projection::Cylindrical;
+        // pixel centres sit half a pixel in from the edges of the 180 degree sweep
+        let edge_azimuth = PI / 2.0 - (PI / 201.0) / 2.0;
+
+        let left = c.ray_for_pixel(0.0, 50.0).unwrap();
+        left.direction
+            .approx_eq(vector(edge_azimuth.sin(), 0.0, -edge_azimuth.cos()));
+
+        let right = c.ray_for_pixel(200.0, 50.0).unwrap();
+        right
+            .direction
+            .approx_eq(vector(-edge_azimuth.sin(), 0.0, -edge_azimuth.cos()));
+    }
+
+    #[test]
+    fn objects_behind_the_camera_are_culled() {
+        let visible = Sphere::builder().build_trait();
+        let behind = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 0.0, -20.0))
+            .build_trait();
+        let mut world = World::new(vec![visible, behind], PointLight::default());
+        world.stats.enabled = true;
+
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(c.visible_objects(&world), vec![true, false]);
+
+        let full = c.render(&world);
+        let full_tests = world.stats.intersection_tests();
+        world.stats.reset();
+        let culled = c.render_culled(&world);
+        let culled_tests = world.stats.intersection_tests();
+
+        // one fewer test per camera ray, shadows still test everything
+        assert_eq!(full_tests - culled_tests, 11 * 11);
+        for x in 0..11 {
+            for y in 0..11 {
+                assert_eq!(full.get_pixel(x, y), culled.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_render_returns_nothing() {
+        let mut world = World::default();
+        world.stats.enabled = true;
+        let c = Camera::new(11, 11, PI / 2.0);
+
+        assert!(c
+            .render_cancellable(&world, &AtomicBool::new(true))
+            .is_none());
+        assert_eq!(world.stats.rays_traced(), 0);
+
+        let sut = c
+            .render_cancellable(&world, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!((sut.width, sut.height), (11, 11));
+    }
+
+    #[test]
+    fn gbuffer_records_nearest_surface_per_pixel() {
+        let near = Sphere::builder()
+            .with_transform(Matrix::translation(-1.5, 0.0, 0.0))
+            .with_material(Material::with_colour(Colour::new(1.0, 0.0, 0.0)))
+            .build_trait();
+        let far = Sphere::builder()
+            .with_transform(Matrix::translation(3.0, 0.0, 5.0))
+            .with_material(Material::with_colour(Colour::new(0.0, 0.0, 1.0)))
+            .build_trait();
+        let world = World::new(vec![near, far], PointLight::default());
+
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let sut = c.render_gbuffer(&world);
+
+        // the near sphere is on the left of the image, the far one on the right
+        let (near_px, far_px) = ((3, 5), (7, 5));
+        assert!(sut.depth[near_px.1][near_px.0] < sut.depth[far_px.1][far_px.0]);
+        assert!(sut.depth[5][5].is_infinite());
+        assert_eq!(
+            sut.albedo.get_pixel(near_px.0, near_px.1),
+            Some(Colour::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            sut.albedo.get_pixel(far_px.0, far_px.1),
+            Some(Colour::new(0.0, 0.0, 1.0))
+        );
+        for (x, y) in [near_px, far_px] {
+            assert!(sut.normals[y][x].2 < 0.0);
+        }
+    }
+
+    #[test]
+    fn direct_only_render_misses_reflections() {
+        let floor = Plane::builder()
+            .with_material(Material::builder().with_reflectivity(0.8).build())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let ball = Sphere::builder()
+            .with_material(Material::with_colour(Colour::new(1.0, 0.2, 0.2)))
+            .build_trait();
+        let world = World::new(vec![floor, ball], PointLight::default());
+
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 1.0, -5.0),
+            point(0.0, -0.5, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let full = c.render(&world);
+        let direct = c.render_direct_only(&world);
+
+        // reflections only ever add light, and the floor in front of the ball reflects it
+        for y in 0..11 {
+            for x in 0..11 {
+                let direct_px = direct.get_pixel(x, y).unwrap();
+                assert!(direct_px.luminance() <= full.get_pixel(x, y).unwrap().luminance());
+            }
+        }
+        assert!(direct.mean_luminance() < full.mean_luminance());
+    }
+
+    #[test]
+    fn vertical_lens_shift_moves_horizon_and_keeps_verticals_parallel() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        let level = c.ray_for_pixel(5.0, 5.0).unwrap();
+        assert!(level.direction.1.abs() < 0.00001);
+
+        c.lens_shift = (0.0, 0.5);
+        let centre = c.ray_for_pixel(5.0, 5.0).unwrap();
+        assert!(centre.direction.1 > 0.0);
+
+        // rays down a column of pixels lie in one vertical plane
+        let heading = |y: f64| {
+            let d = c.ray_for_pixel(2.0, y).unwrap().direction;
+            d.0 / d.2
+        };
+        for y in 0..11 {
+            heading(y as f64).approx_eq(heading(0.0));
+        }
+    }
+
+    #[test]
+    fn picking_returns_the_object_under_the_pixel() {
+        let sphere = Sphere::builder().build_trait();
+        let id = sphere.id();
+        let world = World::new(vec![sphere], PointLight::default());
+
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.transform = Matrix::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(c.pick(&world, 5, 5), Some(id));
+        assert_eq!(c.pick(&world, 0, 0), None);
+    }
 }
@@ -1,21 +1,38 @@
-use std::fs;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use exr::prelude::{read_first_rgba_layer_from_file, write_rgb_file};
+use serde::{Deserialize, Serialize};
 
 use crate::colour::colour::Colour;
 
-#[derive(Debug)]
+// characters used for ascii previews, from darkest to brightest
+const ASCII_RAMP: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+// terminal characters are roughly twice as tall as they are wide
+const ASCII_CHAR_ASPECT: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pixels: Vec<Vec<Colour>>,
+    /// Channels are raised to `1 / gamma` when written to 8-bit output, 1 leaves them linear
+    gamma: f64,
 }
 
 trait NormaliseColour<T> {
     fn as_norm_colour(self) -> i32;
+    fn gamma_norm_colour(self, gamma: f64) -> i32;
 }
 
 impl NormaliseColour<f64> for f64 {
     fn as_norm_colour(self) -> i32 {
-        let normalised_self = (self * 255.0).ceil() as i32;
+        let normalised_self = (self * 255.0).round() as i32;
         if normalised_self >= 255 {
             255
         } else if normalised_self <= 0 {
@@ -24,6 +41,20 @@ impl NormaliseColour<f64> for f64 {
             normalised_self
         }
     }
+
+    fn gamma_norm_colour(self, gamma: f64) -> i32 {
+        if gamma == 1.0 {
+            return self.as_norm_colour();
+        }
+        self.max(0.0).powf(1.0 / gamma).as_norm_colour()
+    }
+}
+
+/// Formats the canvas as the contents of a PPM file
+impl fmt::Display for Canvas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_ppm())
+    }
 }
 
 trait LineLengthLimited {
@@ -66,16 +97,137 @@ impl Canvas {
             width,
             height,
             pixels: vec![vec![Colour::default(); width]; height],
+            gamma: 1.0,
         }
     }
 
-    pub fn save(&self, location: &str) -> () {
-        fs::write(location, self.to_ppm()).expect("could not write ppm to file");
+    /// Gamma applied by `save`, `save_png` and the other 8-bit outputs. 2.2 suits most displays
+    pub fn set_gamma(&mut self, gamma: f64) {
+        self.gamma = gamma;
+    }
+
+    pub fn save(&self, location: &str) -> io::Result<()> {
+        fs::write(location, self.to_ppm())
+    }
+
+    /// Red, green and blue bytes of each pixel, row by row from the top left, clamped the same
+    /// way as `save`
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flatten()
+            .flat_map(|c| [c.red, c.green, c.blue].map(|v| v.gamma_norm_colour(self.gamma) as u8))
+            .collect()
+    }
+
+    /// Writes the canvas as an 8-bit RGB PNG, with channels clamped the same way as `save`
+    pub fn save_png(&self, location: &str) -> Result<(), io::Error> {
+        let data = self.to_rgb_bytes();
+
+        let file = BufWriter::new(File::create(location)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    /// Writes the canvas as a 32-bit float OpenEXR image. Unlike `save` the channels are not
+    /// clamped, so values above 1.0 survive for tone mapping in other tools
+    pub fn save_exr(&self, location: &str) -> io::Result<()> {
+        write_rgb_file(location, self.width, self.height, |x, y| {
+            let c = self.pixels[y][x];
+            (c.red as f32, c.green as f32, c.blue as f32)
+        })
+        .map_err(io::Error::other)
+    }
+
+    /// Reads the first RGBA layer of an OpenEXR image, dropping its alpha channel
+    pub fn load_exr(location: &str) -> io::Result<Self> {
+        let image = read_first_rgba_layer_from_file(
+            location,
+            |resolution, _| Canvas::new(resolution.width(), resolution.height()),
+            |canvas, position, (r, g, b, _): (f32, f32, f32, f32)| {
+                canvas.pixels[position.y()][position.x()] =
+                    Colour::new(r as f64, g as f64, b as f64);
+            },
+        )
+        .map_err(io::Error::other)?;
+        Ok(image.layer_data.channel_data.pixels)
+    }
+
+    /// Reads a plain (P3) PPM image. Samples are scaled by the file's maximum colour value, and
+    /// may be split across lines in any way. Lines starting with `#` are comments
+    pub fn from_ppm(contents: &str) -> Result<Canvas, String> {
+        let mut tokens = contents
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace);
+
+        match tokens.next() {
+            Some("P3") => {}
+            Some(magic) => return Err(format!("expected magic number P3, found {}", magic)),
+            None => return Err("ppm is empty".to_string()),
+        }
+        let mut next_number = |name: &str| {
+            let token = tokens
+                .next()
+                .ok_or(format!("ppm ended before the {}", name))?;
+            token
+                .parse::<usize>()
+                .map_err(|_| format!("invalid {} {:?}", name, token))
+        };
+
+        let width = next_number("width")?;
+        let height = next_number("height")?;
+        let max = next_number("maximum colour value")?;
+        if max == 0 {
+            return Err("maximum colour value must be positive".to_string());
+        }
+
+        // the header can't be trusted, so check it matches the data before allocating for it. A
+        // row of zero height or a column of zero width would allocate nothing but its other side
+        if (width == 0) != (height == 0) {
+            return Err(format!("ppm size {}x{} is empty", width, height));
+        }
+        let samples = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or(format!("ppm size {}x{} is too large", width, height))?;
+        let data: Vec<&str> = tokens.collect();
+        if data.len() < samples {
+            return Err("ppm ended before the pixel data".to_string());
+        }
+        if data.len() > samples {
+            return Err("ppm has more pixel data than its size".to_string());
+        }
+
+        let mut data = data.into_iter();
+        let mut canvas = Canvas::new(width, height);
+        for row in canvas.pixels.iter_mut() {
+            for pixel in row.iter_mut() {
+                let mut sample = || {
+                    let token = data.next().unwrap_or_default();
+                    token
+                        .parse::<usize>()
+                        .map(|v| v as f64 / max as f64)
+                        .map_err(|_| format!("invalid pixel data {:?}", token))
+                };
+                *pixel = Colour::new(sample()?, sample()?, sample()?);
+            }
+        }
+        Ok(canvas)
     }
 
     fn to_ppm(&self) -> String {
+        self.to_ppm_with_gamma(self.gamma)
+    }
+
+    /// Contents of a PPM file of the canvas with each channel raised to `1 / gamma`
+    pub fn to_ppm_with_gamma(&self, gamma: f64) -> String {
         let width_height = format!("{} {}", self.width, self.height);
-        let pixel_grid = self.get_pixel_grid();
+        let pixel_grid = self.get_pixel_grid(gamma);
         let lines = vec!["P3", width_height.as_str(), "255", pixel_grid.as_str()];
         return lines
             .into_iter()
@@ -83,7 +235,7 @@ impl Canvas {
             .collect();
     }
 
-    fn get_pixel_grid(&self) -> String {
+    fn get_pixel_grid(&self, gamma: f64) -> String {
         self.pixels
             .iter()
             .map(|pixel_col_line| {
@@ -92,9 +244,9 @@ impl Canvas {
                     .map(|colour| {
                         format!(
                             "{} {} {} ",
-                            colour.red.as_norm_colour(),
-                            colour.green.as_norm_colour(),
-                            colour.blue.as_norm_colour()
+                            colour.red.gamma_norm_colour(gamma),
+                            colour.green.gamma_norm_colour(gamma),
+                            colour.blue.gamma_norm_colour(gamma)
                         )
                     })
                     .collect()
@@ -103,6 +255,25 @@ impl Canvas {
             .collect()
     }
 
+    /// Canvas as an 8-bit image for use with the `image` crate, clamped the same way as `save`
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let c = self.pixels[y as usize][x as usize];
+            image::Rgb([c.red, c.green, c.blue].map(|v| v.gamma_norm_colour(self.gamma) as u8))
+        })
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_image(img: &image::RgbImage) -> Self {
+        let mut canvas = Canvas::new(img.width() as usize, img.height() as usize);
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let [r, g, b] = pixel.0.map(|v| v as f64 / 255.0);
+            canvas.pixels[y as usize][x as usize] = Colour::new(r, g, b);
+        }
+        canvas
+    }
+
     pub fn get_pixel(&self, x: usize, y: usize) -> Option<Colour> {
         if x >= self.width || y >= self.height {
             None
@@ -122,12 +293,183 @@ impl Canvas {
             ()
         }
     }
+
+    pub fn mean_luminance(&self) -> f64 {
+        let count = self.width * self.height;
+        if count == 0 {
+            return 0.0;
+        }
+        let total: f64 = self.pixels.iter().flatten().map(|c| c.luminance()).sum();
+        total / count as f64
+    }
+
+    /// Returns the luminance at or below which `p` percent of the pixels fall, using the
+    /// nearest-rank method
+    pub fn percentile_luminance(&self, p: f64) -> f64 {
+        let mut luminances: Vec<f64> = self
+            .pixels
+            .iter()
+            .flatten()
+            .map(|c| c.luminance())
+            .collect();
+        if luminances.is_empty() {
+            return 0.0;
+        }
+        luminances.sort_by(|a, b| a.total_cmp(b));
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * luminances.len() as f64).ceil() as usize;
+        luminances[rank.saturating_sub(1)]
+    }
+
+    /// Samples the canvas at `(u, v)` in [0, 1], with (0, 0) the top left corner, blending the
+    /// four texels surrounding the point. Points beyond the outer texel centres clamp to the edge
+    pub fn sample_bilinear(&self, u: f64, v: f64) -> Colour {
+        if self.width == 0 || self.height == 0 {
+            return Colour::black();
+        }
+        // continuous texel coordinates, where integers are texel centres
+        let x = (u * self.width as f64 - 0.5).clamp(0.0, (self.width - 1) as f64);
+        let y = (v * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+        let top = self.pixels[y0][x0] * (1.0 - fx) + self.pixels[y0][x1] * fx;
+        let bottom = self.pixels[y1][x0] * (1.0 - fx) + self.pixels[y1][x1] * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Edge preserving blur. Each pixel becomes a weighted average of its neighbours, with
+    /// weights falling off with distance (`spatial_sigma`, in pixels) and with difference in
+    /// colour (`range_sigma`), so noise is smoothed while strong edges are kept
+    pub fn denoise_bilateral(&self, spatial_sigma: f64, range_sigma: f64) -> Canvas {
+        let radius = (2.0 * spatial_sigma).ceil() as isize;
+        let mut denoised = self.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let centre = self.pixels[y][x];
+                let mut total = Colour::black();
+                let mut total_weight = 0.0;
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (nx, ny) = (x as isize + dx, y as isize + dy);
+                        if nx < 0
+                            || ny < 0
+                            || nx >= self.width as isize
+                            || ny >= self.height as isize
+                        {
+                            continue;
+                        }
+                        let neighbour = self.pixels[ny as usize][nx as usize];
+                        let diff = neighbour - centre;
+                        let range_sq = diff.red.powi(2) + diff.green.powi(2) + diff.blue.powi(2);
+                        let spatial_sq = (dx * dx + dy * dy) as f64;
+                        let weight = (-spatial_sq / (2.0 * spatial_sigma.powi(2))
+                            - range_sq / (2.0 * range_sigma.powi(2)))
+                        .exp();
+                        total = total + neighbour * weight;
+                        total_weight += weight;
+                    }
+                }
+                denoised.pixels[y][x] = total * (1.0 / total_weight);
+            }
+        }
+        denoised
+    }
+
+    /// Greyscale edge map from the gradient of luminance, found with the Sobel operator. The
+    /// magnitude is scaled so that a hard step from black to white gives 1.0
+    pub fn sobel_edges(&self) -> Canvas {
+        let mut edges = Canvas::new(self.width, self.height);
+        let luminance = |x: isize, y: isize| {
+            let x = x.clamp(0, self.width as isize - 1) as usize;
+            let y = y.clamp(0, self.height as isize - 1) as usize;
+            self.pixels[y][x].luminance()
+        };
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let gx =
+                    (luminance(x + 1, y - 1) + 2.0 * luminance(x + 1, y) + luminance(x + 1, y + 1))
+                        - (luminance(x - 1, y - 1)
+                            + 2.0 * luminance(x - 1, y)
+                            + luminance(x - 1, y + 1));
+                let gy =
+                    (luminance(x - 1, y + 1) + 2.0 * luminance(x, y + 1) + luminance(x + 1, y + 1))
+                        - (luminance(x - 1, y - 1)
+                            + 2.0 * luminance(x, y - 1)
+                            + luminance(x + 1, y - 1));
+                let magnitude = (gx * gx + gy * gy).sqrt() / 4.0;
+                edges.pixels[y as usize][x as usize] = Colour::new(magnitude, magnitude, magnitude);
+            }
+        }
+        edges
+    }
+
+    /// Renders the canvas as ascii art `cols` characters wide for quick terminal previews. Each
+    /// character covers a block of pixels whose average luminance picks a character from the ramp
+    pub fn to_ascii(&self, cols: usize) -> String {
+        if cols == 0 || self.width == 0 || self.height == 0 {
+            return String::new();
+        }
+        let rows = ((self.height as f64 * cols as f64 / self.width as f64) * ASCII_CHAR_ASPECT)
+            .round()
+            .max(1.0) as usize;
+
+        // pixel range covered by the nth of `cells` cells along an axis of `size` pixels
+        let span = |n: usize, cells: usize, size: usize| {
+            let start = n * size / cells;
+            let end = ((n + 1) * size / cells).max(start + 1);
+            start..end
+        };
+
+        (0..rows)
+            .map(|row| {
+                let line: String = (0..cols)
+                    .map(|col| {
+                        let ys = span(row, rows, self.height);
+                        let xs = span(col, cols, self.width);
+                        let count = (ys.len() * xs.len()) as f64;
+                        let total: f64 = ys
+                            .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                            .map(|(x, y)| self.pixels[y][x].luminance())
+                            .sum();
+                        let luminance = (total / count).clamp(0.0, 1.0);
+                        ASCII_RAMP[(luminance * (ASCII_RAMP.len() - 1) as f64).round() as usize]
+                    })
+                    .collect();
+                format!("{}\n", line)
+            })
+            .collect()
+    }
+}
+
+/// File name for one frame of an animation. The frame is zero padded to the width of `total` so
+/// the files of a sequence sort in frame order
+pub fn sequence_file_name(base: &str, frame: usize, total: usize) -> String {
+    let width = total.to_string().len();
+    format!("{}_{:0width$}.ppm", base, frame, width = width)
+}
+
+/// Saves `canvas` as one frame of an animation in `dir`, returning the path written
+pub fn save_sequence(
+    dir: &str,
+    base: &str,
+    frame: usize,
+    total: usize,
+    canvas: &Canvas,
+) -> io::Result<String> {
+    let location = Path::new(dir).join(sequence_file_name(base, frame, total));
+    let location = location
+        .to_str()
+        .expect("sequence path is not valid unicode");
+    canvas.save(location)?;
+    Ok(location.to_string())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Canvas, LineLengthLimited};
-    use crate::colour::colour::Colour;
+    use super::{sequence_file_name, Canvas, LineLengthLimited, NormaliseColour};
+    use crate::{colour::colour::Colour, utils::test::ApproxEq};
 
     #[test]
     fn canvas_will_return_some_pixel_in_bounds() {
@@ -136,6 +478,45 @@ mod tests {
         assert_eq!(in_bounds, Some(Colour::default()));
     }
 
+    #[test]
+    fn rgb_bytes_are_clamped_row_by_row() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, Colour::new(1.5, 0.0, -0.5));
+        canvas.set_pixel(1, 0, Colour::new(0.0, 0.5, 0.0));
+        canvas.set_pixel(0, 1, Colour::new(0.0, 0.0, 1.0));
+        canvas.set_pixel(1, 1, Colour::new(0.2, 0.4, 0.6));
+        assert_eq!(
+            canvas.to_rgb_bytes(),
+            vec![255, 0, 0, 0, 128, 0, 0, 0, 255, 51, 102, 153]
+        );
+    }
+
+    #[test]
+    fn channels_are_rounded_to_the_nearest_byte() {
+        assert_eq!(0.5.as_norm_colour(), 128);
+        assert_eq!(1.0.as_norm_colour(), 255);
+        assert_eq!(0.001.as_norm_colour(), 0);
+        assert_eq!(1.2.as_norm_colour(), 255);
+        assert_eq!((-0.3).as_norm_colour(), 0);
+    }
+
+    #[test]
+    fn gamma_brightens_mid_grey() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_pixel(0, 0, Colour::new(0.5, 0.5, 0.5));
+        assert!(canvas.to_ppm_with_gamma(2.2).contains("186 186 186"));
+        assert!(canvas.to_ppm_with_gamma(1.0).contains("128 128 128"));
+        assert_eq!(canvas.to_rgb_bytes(), vec![128, 128, 128]);
+        canvas.set_gamma(2.2);
+        assert_eq!(canvas.to_rgb_bytes(), vec![186, 186, 186]);
+    }
+
+    #[test]
+    fn display_writes_the_ppm() {
+        let canvas = Canvas::new(1, 1);
+        assert_eq!(canvas.to_string(), "P3\n1 1\n255\n0 0 0 \n\n");
+    }
+
     #[test]
     fn canvas_will_return_none_out_of_bounds() {
         let canvas = Canvas::new(5, 4);
@@ -150,6 +531,78 @@ mod tests {
         assert_eq!(Colour::new(1.0, 1.0, 1.0), canvas.get_pixel(3, 3).unwrap());
     }
 
+    #[test]
+    fn ppm_round_trip() {
+        let mut canvas = Canvas::new(4, 3);
+        canvas.set_pixel(0, 0, Colour::new(1.0, 0.0, 0.6));
+        canvas.set_pixel(3, 2, Colour::new(0.2, 0.4, 1.0));
+
+        let sut = Canvas::from_ppm(&canvas.to_ppm()).unwrap();
+        assert_eq!((sut.width, sut.height), (4, 3));
+        for (row, expected) in sut.pixels.iter().zip(&canvas.pixels) {
+            for (c, e) in row.iter().zip(expected) {
+                c.approx_eq(*e);
+            }
+        }
+    }
+
+    #[test]
+    fn ppm_pixel_data_may_span_lines() {
+        let ppm = "P3
+# a comment before the size
+2
+1 100
+100 0
+50 0 0
+# and one between pixels
+100
+";
+        let sut = Canvas::from_ppm(ppm).unwrap();
+        sut.get_pixel(0, 0)
+            .unwrap()
+            .approx_eq(Colour::new(1.0, 0.0, 0.5));
+        sut.get_pixel(1, 0)
+            .unwrap()
+            .approx_eq(Colour::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn ppm_with_bad_magic_or_missing_data_is_an_error() {
+        assert_eq!(
+            Canvas::from_ppm("P6\n1 1\n255\n").unwrap_err(),
+            "expected magic number P3, found P6"
+        );
+        assert_eq!(
+            Canvas::from_ppm("P3\n2 1\n255\n1 2 3 4").unwrap_err(),
+            "ppm ended before the pixel data"
+        );
+    }
+
+    #[test]
+    fn ppm_header_larger_than_its_data_is_an_error() {
+        assert_eq!(
+            Canvas::from_ppm("P3\n100000 100000\n255\n1 2 3").unwrap_err(),
+            "ppm ended before the pixel data"
+        );
+        let huge = format!("P3\n{} 2\n255\n1 2 3", usize::MAX);
+        assert_eq!(
+            Canvas::from_ppm(&huge).unwrap_err(),
+            format!("ppm size {}x2 is too large", usize::MAX)
+        );
+        assert_eq!(
+            Canvas::from_ppm("P3\n100000000000 0\n255\n").unwrap_err(),
+            "ppm size 100000000000x0 is empty"
+        );
+        assert_eq!(
+            Canvas::from_ppm("P3\n0 100000000000\n255\n").unwrap_err(),
+            "ppm size 0x100000000000 is empty"
+        );
+        assert_eq!(
+            Canvas::from_ppm("P3\n1 1\n255\n1 2 3 4").unwrap_err(),
+            "ppm has more pixel data than its size"
+        );
+    }
+
     #[test]
     fn canvas_to_ppm_returns_correct_headers() {
         let canvas = Canvas::new(5, 4);
@@ -166,7 +619,7 @@ mod tests {
         canvas.set_pixel(0, 0, Colour::new(1.5, 0.0, 0.0));
         canvas.set_pixel(2, 1, Colour::new(0.0, 0.5, 0.0));
         canvas.set_pixel(4, 2, Colour::new(-0.5, 0.0, 1.0));
-        let sut = canvas.get_pixel_grid();
+        let sut = canvas.get_pixel_grid(1.0);
         assert_eq!("255 0 0 0 0 0 0 0 0 0 0 0 0 0 0 \n0 0 0 0 0 0 0 128 0 0 0 0 0 0 0 \n0 0 0 0 0 0 0 0 0 0 0 0 0 0 255 \n", sut)
     }
 
@@ -220,8 +673,228 @@ mod tests {
                 canvas.set_pixel(i, j, Colour::new(1.0, 0.8, 0.6));
             }
         }
-        let sut = canvas.get_pixel_grid();
+        let sut = canvas.get_pixel_grid(1.0);
         let expected = "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204 \n153 255 204 153 255 204 153 255 204 153 255 204 153 \n255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204 \n153 255 204 153 255 204 153 255 204 153 255 204 153 \n";
         assert_eq!(sut, expected);
     }
+
+    #[test]
+    fn mean_luminance_of_uniform_grey_canvas() {
+        let mut canvas = Canvas::new(4, 4);
+        for x in 0..4 {
+            for y in 0..4 {
+                canvas.set_pixel(x, y, Colour::new(0.5, 0.5, 0.5));
+            }
+        }
+        canvas.mean_luminance().approx_eq(0.5);
+    }
+
+    #[test]
+    fn percentile_luminance_reflects_bright_tail() {
+        let mut canvas = Canvas::new(10, 10);
+        for x in 0..10 {
+            for y in 0..10 {
+                canvas.set_pixel(x, y, Colour::new(0.05, 0.05, 0.05));
+            }
+        }
+        // 15 bright pixels in a mostly dark image
+        for x in 0..10 {
+            canvas.set_pixel(x, 0, Colour::white());
+        }
+        for x in 0..5 {
+            canvas.set_pixel(x, 1, Colour::white());
+        }
+        canvas.percentile_luminance(90.0).approx_eq(1.0);
+        canvas.percentile_luminance(50.0).approx_eq(0.05);
+        assert!(canvas.mean_luminance() < 0.2);
+    }
+
+    #[test]
+    fn white_canvas_renders_as_densest_ascii_character() {
+        let mut canvas = Canvas::new(20, 10);
+        for x in 0..20 {
+            for y in 0..10 {
+                canvas.set_pixel(x, y, Colour::white());
+            }
+        }
+        let sut = canvas.to_ascii(10);
+        let lines: Vec<&str> = sut.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| *line == "@@@@@@@@@@"));
+    }
+
+    #[test]
+    fn black_canvas_renders_as_spaces() {
+        let canvas = Canvas::new(20, 10);
+        let sut = canvas.to_ascii(5);
+        assert!(sut.lines().all(|line| line == "     "));
+    }
+
+    #[test]
+    fn bilinear_sample_at_texel_centre_returns_texel() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, Colour::new(1.0, 0.0, 0.0));
+        canvas.set_pixel(1, 0, Colour::new(0.0, 0.0, 1.0));
+        canvas.set_pixel(1, 1, Colour::new(0.0, 1.0, 0.0));
+        canvas
+            .sample_bilinear(0.25, 0.25)
+            .approx_eq(Colour::new(1.0, 0.0, 0.0));
+        canvas
+            .sample_bilinear(0.75, 0.75)
+            .approx_eq(Colour::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn bilinear_sample_between_texels_returns_average() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(0, 0, Colour::new(1.0, 0.0, 0.0));
+        canvas.set_pixel(1, 0, Colour::new(0.0, 0.0, 1.0));
+        canvas
+            .sample_bilinear(0.5, 0.5)
+            .approx_eq(Colour::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn saving_to_an_invalid_path_is_an_error() {
+        let path = std::env::temp_dir().join("ray_tracer_missing_dir/canvas.ppm");
+        assert!(Canvas::new(2, 2).save(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn png_header_records_the_canvas_size() {
+        let mut canvas = Canvas::new(5, 3);
+        for x in 0..5 {
+            for y in 0..3 {
+                canvas.set_pixel(x, y, Colour::new(x as f64 / 4.0, y as f64 / 2.0, 0.5));
+            }
+        }
+        let path = std::env::temp_dir().join("ray_tracer_png_header.png");
+        let path = path.to_str().unwrap();
+
+        canvas.save_png(path).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // signature, then the IHDR chunk's length and type before its width and height
+        assert_eq!(&bytes[1..4], b"PNG");
+        assert_eq!(&bytes[12..16], b"IHDR");
+        let read_u32 = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!((read_u32(16), read_u32(20)), (5, 3));
+    }
+
+    #[test]
+    fn exr_round_trip_keeps_values_above_one() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_pixel(2, 1, Colour::new(3.5, 0.25, 1.0));
+        let path = std::env::temp_dir().join("ray_tracer_exr_round_trip.exr");
+        let path = path.to_str().unwrap();
+
+        canvas.save_exr(path).unwrap();
+        let sut = Canvas::load_exr(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!((sut.width, sut.height), (3, 2));
+        sut.get_pixel(2, 1)
+            .unwrap()
+            .approx_eq(Colour::new(3.5, 0.25, 1.0));
+        // the ppm output clamps the same channel
+        assert!(canvas.to_ppm().contains("255 64 255"));
+    }
+
+    #[test]
+    fn loading_a_missing_exr_is_an_error() {
+        let path = std::env::temp_dir().join("ray_tracer_missing.exr");
+        assert!(Canvas::load_exr(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn denoising_reduces_salt_and_pepper_noise() {
+        let mut clean = Canvas::new(16, 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                let v = 0.3 + 0.4 * x as f64 / 15.0;
+                clean.set_pixel(x, y, Colour::new(v, v, v));
+            }
+        }
+        let mut noisy = clean.clone();
+        for (i, (x, y)) in [(2, 3), (7, 7), (12, 4), (5, 13), (14, 11)]
+            .iter()
+            .enumerate()
+        {
+            let v = if i % 2 == 0 { 1.0 } else { 0.0 };
+            noisy.set_pixel(*x, *y, Colour::new(v, v, v));
+        }
+
+        let max_diff = |canvas: &Canvas| {
+            canvas
+                .pixels
+                .iter()
+                .flatten()
+                .zip(clean.pixels.iter().flatten())
+                .map(|(a, b)| {
+                    let d = *a - *b;
+                    d.red.abs().max(d.green.abs()).max(d.blue.abs())
+                })
+                .fold(0.0, f64::max)
+        };
+        let sut = noisy.denoise_bilateral(1.0, 1.0);
+        assert!(max_diff(&sut) < max_diff(&noisy) / 2.0);
+    }
+
+    #[test]
+    fn sobel_finds_boundary_between_black_and_white() {
+        let mut canvas = Canvas::new(8, 4);
+        for y in 0..4 {
+            for x in 4..8 {
+                canvas.set_pixel(x, y, Colour::white());
+            }
+        }
+        let sut = canvas.sobel_edges();
+        for y in 0..4 {
+            for x in 0..8 {
+                let edge = sut.get_pixel(x, y).unwrap().red;
+                if x == 3 || x == 4 {
+                    assert!(edge > 0.9);
+                } else {
+                    assert!(edge < 0.01);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn canvas_round_trips_through_an_image() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_pixel(0, 0, Colour::new(1.0, 0.0, 0.0));
+        canvas.set_pixel(2, 1, Colour::new(0.2, 0.5, 0.8));
+        canvas.set_pixel(1, 1, Colour::new(1.5, -0.5, 0.0));
+
+        let image = canvas.to_image();
+        assert_eq!((image.width(), image.height()), (3, 2));
+
+        let sut = Canvas::from_image(&image);
+        for y in 0..2 {
+            for x in 0..3 {
+                let expected = canvas.get_pixel(x, y).unwrap();
+                let clamp = |v: f64| v.clamp(0.0, 1.0);
+                let actual = sut.get_pixel(x, y).unwrap();
+                for (a, e) in [
+                    (actual.red, expected.red),
+                    (actual.green, expected.green),
+                    (actual.blue, expected.blue),
+                ] {
+                    // within one 8-bit step of the clamped original
+                    assert!((a - clamp(e)).abs() <= 1.0 / 255.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sequence_frames_are_padded_to_the_width_of_the_total() {
+        assert_eq!(sequence_file_name("frame", 7, 1000), "frame_0007.ppm");
+        assert_eq!(sequence_file_name("frame", 7, 10), "frame_07.ppm");
+        assert_eq!(sequence_file_name("frame", 7, 9), "frame_7.ppm");
+    }
 }
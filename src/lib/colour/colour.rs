@@ -1,6 +1,8 @@
 use std::ops::{Add, Mul, Sub};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Colour {
     pub red: f64,
     pub green: f64,
@@ -22,6 +24,73 @@ impl Colour {
             blue: 1.0,
         }
     }
+
+    // relative luminance using the Rec. 709 weights
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    /// Brings an over-bright colour back into gamut by blending it towards the grey of the same
+    /// luminance until no channel exceeds 1.0. Unlike clamping each channel this keeps the
+    /// brightness and the hue roughly intact, so bright red becomes pink rather than pure red
+    pub fn desaturate_to_gamut(&self) -> Colour {
+        let max = self.red.max(self.green).max(self.blue);
+        if max <= 1.0 {
+            return *self;
+        }
+        let luminance = self.luminance();
+        if luminance >= 1.0 {
+            return Colour::white();
+        }
+        let grey = Colour::new(luminance, luminance, luminance);
+        let t = (max - 1.0) / (max - luminance);
+        *self * (1.0 - t) + grey * t
+    }
+
+    /// Composites this colour with coverage `alpha` over a background using straight alpha,
+    /// where colours are stored independently of their coverage. Returns the resulting colour
+    /// and alpha
+    pub fn blend_over(
+        self,
+        alpha: f64,
+        background: Colour,
+        background_alpha: f64,
+    ) -> (Colour, f64) {
+        let out_alpha = alpha + background_alpha * (1.0 - alpha);
+        if out_alpha == 0.0 {
+            return (Colour::black(), 0.0);
+        }
+        let colour =
+            (self * alpha + background * (background_alpha * (1.0 - alpha))) * (1.0 / out_alpha);
+        (colour, out_alpha)
+    }
+
+    /// As `blend_over`, for colours already multiplied by their alpha. Averaging premultiplied
+    /// colours doesn't bleed the colour of transparent pixels into edges, which is what leaves
+    /// dark fringes around straight alpha images once they are filtered
+    pub fn blend_over_premultiplied(
+        self,
+        alpha: f64,
+        background: Colour,
+        background_alpha: f64,
+    ) -> (Colour, f64) {
+        (
+            self + background * (1.0 - alpha),
+            alpha + background_alpha * (1.0 - alpha),
+        )
+    }
+
+    pub fn premultiply(self, alpha: f64) -> Colour {
+        self * alpha
+    }
+
+    pub fn unpremultiply(self, alpha: f64) -> Colour {
+        if alpha == 0.0 {
+            Colour::black()
+        } else {
+            self * (1.0 / alpha)
+        }
+    }
 }
 impl Default for Colour {
     fn default() -> Self {
@@ -142,4 +211,50 @@ mod tests {
         assert!(approx_eq!(f64, sut.green, 0.2, ulps = 2));
         assert!(approx_eq!(f64, sut.blue, 0.04, ulps = 2));
     }
+
+    #[test]
+    pub fn luminance_of_grey_is_its_channel_value() {
+        let sut = Colour::new(0.5, 0.5, 0.5).luminance();
+        assert!(approx_eq!(f64, sut, 0.5, ulps = 2));
+    }
+
+    #[test]
+    pub fn desaturating_bright_red_moves_towards_pink() {
+        let c = Colour::new(2.0, 0.5, 0.5);
+        let sut = c.desaturate_to_gamut();
+        assert!(approx_eq!(f64, sut.red, 1.0, epsilon = 1e-9));
+        assert!(sut.green > 0.5 && sut.green < 1.0);
+        assert!(approx_eq!(f64, sut.green, sut.blue, ulps = 2));
+        assert!(approx_eq!(
+            f64,
+            sut.luminance(),
+            c.luminance(),
+            epsilon = 1e-9
+        ));
+    }
+
+    #[test]
+    pub fn desaturating_leaves_in_gamut_colours_unchanged() {
+        let c = Colour::new(0.9, 0.2, 0.4);
+        assert_eq!(c.desaturate_to_gamut(), c);
+    }
+
+    #[test]
+    pub fn premultiplied_edges_have_no_dark_halo() {
+        // an edge pixel made by averaging an opaque white pixel with a transparent black one
+        let (white, clear) = ((Colour::white(), 1.0), (Colour::black(), 0.0));
+        let edge_alpha = (white.1 + clear.1) / 2.0;
+        let straight_edge = (white.0 + clear.0) * 0.5;
+        let premultiplied_edge =
+            (white.0.premultiply(white.1) + clear.0.premultiply(clear.1)) * 0.5;
+
+        let (straight, _) = straight_edge.blend_over(edge_alpha, Colour::black(), 1.0);
+        let (premultiplied, alpha) =
+            premultiplied_edge.blend_over_premultiplied(edge_alpha, Colour::black(), 1.0);
+
+        // half covered by white, so the edge should be half grey
+        let sut = premultiplied.unpremultiply(alpha);
+        assert!(approx_eq!(f64, sut.red, 0.5));
+        assert!(straight.red < sut.red);
+    }
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use crate::{
     canvas::canvas::Canvas,
     exercises::shared::shared::{degrees_to_radians, save_canvas, set_pixel, Coord},
@@ -5,7 +7,7 @@ use crate::{
     matrix::matrix::{Axis, Matrix},
 };
 
-pub fn create_clock() {
+pub fn create_clock() -> io::Result<()> {
     let canvas_height = 500;
     let canvas_width = 500;
 
@@ -25,7 +27,7 @@ pub fn create_clock() {
         set_pixel(get_point(degrees_to_radians(degree), length), &mut canvas);
     });
 
-    save_canvas("test_clock", &canvas);
+    save_canvas("test_clock", &canvas)
 }
 
 fn get_point(radians: f64, length: f64) -> Coord {
@@ -1,3 +1,5 @@
+use std::io;
+
 use crate::{
     canvas::canvas::Canvas,
     colour::colour::Colour,
@@ -27,7 +29,7 @@ pub fn tick(env: Env, proj: Projectile) -> Projectile {
     return Projectile { position, velocity };
 }
 
-pub fn create_projectile_canvas(file_name: &str) {
+pub fn create_projectile_canvas(file_name: &str) -> io::Result<()> {
     let canvas_height = 500;
     let canvas_width = 1000;
     let mut canvas = Canvas::new(canvas_width, canvas_height);
@@ -64,5 +66,5 @@ pub fn create_projectile_canvas(file_name: &str) {
             file_name
         )
         .as_str(),
-    );
+    )
 }
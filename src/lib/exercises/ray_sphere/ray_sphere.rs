@@ -1,3 +1,5 @@
+use std::io;
+
 use crate::{
     canvas::canvas::Canvas,
     colour::colour::Colour,
@@ -11,7 +13,7 @@ use crate::{
 };
 use rayon::prelude::*;
 
-pub fn render_sphere() {
+pub fn render_sphere() -> io::Result<()> {
     let sphere = Sphere::builder()
         .with_transform(Matrix::scaling(400.0, 400.0, 500.0).translate(500.0, 500.0, 0.0))
         .with_material(Material::with_colour(Colour::new(0.5, 0.2, 1.0)))
@@ -52,7 +54,7 @@ pub fn render_sphere() {
                         &light,
                         eye,
                         normal,
-                        false,
+                        1.0,
                         sphere_trait.to_trait_ref(),
                     )
                 });
@@ -69,7 +71,7 @@ pub fn render_sphere() {
         };
     });
 
-    save_canvas("sphere_test", &canvas);
+    save_canvas("sphere_test", &canvas)
 }
 
 #[cfg(test)]
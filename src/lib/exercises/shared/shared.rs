@@ -1,4 +1,4 @@
-use std::f64::consts::PI;
+use std::{f64::consts::PI, io};
 
 use crate::{canvas::canvas::Canvas, colour::colour::Colour};
 
@@ -19,7 +19,7 @@ pub fn set_pixel_with_colour(coord: Coord, colour: Colour, canvas: &mut Canvas)
     canvas.set_pixel(coord.x as usize, coord.y as usize, colour);
 }
 
-pub fn save_canvas(name: &str, canvas: &Canvas) -> () {
+pub fn save_canvas(name: &str, canvas: &Canvas) -> io::Result<()> {
     canvas.save(format!("/home/harry/Code/ray-tracer-rust/resources/{}.ppm", name).as_str())
 }
 
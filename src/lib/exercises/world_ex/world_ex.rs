@@ -1,5 +1,5 @@
 #![allow(unused)]
-use std::f64::consts::PI;
+use std::{f64::consts::PI, io};
 
 use crate::{
     camera::camera::Camera,
@@ -16,7 +16,7 @@ use crate::{
     world::world::World,
 };
 
-pub fn render_world(size: usize) {
+pub fn render_world(size: usize) -> io::Result<()> {
     let bg_colour = Colour::new(0.5, 0.5, 0.5);
 
     let floor = Plane::builder()
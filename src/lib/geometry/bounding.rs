@@ -0,0 +1,78 @@
+use super::vector::{point, Operations, Tup, Vector};
+
+/// Centre and radius of a sphere enclosing every point, using Ritter's algorithm. The sphere is
+/// close to, but not always exactly, the smallest possible
+pub fn bounding_sphere(points: &[Tup]) -> (Tup, f64) {
+    let Some(&first) = points.first() else {
+        return (point(0.0, 0.0, 0.0), 0.0);
+    };
+    let farthest_from = |from: Tup| {
+        points
+            .iter()
+            .copied()
+            .max_by(|a, b| {
+                a.sub(from)
+                    .magnitude_squared()
+                    .total_cmp(&b.sub(from).magnitude_squared())
+            })
+            .unwrap_or(from)
+    };
+
+    // start with the sphere spanning two points far apart
+    let a = farthest_from(first);
+    let b = farthest_from(a);
+    let mut centre = a.add(b).div(2.0);
+    let mut radius = b.sub(a).length() / 2.0;
+
+    // then grow it just enough to take in each point left outside
+    for &p in points {
+        let distance = p.sub(centre).length();
+        if distance > radius {
+            let grown = (radius + distance) / 2.0;
+            centre = centre.add(p.sub(centre).mul((grown - radius) / distance));
+            radius = grown;
+        }
+    }
+    (centre, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, Operations, Vector},
+        utils::test::ApproxEq,
+    };
+
+    use super::bounding_sphere;
+
+    #[test]
+    fn sphere_contains_every_point() {
+        let points = [
+            point(1.0, 2.0, 3.0),
+            point(-4.0, 0.5, 2.0),
+            point(0.0, -3.0, -1.0),
+            point(2.5, 2.5, -4.0),
+            point(-1.0, 4.0, 0.0),
+            point(3.0, -2.0, 1.5),
+        ];
+        let (centre, radius) = bounding_sphere(&points);
+        for p in points {
+            assert!(p.sub(centre).length() <= radius + 0.00001);
+        }
+    }
+
+    #[test]
+    fn symmetric_points_are_centred_on_their_centroid() {
+        let points = [
+            point(3.0, 1.0, 1.0),
+            point(-1.0, 1.0, 1.0),
+            point(1.0, 3.0, 1.0),
+            point(1.0, -1.0, 1.0),
+            point(1.0, 1.0, 3.0),
+            point(1.0, 1.0, -1.0),
+        ];
+        let (centre, radius) = bounding_sphere(&points);
+        centre.approx_eq(point(1.0, 1.0, 1.0));
+        assert!((radius - 2.0).abs() < 0.00001);
+    }
+}
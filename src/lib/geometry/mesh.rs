@@ -0,0 +1,86 @@
+use super::vector::{vector, Operations, Tup, Vector};
+
+/// Normal of a triangle whose corners are listed anticlockwise when viewed from its front, the
+/// same normal `Triangle` gives them
+pub fn face_normal(vertices: &[Tup], face: &[usize; 3]) -> Tup {
+    let [p1, p2, p3] = face.map(|v| vertices[v]);
+    p3.sub(p1).cross_prod(p2.sub(p1)).norm()
+}
+
+/// Normals for each corner of each face of a mesh which has none of its own. A corner averages
+/// the normals of the faces sharing its vertex, leaving out any which meet this face at more
+/// than `max_angle` radians so that sharp creases stay faceted
+pub fn smooth_normals(vertices: &[Tup], faces: &[[usize; 3]], max_angle: f64) -> Vec<[Tup; 3]> {
+    let face_normals: Vec<Tup> = faces.iter().map(|f| face_normal(vertices, f)).collect();
+    let min_cos = max_angle.cos();
+
+    let mut adjacent_faces: Vec<Vec<usize>> = vec![vec![]; vertices.len()];
+    for (i, face) in faces.iter().enumerate() {
+        for &v in face {
+            adjacent_faces[v].push(i);
+        }
+    }
+
+    faces
+        .iter()
+        .zip(&face_normals)
+        .map(|(face, &normal)| {
+            face.map(|v| {
+                adjacent_faces[v]
+                    .iter()
+                    .map(|&f| face_normals[f])
+                    .filter(|n| n.dot(normal) >= min_cos)
+                    .fold(vector(0.0, 0.0, 0.0), |acc, n| acc.add(n))
+                    .norm()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{
+        geometry::vector::{point, vector, Tup},
+        utils::test::ApproxEq,
+    };
+
+    use super::{face_normal, smooth_normals};
+
+    // two triangles sharing the edge along the z axis, each sloping down away from it by `drop`
+    fn creased_quad(drop: f64) -> (Vec<Tup>, Vec<[usize; 3]>) {
+        let vertices = vec![
+            point(0.0, 0.0, 0.0),
+            point(0.0, 0.0, 1.0),
+            point(1.0, -drop, 0.0),
+            point(-1.0, -drop, 0.0),
+        ];
+        (vertices, vec![[0, 2, 1], [0, 1, 3]])
+    }
+
+    #[test]
+    fn shallow_crease_is_smoothed() {
+        let (vertices, faces) = creased_quad(0.1);
+        let sut = smooth_normals(&vertices, &faces, PI / 6.0);
+
+        // the shared edge takes the average, the outer corners keep their face normal
+        sut[0][0].approx_eq(vector(0.0, 1.0, 0.0));
+        sut[0][2].approx_eq(vector(0.0, 1.0, 0.0));
+        sut[1][0].approx_eq(vector(0.0, 1.0, 0.0));
+        sut[0][1].approx_eq(face_normal(&vertices, &faces[0]));
+    }
+
+    #[test]
+    fn sharp_crease_keeps_face_normals() {
+        let (vertices, faces) = creased_quad(2.0);
+        let sut = smooth_normals(&vertices, &faces, PI / 6.0);
+
+        for (corners, face) in sut.iter().zip(&faces) {
+            let normal = face_normal(&vertices, face);
+            for corner in corners {
+                corner.approx_eq(normal);
+            }
+        }
+    }
+}
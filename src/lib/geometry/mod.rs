@@ -1 +1,10 @@
+/// Tolerance used throughout for comparing floats, and how far rays leaving a surface are
+/// nudged off it so they don't hit it again. Raising it trades shadow acne for light leaking
+/// through thin objects
+pub const EPSILON: f64 = 0.00001;
+
+pub mod bounding;
+pub mod mesh;
+pub mod typed;
+pub mod uv;
 pub mod vector;
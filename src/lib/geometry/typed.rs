@@ -0,0 +1,311 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use super::vector::{point, vector, Operations, Tup, Vector};
+
+/// A position in space. Unlike a bare `Tup` it can't be mistaken for a direction: points only
+/// combine with vectors, and the difference of two points is a vector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point(Tup);
+
+/// A direction and magnitude in space, with the operations that make sense for one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector3(Tup);
+
+impl Point {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self(point(x, y, z))
+    }
+
+    pub fn x(self) -> f64 {
+        self.0 .0
+    }
+
+    pub fn y(self) -> f64 {
+        self.0 .1
+    }
+
+    pub fn z(self) -> f64 {
+        self.0 .2
+    }
+}
+
+impl Vector3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self(vector(x, y, z))
+    }
+
+    pub fn x(self) -> f64 {
+        self.0 .0
+    }
+
+    pub fn y(self) -> f64 {
+        self.0 .1
+    }
+
+    pub fn z(self) -> f64 {
+        self.0 .2
+    }
+
+    pub fn length(self) -> f64 {
+        self.0.length()
+    }
+
+    pub fn magnitude_squared(self) -> f64 {
+        self.0.magnitude_squared()
+    }
+
+    pub fn norm(self) -> Self {
+        Self(self.0.norm())
+    }
+
+    pub fn dot(self, other: Vector3) -> f64 {
+        self.0.dot(other.0)
+    }
+
+    pub fn cross(self, other: Vector3) -> Self {
+        Self(self.0.cross_prod(other.0))
+    }
+
+    /// Mirror image of self about `normal`, which should be of unit length
+    pub fn reflect(self, normal: Vector3) -> Self {
+        Self(self.0.reflect(normal.0))
+    }
+}
+
+/// A tuple whose w doesn't match the type it was converted to, 1 for points and 0 for vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WrongW(pub f64);
+
+// the raw tuples are still what matrices multiply. Converting back checks w, so a point can't
+// become a vector by way of a tuple
+
+impl From<Point> for Tup {
+    fn from(p: Point) -> Self {
+        p.0
+    }
+}
+
+impl From<Vector3> for Tup {
+    fn from(v: Vector3) -> Self {
+        v.0
+    }
+}
+
+impl TryFrom<Tup> for Point {
+    type Error = WrongW;
+
+    fn try_from(t: Tup) -> Result<Self, WrongW> {
+        if t.3 == 1.0 {
+            Ok(Self(t))
+        } else {
+            Err(WrongW(t.3))
+        }
+    }
+}
+
+impl TryFrom<Tup> for Vector3 {
+    type Error = WrongW;
+
+    fn try_from(t: Tup) -> Result<Self, WrongW> {
+        if t.3 == 0.0 {
+            Ok(Self(t))
+        } else {
+            Err(WrongW(t.3))
+        }
+    }
+}
+
+impl Add<Vector3> for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Vector3) -> Point {
+        Point(self.0.add(rhs.0))
+    }
+}
+
+impl Sub<Vector3> for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Vector3) -> Point {
+        Point(self.0.sub(rhs.0))
+    }
+}
+
+impl Sub for Point {
+    type Output = Vector3;
+
+    fn sub(self, rhs: Point) -> Vector3 {
+        Vector3(self.0.sub(rhs.0))
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+
+    fn add(self, rhs: Vector3) -> Vector3 {
+        Vector3(self.0.add(rhs.0))
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+
+    fn sub(self, rhs: Vector3) -> Vector3 {
+        Vector3(self.0.sub(rhs.0))
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+
+    fn neg(self) -> Vector3 {
+        Vector3(self.0.neg())
+    }
+}
+
+impl Mul<f64> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: f64) -> Vector3 {
+        Vector3(self.0.mul(rhs))
+    }
+}
+
+impl Div<f64> for Vector3 {
+    type Output = Vector3;
+
+    fn div(self, rhs: f64) -> Vector3 {
+        Vector3(self.0.div(rhs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector, Tup},
+        matrix::matrix::Matrix,
+        utils::test::ApproxEq,
+    };
+
+    use super::{Point, Vector3, WrongW};
+
+    #[test]
+    fn points_and_vectors_convert_to_tuples_with_their_w() {
+        assert_eq!(Tup::from(Point::new(1.0, 2.0, 3.0)), point(1.0, 2.0, 3.0));
+        assert_eq!(
+            Tup::from(Vector3::new(1.0, 2.0, 3.0)),
+            vector(1.0, 2.0, 3.0)
+        );
+        assert_eq!(
+            Point::try_from(point(1.0, 2.0, 3.0)),
+            Ok(Point::new(1.0, 2.0, 3.0))
+        );
+        assert_eq!(
+            Vector3::try_from(vector(1.0, 2.0, 3.0)),
+            Ok(Vector3::new(1.0, 2.0, 3.0))
+        );
+    }
+
+    #[test]
+    fn tuples_with_the_wrong_w_do_not_convert() {
+        assert_eq!(Vector3::try_from(point(1.0, 2.0, 3.0)), Err(WrongW(1.0)));
+        assert_eq!(Point::try_from(vector(1.0, 2.0, 3.0)), Err(WrongW(0.0)));
+        assert_eq!(Point::try_from((1.0, 2.0, 3.0, 2.0)), Err(WrongW(2.0)));
+    }
+
+    #[test]
+    fn points_and_vectors_are_transformed_through_tuples() {
+        let m = Matrix::translation(5.0, -3.0, 2.0);
+        let p = Point::try_from(m.mul_tup(Point::new(-3.0, 4.0, 5.0).into()));
+        assert_eq!(p, Ok(Point::new(2.0, 1.0, 7.0)));
+        // translation doesn't move vectors
+        let v = Vector3::try_from(m.mul_tup(Vector3::new(-3.0, 4.0, 5.0).into()));
+        assert_eq!(v, Ok(Vector3::new(-3.0, 4.0, 5.0)));
+    }
+
+    #[test]
+    fn vector_and_point_add_to_point() {
+        let result = Point::new(3.0, -2.0, 5.0) + Vector3::new(-2.0, 3.0, 1.0);
+        assert_eq!(result, Point::new(1.0, 1.0, 6.0));
+    }
+
+    #[test]
+    fn vector_and_vector_add_to_vector() {
+        let result = Vector3::new(3.0, -2.0, 5.0) + Vector3::new(-2.0, 3.0, 1.0);
+        assert_eq!(result, Vector3::new(1.0, 1.0, 6.0));
+    }
+
+    #[test]
+    fn point_and_point_subtract_to_vector() {
+        let result = Point::new(3.0, 2.0, 1.0) - Point::new(5.0, 6.0, 7.0);
+        assert_eq!(result, Vector3::new(-2.0, -4.0, -6.0));
+    }
+
+    #[test]
+    fn point_and_vector_subtract_to_point() {
+        let result = Point::new(3.0, 2.0, 1.0) - Vector3::new(5.0, 6.0, 7.0);
+        assert_eq!(result, Point::new(-2.0, -4.0, -6.0));
+    }
+
+    #[test]
+    fn vector_and_vector_subtract_to_vector() {
+        let result = Vector3::new(3.0, 2.0, 1.0) - Vector3::new(5.0, 6.0, 7.0);
+        assert_eq!(result, Vector3::new(-2.0, -4.0, -6.0));
+    }
+
+    #[test]
+    fn vector_can_be_negated() {
+        assert_eq!(-Vector3::new(3.0, 2.0, 1.0), Vector3::new(-3.0, -2.0, -1.0));
+    }
+
+    #[test]
+    fn vector_can_be_scaled() {
+        let v = Vector3::new(1.0, -2.0, 3.0);
+        assert_eq!(v * 3.5, Vector3::new(3.5, -7.0, 10.5));
+        assert_eq!(v * 0.5, Vector3::new(0.5, -1.0, 1.5));
+        assert_eq!(v / 2.0, Vector3::new(0.5, -1.0, 1.5));
+    }
+
+    #[test]
+    fn vector_magnitudes() {
+        for (v, expected) in [
+            (Vector3::new(1.0, 0.0, 0.0), 1.0),
+            (Vector3::new(0.0, 1.0, 0.0), 1.0),
+            (Vector3::new(0.0, 0.0, 1.0), 1.0),
+            (Vector3::new(1.0, 2.0, 3.0), 14.0_f64.sqrt()),
+            (Vector3::new(-1.0, -2.0, -3.0), 14.0_f64.sqrt()),
+        ] {
+            assert_eq!(v.length(), expected);
+            v.magnitude_squared().approx_eq(expected * expected);
+        }
+    }
+
+    #[test]
+    fn normalising_vectors() {
+        assert_eq!(
+            Vector3::new(4.0, 0.0, 0.0).norm(),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+        let sut = Vector3::new(1.0, 2.0, 3.0).norm();
+        sut.approx_eq(Vector3::new(0.26726, 0.53452, 0.80178));
+        sut.length().approx_eq(1.0);
+    }
+
+    #[test]
+    fn dot_and_cross_products() {
+        let (a, b) = (Vector3::new(1.0, 2.0, 3.0), Vector3::new(2.0, 3.0, 4.0));
+        assert_eq!(a.dot(b), 20.0);
+        assert_eq!(a.cross(b), Vector3::new(-1.0, 2.0, -1.0));
+        assert_eq!(b.cross(a), Vector3::new(1.0, -2.0, 1.0));
+    }
+
+    #[test]
+    fn reflecting_vectors() {
+        let sut = Vector3::new(1.0, -1.0, 0.0).reflect(Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(sut, Vector3::new(1.0, 1.0, 0.0));
+
+        let slanted = Vector3::new(2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0, 0.0);
+        let sut = Vector3::new(0.0, -1.0, 0.0).reflect(slanted);
+        sut.approx_eq(Vector3::new(1.0, 0.0, 0.0));
+    }
+}
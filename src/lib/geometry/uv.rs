@@ -0,0 +1,72 @@
+use std::f64::consts::PI;
+
+use crate::geometry::vector::{Tup, Vector};
+
+/// Maps a point in object space onto the unit sphere's surface, with `u` running once around
+/// the y axis and `v` from the south pole at 0 to the north pole at 1
+pub fn spherical_map(point: Tup) -> (f64, f64) {
+    let theta = point.0.atan2(point.2);
+    let radius = (point.0, point.1, point.2, 0.0).length();
+    let phi = (point.1 / radius).acos();
+    let raw_u = theta / (2.0 * PI);
+    // flip u so it increases counter-clockwise looking down from above
+    (1.0 - (raw_u + 0.5), 1.0 - phi / PI)
+}
+
+/// Maps a point on the xz plane onto the unit square, repeating every unit
+pub fn planar_map(point: Tup) -> (f64, f64) {
+    (point.0.rem_euclid(1.0), point.2.rem_euclid(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::vector::point;
+
+    use super::{planar_map, spherical_map};
+
+    fn assert_uv(sut: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (sut.0 - expected.0).abs() < 1e-5,
+            "{:?} != {:?}",
+            sut,
+            expected
+        );
+        assert!(
+            (sut.1 - expected.1).abs() < 1e-5,
+            "{:?} != {:?}",
+            sut,
+            expected
+        );
+    }
+
+    #[test]
+    fn spherical_mapping_on_a_3d_point() {
+        let half = 2.0_f64.sqrt() / 2.0;
+        for (p, expected) in [
+            (point(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (point(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (point(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (point(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (point(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (point(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (point(half, half, 0.0), (0.25, 0.75)),
+        ] {
+            assert_uv(spherical_map(p), expected);
+        }
+    }
+
+    #[test]
+    fn planar_mapping_on_a_3d_point() {
+        for (p, expected) in [
+            (point(0.25, 0.0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0.0, -0.25), (0.25, 0.75)),
+            (point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (point(1.25, 0.0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0.0, -1.75), (0.25, 0.25)),
+            (point(1.0, 0.0, -1.0), (0.0, 0.0)),
+            (point(0.0, 0.0, 0.0), (0.0, 0.0)),
+        ] {
+            assert_uv(planar_map(p), expected);
+        }
+    }
+}
@@ -12,13 +12,51 @@ pub fn vector(x: f64, y: f64, z: f64) -> Tup {
     (x, y, z, 0.0)
 }
 
+/// Bends `incoming` through a surface with the given `normal` according to Snell's law, where
+/// `n_ratio` is the refractive index being left divided by the one being entered. The normal
+/// should face against the incoming direction. Returns `None` on total internal reflection
+pub fn refract(incoming: Tup, normal: Tup, n_ratio: f64) -> Option<Tup> {
+    let cos_i = incoming.neg().dot(normal);
+    let sin2_t = n_ratio.squared() * (1.0 - cos_i.squared());
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(
+        normal
+            .mul(n_ratio * cos_i - cos_t)
+            .add(incoming.mul(n_ratio)),
+    )
+}
+
+/// Two unit tangents perpendicular to `normal` and to each other, returned with the normalised
+/// normal as `(tangent, bitangent, normal)`. Uses the branchless construction of Duff et al,
+/// which stays stable for normals along any axis
+pub fn orthonormal_basis(normal: Tup) -> (Tup, Tup, Tup) {
+    let n = normal.norm();
+    let sign = 1.0_f64.copysign(n.2);
+    let a = -1.0 / (sign + n.2);
+    let b = n.0 * n.1 * a;
+    let tangent = vector(1.0 + sign * n.0.squared() * a, sign * b, -sign * n.0);
+    let bitangent = vector(b, sign + n.1.squared() * a, -n.1);
+    (tangent, bitangent, n)
+}
+
 pub trait Vector {
     type Output;
     fn length(self) -> f64;
+    /// Square of `length`, for comparing lengths without taking a square root
+    fn magnitude_squared(self) -> f64;
+    /// Unit length direction of self, always a vector whatever the w of self
     fn norm(self) -> Self::Output;
     fn dot(self, other: Self::Output) -> f64;
     fn cross_prod(self, other: Self::Output) -> Self::Output;
+    /// Mirror image of self about `normal`, which should be of unit length
     fn reflect(self, normal: Self::Output) -> Self::Output;
+    /// The component of self which lies along `other`
+    fn project_onto(self, other: Self::Output) -> Self::Output;
+    /// The component of self which is perpendicular to `other`
+    fn reject_from(self, other: Self::Output) -> Self::Output;
     fn x(self) -> f64;
     fn y(self) -> f64;
     fn z(self) -> f64;
@@ -37,20 +75,20 @@ impl Vector for Tup {
     type Output = Tup;
 
     fn length(self) -> f64 {
-        (self.0.squared() + self.1.squared() + self.2.squared()).sqrt()
+        self.magnitude_squared().sqrt()
+    }
+
+    fn magnitude_squared(self) -> f64 {
+        self.0.squared() + self.1.squared() + self.2.squared()
     }
 
     fn norm(self) -> Self::Output {
-        (
-            self.0 / self.length(),
-            self.1 / self.length(),
-            self.2 / self.length(),
-            self.3 / self.length(),
-        )
+        let length = self.length();
+        (self.0 / length, self.1 / length, self.2 / length, 0.0)
     }
 
     fn dot(self, other: Self::Output) -> f64 {
-        (self.0 * other.0) + (self.1 * other.1) + (self.2 * other.2) + (self.3 * self.3)
+        (self.0 * other.0) + (self.1 * other.1) + (self.2 * other.2) + (self.3 * other.3)
     }
 
     fn cross_prod(self, other: Self::Output) -> Self::Output {
@@ -66,6 +104,14 @@ impl Vector for Tup {
         self.sub(normal.mul(2.0).mul(self.dot(normal)))
     }
 
+    fn project_onto(self, other: Self::Output) -> Self::Output {
+        other.mul(self.dot(other) / other.dot(other))
+    }
+
+    fn reject_from(self, other: Self::Output) -> Self::Output {
+        self.sub(self.project_onto(other))
+    }
+
     fn x(self) -> f64 {
         self.0
     }
@@ -113,166 +159,113 @@ impl Operations for Tup {
     }
 }
 
-#[cfg(test)]
-mod tests {
-
-    use crate::utils::test::ApproxEq;
-
-    use super::{point, vector, Operations, Vector};
-
-    #[test]
-    fn vector_and_point_add_to_point() {
-        let p1 = point(3.0, -2.0, 5.0);
-        let v1 = vector(-2.0, 3.0, 1.0);
-        let result = p1.add(v1);
-        assert_eq!(result, point(1.0, 1.0, 6.0));
-    }
-
-    #[test]
-    fn vector_and_vector_add_to_vector() {
-        let v1 = vector(3.0, -2.0, 5.0);
-        let v2 = vector(-2.0, 3.0, 1.0);
-        let result = v1.add(v2);
-        assert_eq!(result, vector(1.0, 1.0, 6.0))
-    }
-
-    #[test]
-    fn point_and_point_subtract_to_vector() {
-        let p1 = point(3.0, 2.0, 1.0);
-        let p2 = point(5.0, 6.0, 7.0);
-        let result = p1.sub(p2);
-        assert_eq!(result, vector(-2.0, -4.0, -6.0));
-    }
-
-    #[test]
-    fn point_and_vector_subtract_to_point() {
-        let v1 = vector(5.0, 6.0, 7.0);
-        let p1 = point(3.0, 2.0, 1.0);
-        let result = p1.sub(v1);
-        assert_eq!(result, point(-2.0, -4.0, -6.0));
-    }
-
-    #[test]
-    fn vector_and_vector_subtract_to_vector() {
-        let v1 = vector(3.0, 2.0, 1.0);
-        let v2 = vector(5.0, 6.0, 7.0);
-        let result = v1.sub(v2);
-        assert_eq!(result, vector(-2.0, -4.0, -6.0))
-    }
-
-    #[test]
-    fn vector_can_be_negated() {
-        let v1 = vector(3.0, 2.0, 1.0);
-        let result = v1.neg();
-        assert_eq!(result, vector(-3.0, -2.0, -1.0))
-    }
+pub trait TupExt {
+    /// True when every component differs by no more than `eps`
+    fn close_to(self, other: Self, eps: f64) -> bool;
+}
 
-    #[test]
-    fn vector_and_vector_multiply_to_vector() {
-        let v1 = vector(1.0, -2.0, 3.0);
-        let result = v1.mul(3.5);
-        assert_eq!(vector(3.5, -7.0, 10.5), result)
+impl TupExt for Tup {
+    fn close_to(self, other: Self, eps: f64) -> bool {
+        (self.0 - other.0).abs() <= eps
+            && (self.1 - other.1).abs() <= eps
+            && (self.2 - other.2).abs() <= eps
+            && (self.3 - other.3).abs() <= eps
     }
+}
 
-    #[test]
-    fn vector_and_vector_multiply_with_fraction() {
-        let v1 = vector(1.0, -2.0, 3.0);
-        let result = v1.mul(0.5);
-        assert_eq!(vector(0.5, -1.0, 1.5), result)
-    }
+#[cfg(test)]
+mod tests {
 
-    #[test]
-    fn vector_and_vector_divide_to_vector() {
-        let v1 = vector(1.0, -2.0, 3.0);
-        let result = v1.div(2.0);
-        assert_eq!(vector(0.5, -1.0, 1.5), result)
-    }
+    use crate::utils::test::ApproxEq;
 
-    #[test]
-    fn vector_with_x_of_one_will_have_one_magnitude() {
-        let v1 = vector(1.0, 0.0, 0.0);
-        let result = v1.length();
-        assert_eq!(1.0, result)
-    }
+    use super::{orthonormal_basis, point, refract, vector, Tup, TupExt, Vector};
 
     #[test]
-    fn vector_with_y_of_one_will_have_one_magnitude() {
-        let v1 = vector(0.0, 1.0, 0.0);
-        let result = v1.length();
-        assert_eq!(1.0, result)
+    fn orthonormal_basis_is_perpendicular_and_unit_length() {
+        for normal in [
+            vector(0.0, 1.0, 0.0),
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, 0.0, 1.0),
+            vector(0.0, 0.0, -1.0),
+            vector(1.0, 1.0, 1.0),
+            vector(-0.3, 0.2, -0.9),
+        ] {
+            let (t, b, n) = orthonormal_basis(normal);
+            n.approx_eq(normal.norm());
+            for (u, v) in [(t, b), (t, n), (b, n)] {
+                u.dot(v).approx_eq(0.0);
+            }
+            for v in [t, b, n] {
+                v.length().approx_eq(1.0);
+            }
+        }
     }
 
     #[test]
-    fn vector_with_z_of_one_will_have_one_magnitude() {
-        let v1 = vector(0.0, 0.0, 1.0);
-        let result = v1.length();
-        assert_eq!(1.0, result)
+    fn tuples_round_trip_through_arrays() {
+        // std provides both conversions for four element tuples
+        let p = point(1.0, 2.0, 3.0);
+        let array: [f64; 4] = p.into();
+        assert_eq!(array, [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(Tup::from(array), p);
     }
 
     #[test]
-    fn vector_one_two_three_will_have_sqrt_14_magnitude() {
-        let v1 = vector(1.0, 2.0, 3.0);
-        let result = v1.length();
-        assert_eq!(14.0_f64.sqrt(), result)
+    fn normalising_a_point_gives_a_vector() {
+        let sut = point(4.0, 0.0, 0.0).norm();
+        assert_eq!(sut, vector(1.0, 0.0, 0.0));
+        assert_eq!(sut.3, 0.0);
     }
 
     #[test]
-    fn negative_vector_will_have_correct_magnitude() {
-        let v1 = vector(-1.0, -2.0, -3.0);
-        let result = v1.length();
-        assert_eq!(14.0_f64.sqrt(), result)
+    fn dot_product_uses_both_w_components() {
+        assert_eq!(point(1.0, 2.0, 3.0).dot(point(4.0, 5.0, 6.0)), 33.0);
+        assert_eq!(point(1.0, 2.0, 3.0).dot(vector(4.0, 5.0, 6.0)), 32.0);
     }
 
     #[test]
-    fn norm_vector_of_x_4_will_have_x_1() {
-        let v1 = vector(4.0, 0.0, 0.0);
-        let result = v1.norm();
-        assert_eq!(result, (1.0, 0.0, 0.0, 0.0))
+    fn tuples_within_epsilon_are_close() {
+        let p = point(1.0, 2.0, 3.0);
+        assert!(p.close_to(point(1.0 + 1e-9, 2.0, 3.0 - 1e-9), 1e-6));
+        assert!(!p.close_to(point(1.0, 2.0 + 1e-3, 3.0), 1e-6));
     }
 
     #[test]
-    fn complex_normalisation_is_correct() {
-        let v1 = vector(1.0_f64, 2.0_f64, 3.0_f64);
-        let result = v1.norm();
-        assert_eq!(
-            result,
-            (
-                0.2672612419124244,
-                0.5345224838248488,
-                0.8017837257372732,
-                0.0
-            )
-        )
+    fn refract_perpendicular_ray_is_unchanged() {
+        let incoming = vector(0.0, 0.0, 1.0);
+        let normal = vector(0.0, 0.0, -1.0);
+        let sut = refract(incoming, normal, 1.0 / 1.5).unwrap();
+        sut.approx_eq(vector(0.0, 0.0, 1.0));
     }
 
     #[test]
-    fn dot_of_two_simple_vectors_is_correct() {
-        let v1 = vector(1.0, 2.0, 3.0);
-        let v2 = vector(2.0, 3.0, 4.0);
-        assert_eq!(v1.dot(v2), 20.0)
+    fn refract_bends_towards_normal_entering_denser_medium() {
+        let incoming = vector(2.0_f64.sqrt() / 2.0, -(2.0_f64.sqrt()) / 2.0, 0.0);
+        let normal = vector(0.0, 1.0, 0.0);
+        let sut = refract(incoming, normal, 1.0 / 1.5).unwrap();
+        let sin_t = (2.0_f64.sqrt() / 2.0) / 1.5;
+        sut.approx_eq(vector(sin_t, -(1.0 - sin_t * sin_t).sqrt(), 0.0));
     }
 
     #[test]
-    fn cross_product_of_two_vectors_is_correct() {
-        let v1 = vector(1.0, 2.0, 3.0);
-        let v2 = vector(2.0, 3.0, 4.0);
-        assert_eq!(v1.cross_prod(v2), vector(-1.0, 2.0, -1.0));
-        assert_eq!(v2.cross_prod(v1), vector(1.0, -2.0, 1.0));
+    fn refract_returns_none_on_total_internal_reflection() {
+        let incoming = vector(2.0_f64.sqrt() / 2.0, -(2.0_f64.sqrt()) / 2.0, 0.0);
+        let normal = vector(0.0, 1.0, 0.0);
+        let sut = refract(incoming, normal, 1.5);
+        assert!(sut.is_none());
     }
 
     #[test]
-    fn reflect_vector_approach_at_45() {
-        let v = vector(1.0, -1.0, 0.0);
-        let n = vector(0.0, 1.0, 0.0);
-        let sut = v.reflect(n);
-        assert_eq!(sut, vector(1.0, 1.0, 0.0))
+    fn project_onto_axis_keeps_parallel_component() {
+        let v = vector(2.0, 2.0, 0.0);
+        let sut = v.project_onto(vector(1.0, 0.0, 0.0));
+        assert_eq!(sut, vector(2.0, 0.0, 0.0));
     }
 
     #[test]
-    fn reflect_off_slanted_surface() {
-        let v = vector(0.0, -1.0, 0.0);
-        let n = vector(2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0, 0.0);
-        let sut = v.reflect(n);
-        sut.approx_eq(vector(1.0, 0.0, 0.0));
+    fn reject_from_axis_keeps_perpendicular_component() {
+        let v = vector(2.0, 2.0, 0.0);
+        let sut = v.reject_from(vector(1.0, 0.0, 0.0));
+        assert_eq!(sut, vector(0.0, 2.0, 0.0));
     }
 }
@@ -6,6 +6,7 @@ pub mod geometry;
 pub mod light;
 pub mod material;
 pub mod matrix;
+pub mod obj;
 pub mod ray;
 pub mod shapes;
 pub mod utils;
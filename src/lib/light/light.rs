@@ -1,10 +1,62 @@
 #![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+
 use crate::{
     colour::colour::Colour,
-    geometry::vector::{point, Tup},
+    geometry::vector::{point, vector, Operations, Tup, Vector},
+    utils::math_ext::seeded_random,
 };
 
-#[derive(Clone)]
+use super::serial::TaggedLight;
+
+/// Source of light in a scene
+pub trait TLight: Send + Sync {
+    /// Where the light is taken to come from when shading. Lights infinitely far away give the
+    /// direction toward them as a vector instead
+    fn position(&self) -> Tup;
+
+    fn intensity(&self) -> Colour;
+
+    /// Points on the light that shadow rays are cast toward from `from`, each carrying an equal
+    /// share of the light. Vectors are directions toward parts of the light at infinity
+    fn samples(&self, from: Tup) -> Vec<Tup>;
+
+    /// Whether the samples move with the point they are taken from, so that shadow results for
+    /// one point can't be reused for its neighbours
+    fn jittered(&self) -> bool {
+        false
+    }
+
+    /// The light as its concrete type, for serialising boxed lights
+    fn tagged(&self) -> TaggedLight<'_>;
+
+    /// Unit vector from `point` toward the light
+    fn direction_from(&self, point: Tup) -> Tup {
+        towards(point, self.position()).0
+    }
+
+    /// How far the light is from `point`, infinite for lights with no position
+    fn distance_from(&self, point: Tup) -> f64 {
+        towards(point, self.position()).1
+    }
+
+    /// Share of the light's intensity aimed at `point`, from 0 to 1
+    fn falloff(&self, _point: Tup) -> f64 {
+        1.0
+    }
+}
+
+/// Unit vector from `from` toward `target` and the distance between them. A vector target
+/// (w = 0) is a direction to something infinitely far away
+pub fn towards(from: Tup, target: Tup) -> (Tup, f64) {
+    if target.3 == 0.0 {
+        return (target.norm(), f64::INFINITY);
+    }
+    let v = target.sub(from);
+    (v.norm(), v.length())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PointLight {
     pub position: Tup,
     pub intensity: Colour,
@@ -27,3 +79,309 @@ impl PointLight {
         }
     }
 }
+
+impl TLight for PointLight {
+    fn position(&self) -> Tup {
+        self.position
+    }
+
+    fn tagged(&self) -> TaggedLight<'_> {
+        TaggedLight::Point(self)
+    }
+
+    fn intensity(&self) -> Colour {
+        self.intensity
+    }
+
+    fn samples(&self, _from: Tup) -> Vec<Tup> {
+        vec![self.position]
+    }
+}
+
+/// Light from infinitely far away, such as the sun, which reaches every point from the same
+/// direction with the same intensity
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectionalLight {
+    /// Direction the light travels in
+    pub direction: Tup,
+    pub intensity: Colour,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Tup, intensity: Colour) -> Self {
+        Self {
+            direction: direction.norm(),
+            intensity,
+        }
+    }
+}
+
+impl TLight for DirectionalLight {
+    fn position(&self) -> Tup {
+        let d = self.direction;
+        vector(-d.0, -d.1, -d.2)
+    }
+
+    fn tagged(&self) -> TaggedLight<'_> {
+        TaggedLight::Directional(self)
+    }
+
+    fn intensity(&self) -> Colour {
+        self.intensity
+    }
+
+    fn samples(&self, _from: Tup) -> Vec<Tup> {
+        vec![self.position()]
+    }
+}
+
+/// Point light which only shines within a cone around `direction`. Points within `inner_angle`
+/// of the axis are fully lit, with a smooth falloff to darkness at `outer_angle`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpotLight {
+    pub position: Tup,
+    pub direction: Tup,
+    /// Half angle of the fully lit cone, in radians
+    pub inner_angle: f64,
+    /// Half angle beyond which no light reaches, in radians
+    pub outer_angle: f64,
+    pub intensity: Colour,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Tup,
+        direction: Tup,
+        inner_angle: f64,
+        outer_angle: f64,
+        intensity: Colour,
+    ) -> Self {
+        Self {
+            position,
+            direction: direction.norm(),
+            inner_angle,
+            outer_angle,
+            intensity,
+        }
+    }
+}
+
+impl TLight for SpotLight {
+    fn position(&self) -> Tup {
+        self.position
+    }
+
+    fn tagged(&self) -> TaggedLight<'_> {
+        TaggedLight::Spot(self)
+    }
+
+    fn intensity(&self) -> Colour {
+        self.intensity
+    }
+
+    fn samples(&self, _from: Tup) -> Vec<Tup> {
+        vec![self.position]
+    }
+
+    fn falloff(&self, point: Tup) -> f64 {
+        let cos_angle = point.sub(self.position).norm().dot(self.direction);
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        // smoothstep across the edge of the cone
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Rectangular light which casts soft shadows. The rectangle spans `usteps` cells of `uvec`
+/// and `vsteps` cells of `vvec` from `corner`, and each cell is one shadow sample
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AreaLight {
+    pub corner: Tup,
+    /// Edge of a single cell
+    pub uvec: Tup,
+    pub usteps: usize,
+    /// Edge of a single cell
+    pub vvec: Tup,
+    pub vsteps: usize,
+    pub intensity: Colour,
+    /// Centre of the rectangle
+    pub position: Tup,
+    /// Samples are taken at random within each cell when set, otherwise at the cell centres.
+    /// The same seed always gives the same render
+    pub jitter_seed: Option<u64>,
+}
+
+impl AreaLight {
+    /// Light covering the rectangle with edges `full_uvec` and `full_vvec` from `corner`. With
+    /// zero steps either way the light has no samples, so lights nothing
+    pub fn new(
+        corner: Tup,
+        full_uvec: Tup,
+        usteps: usize,
+        full_vvec: Tup,
+        vsteps: usize,
+        intensity: Colour,
+    ) -> Self {
+        Self {
+            corner,
+            uvec: full_uvec.div(usteps.max(1) as f64),
+            usteps,
+            vvec: full_vvec.div(vsteps.max(1) as f64),
+            vsteps,
+            intensity,
+            position: corner.add(full_uvec.div(2.0)).add(full_vvec.div(2.0)),
+            jitter_seed: None,
+        }
+    }
+
+    pub fn with_jitter(mut self, seed: u64) -> Self {
+        self.jitter_seed = Some(seed);
+        self
+    }
+
+    /// Centre of the cell `u` along and `v` up
+    pub fn point_on(&self, u: usize, v: usize) -> Tup {
+        self.point_in_cell(u, v, 0.5, 0.5)
+    }
+
+    // point offset by fractions of a cell from the cell's corner
+    fn point_in_cell(&self, u: usize, v: usize, du: f64, dv: f64) -> Tup {
+        self.corner
+            .add(self.uvec.mul(u as f64 + du))
+            .add(self.vvec.mul(v as f64 + dv))
+    }
+}
+
+impl TLight for AreaLight {
+    fn position(&self) -> Tup {
+        self.position
+    }
+
+    fn tagged(&self) -> TaggedLight<'_> {
+        TaggedLight::Area(self)
+    }
+
+    fn intensity(&self) -> Colour {
+        self.intensity
+    }
+
+    fn samples(&self, from: Tup) -> Vec<Tup> {
+        let cells = (0..self.vsteps).flat_map(|v| (0..self.usteps).map(move |u| (u, v)));
+        match self.jitter_seed {
+            // seeding from the shaded point spreads the noise instead of repeating it as bands
+            Some(seed) => {
+                let mut random = seeded_random(seed, &[from.0, from.1, from.2]);
+                cells
+                    .map(|(u, v)| self.point_in_cell(u, v, random(), random()))
+                    .collect()
+            }
+            None => cells.map(|(u, v)| self.point_on(u, v)).collect(),
+        }
+    }
+
+    fn jittered(&self) -> bool {
+        self.jitter_seed.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{
+        colour::colour::Colour,
+        geometry::vector::{point, vector},
+        utils::test::ApproxEq,
+    };
+
+    use super::{AreaLight, DirectionalLight, PointLight, SpotLight, TLight};
+
+    fn light() -> AreaLight {
+        AreaLight::new(
+            point(0.0, 0.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            4,
+            vector(0.0, 0.0, 1.0),
+            2,
+            Colour::white(),
+        )
+    }
+
+    #[test]
+    fn point_light_direction_and_distance() {
+        let sut = PointLight::new(point(0.0, 3.0, 4.0), Colour::white());
+        let from = point(0.0, 0.0, 0.0);
+        sut.direction_from(from).approx_eq(vector(0.0, 0.6, 0.8));
+        sut.distance_from(from).approx_eq(5.0);
+    }
+
+    #[test]
+    fn directional_light_is_the_same_from_everywhere_and_infinitely_far() {
+        let sut = DirectionalLight::new(vector(0.0, -2.0, 0.0), Colour::white());
+        for from in [point(0.0, 0.0, 0.0), point(-40.0, 3.0, 17.0)] {
+            sut.direction_from(from).approx_eq(vector(0.0, 1.0, 0.0));
+            assert_eq!(sut.distance_from(from), f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn spotlight_falls_off_across_the_edge_of_its_cone() {
+        let sut = SpotLight::new(
+            point(0.0, 10.0, 0.0),
+            vector(0.0, -1.0, 0.0),
+            PI / 8.0,
+            PI / 4.0,
+            Colour::white(),
+        );
+        // on the axis, 30 degrees off it and just inside, then outside, the 45 degree cone
+        let off_axis = |degrees: f64| point(10.0 * degrees.to_radians().tan(), 0.0, 0.0);
+        sut.falloff(point(0.0, 0.0, 0.0)).approx_eq(1.0);
+        let partial = sut.falloff(off_axis(30.0));
+        assert!(partial > 0.0 && partial < 1.0);
+        let edge = sut.falloff(off_axis(44.0));
+        assert!(edge > 0.0 && edge < partial);
+        assert_eq!(sut.falloff(off_axis(46.0)), 0.0);
+    }
+
+    #[test]
+    fn creating_an_area_light() {
+        let sut = light();
+        assert_eq!(sut.uvec, vector(0.5, 0.0, 0.0));
+        assert_eq!(sut.vvec, vector(0.0, 0.0, 0.5));
+        assert_eq!(sut.position, point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn finding_a_single_point_on_an_area_light() {
+        let sut = light();
+        for (u, v, expected) in [
+            (0, 0, point(0.25, 0.0, 0.25)),
+            (1, 0, point(0.75, 0.0, 0.25)),
+            (0, 1, point(0.25, 0.0, 0.75)),
+            (2, 0, point(1.25, 0.0, 0.25)),
+            (3, 1, point(1.75, 0.0, 0.75)),
+        ] {
+            sut.point_on(u, v).approx_eq(expected);
+        }
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells_and_repeat_with_the_seed() {
+        let from = point(0.0, -5.0, 0.0);
+        let sut = light().with_jitter(7).samples(from);
+        assert_eq!(sut.len(), 8);
+        for (i, p) in sut.iter().enumerate() {
+            let (u, v) = ((i % 4) as f64, (i / 4) as f64);
+            assert!(p.0 >= u * 0.5 && p.0 < (u + 1.0) * 0.5);
+            assert!(p.2 >= v * 0.5 && p.2 < (v + 1.0) * 0.5);
+        }
+        assert_eq!(sut, light().with_jitter(7).samples(from));
+        assert_ne!(sut, light().with_jitter(8).samples(from));
+    }
+}
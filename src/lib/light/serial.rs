@@ -0,0 +1,41 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::light::{AreaLight, DirectionalLight, PointLight, SpotLight, TLight};
+
+/// A light borrowed as its concrete type, so boxed lights can be written out with a `type`
+/// field naming what they are
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum TaggedLight<'a> {
+    Point(&'a PointLight),
+    Directional(&'a DirectionalLight),
+    Spot(&'a SpotLight),
+    Area(&'a AreaLight),
+}
+
+// mirrors `TaggedLight` to read lights back
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum OwnedLight {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Area(AreaLight),
+}
+
+impl Serialize for dyn TLight {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn TLight> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OwnedLight::deserialize(deserializer)? {
+            OwnedLight::Point(l) => Box::new(l),
+            OwnedLight::Directional(l) => Box::new(l),
+            OwnedLight::Spot(l) => Box::new(l),
+            OwnedLight::Area(l) => Box::new(l),
+        })
+    }
+}
@@ -3,17 +3,20 @@
 use std::ops::{Add, Mul};
 
 use num_traits::Pow;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     colour::colour::Colour,
     geometry::vector::{Operations, Tup, Vector},
-    light::light::PointLight,
+    light::light::TLight,
     shapes::shape::TShape,
 };
 
 use super::pattern::TPattern;
 
-#[derive(Debug)]
+// fields left out of serialised materials take their default values
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Material {
     pub ambient: f64,
     pub diffuse: f64,
@@ -22,8 +25,31 @@ pub struct Material {
     pub colour: Colour,
     pattern: Option<Box<dyn TPattern>>,
     pub reflectivity: f64,
-    transparency: f64,
-    refractive_index: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    /// Where transparent objects overlap, the one with the highest priority decides the
+    /// refractive index of the shared volume
+    pub priority: u32,
+    /// Shade both faces of the surface, as if the normal always faced the light. Useful for thin
+    /// surfaces such as planes which can be lit from behind
+    pub two_sided: bool,
+    /// Colour of specular highlights, which metals tint with their own colour
+    pub specular_tint: Colour,
+    /// Fraction of each channel absorbed per unit of distance travelled through the material,
+    /// following the Beer-Lambert law. Black absorbs nothing
+    pub absorption: Colour,
+    /// Tint applied to reflections, independent of the diffuse colour so that coloured
+    /// plastics can still reflect neutrally
+    pub reflection_colour: Colour,
+    /// Colour of light passing through thin parts of the surface from behind, for wax or skin.
+    /// Black, the default, lets nothing through
+    pub translucency: Colour,
+    /// How far light spreads beneath the surface before leaving it, which wraps the diffuse
+    /// term past the point where the surface turns away from the light. Zero turns it off
+    pub subsurface_radius: f64,
+    /// How much of what lies behind the surface is hidden by it, blended without bending the
+    /// ray as refraction would. 1, the default, is fully opaque
+    pub opacity: f64,
 }
 
 pub struct MaterialBuilder {
@@ -36,6 +62,14 @@ pub struct MaterialBuilder {
     pub reflectivity: f64,
     refractive_index: f64,
     transparency: f64,
+    priority: u32,
+    two_sided: bool,
+    specular_tint: Colour,
+    absorption: Colour,
+    reflection_colour: Colour,
+    translucency: Colour,
+    subsurface_radius: f64,
+    opacity: f64,
 }
 
 impl Default for MaterialBuilder {
@@ -50,6 +84,14 @@ impl Default for MaterialBuilder {
             reflectivity: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            priority: 0,
+            two_sided: false,
+            specular_tint: Colour::white(),
+            absorption: Colour::black(),
+            reflection_colour: Colour::white(),
+            translucency: Colour::black(),
+            subsurface_radius: 0.0,
+            opacity: 1.0,
         }
     }
 }
@@ -66,6 +108,14 @@ impl MaterialBuilder {
             reflectivity: self.reflectivity,
             transparency: self.transparency,
             refractive_index: self.refractive_index,
+            priority: self.priority,
+            two_sided: self.two_sided,
+            specular_tint: self.specular_tint,
+            absorption: self.absorption,
+            reflection_colour: self.reflection_colour,
+            translucency: self.translucency,
+            subsurface_radius: self.subsurface_radius,
+            opacity: self.opacity,
         }
     }
 
@@ -105,36 +155,44 @@ impl MaterialBuilder {
         self.refractive_index = refractive_index;
         self
     }
+    pub fn with_priority(mut self, priority: u32) -> MaterialBuilder {
+        self.priority = priority;
+        self
+    }
+    pub fn with_two_sided(mut self, two_sided: bool) -> MaterialBuilder {
+        self.two_sided = two_sided;
+        self
+    }
+    pub fn with_specular_tint(mut self, specular_tint: Colour) -> MaterialBuilder {
+        self.specular_tint = specular_tint;
+        self
+    }
+    pub fn with_absorption(mut self, absorption: Colour) -> MaterialBuilder {
+        self.absorption = absorption;
+        self
+    }
+    pub fn with_reflection_colour(mut self, reflection_colour: Colour) -> MaterialBuilder {
+        self.reflection_colour = reflection_colour;
+        self
+    }
+    pub fn with_translucency(mut self, translucency: Colour) -> MaterialBuilder {
+        self.translucency = translucency;
+        self
+    }
+    pub fn with_subsurface_radius(mut self, subsurface_radius: f64) -> MaterialBuilder {
+        self.subsurface_radius = subsurface_radius;
+        self
+    }
+    pub fn with_opacity(mut self, opacity: f64) -> MaterialBuilder {
+        self.opacity = opacity;
+        self
+    }
 }
 
 impl Material {
     pub fn builder() -> MaterialBuilder {
         MaterialBuilder::default()
     }
-    fn new(
-        ambient: f64,
-        diffuse: f64,
-        specular: f64,
-        shininess: f64,
-        colour: Colour,
-        pattern: Option<Box<dyn TPattern>>,
-        reflectivity: f64,
-        transparency: f64,
-        refractive_index: f64,
-    ) -> Self {
-        Self {
-            ambient,
-            diffuse,
-            specular,
-            shininess,
-            colour,
-            pattern,
-            reflectivity,
-            transparency,
-            refractive_index,
-        }
-    }
-
     pub fn with_colour(colour: Colour) -> Self {
         Self {
             colour,
@@ -142,46 +200,103 @@ impl Material {
         }
     }
 
-    // phong shading model
+    /// Builds a material from physically based parameters, with `metalness` and `roughness`
+    /// in [0, 1], mapped onto the phong model as
+    ///
+    /// - diffuse = 0.9 * (1 - metalness), as metals have no diffuse reflection
+    /// - specular = 0.9 * (1 - roughness / 2)
+    /// - specular tint = white blended toward the base colour by metalness
+    /// - reflectivity = (0.04 + 0.96 * metalness) * (1 - roughness)
+    /// - shininess = 2 / roughness^4 - 2, the phong exponent matching a microfacet lobe,
+    ///   clamped to [1, 10000]
+    pub fn pbr(base_colour: Colour, metalness: f64, roughness: f64) -> Self {
+        let metalness = metalness.clamp(0.0, 1.0);
+        let roughness = roughness.clamp(0.0, 1.0);
+        let shininess = (2.0 / roughness.powi(4) - 2.0).clamp(1.0, 10_000.0);
+
+        Self::builder()
+            .with_colour(base_colour)
+            .with_diffuse(0.9 * (1.0 - metalness))
+            .with_specular(0.9 * (1.0 - roughness / 2.0))
+            .with_specular_tint(Colour::white() * (1.0 - metalness) + base_colour * metalness)
+            .with_reflectivity((0.04 + 0.96 * metalness) * (1.0 - roughness))
+            .with_shininess(shininess)
+            .build()
+    }
+
+    /// Unlit surface colour at a point on the object, taken from the pattern if there is one and
+    /// `colour` otherwise
+    pub fn colour_at(&self, object: Box<&dyn TShape>, point: Tup) -> Colour {
+        self.pattern
+            .as_deref()
+            .and_then(|pattern| pattern.pattern_at_object(object, point))
+            .unwrap_or(self.colour)
+    }
+
+    /// Fraction of light in each channel which survives travelling `distance` through the
+    /// material, `exp(-absorption * distance)`
+    pub fn transmittance(&self, distance: f64) -> Colour {
+        Colour::new(
+            (-self.absorption.red * distance).exp(),
+            (-self.absorption.green * distance).exp(),
+            (-self.absorption.blue * distance).exp(),
+        )
+    }
+
+    /// Phong shading model. `light_intensity` is the unshadowed fraction of the light reaching
+    /// the point, from 0 in full shadow to 1 fully lit. Ambient light is unaffected by shadows
+    /// or by the light's falloff
     pub fn lighting(
         &self,
         illum_point: Tup,
-        light: &PointLight,
+        light: &dyn TLight,
         eye_vec: Tup,
         norm_vec: Tup,
-        in_shadow: bool,
+        light_intensity: f64,
         object: Box<&dyn TShape>,
     ) -> Colour {
-        if in_shadow {
-            return Colour::black();
-        };
-        let colour = self
-            .pattern
-            .as_ref()
-            .and_then(|p| p.pattern_at_object(object, illum_point))
-            .unwrap_or(self.colour);
-
-        let effective_colour = colour.mul(light.intensity);
-        let light_v = light.position.sub(illum_point).norm();
+        let colour = self.colour_at(object, illum_point);
+
+        let effective_colour = colour.mul(light.intensity());
+        let light_v = light.direction_from(illum_point);
         let ambient = effective_colour.mul(self.ambient);
 
-        let light_dot_normal = light_v.dot(norm_vec);
+        let mut norm_vec = norm_vec;
+        let mut light_dot_normal = light_v.dot(norm_vec);
+        if self.two_sided && light_dot_normal < 0.0 {
+            norm_vec = norm_vec.neg();
+            light_dot_normal = -light_dot_normal;
+        }
+
+        // wrap lighting, light scattered under the surface reaches a little way past the
+        // terminator
+        let wrap = self.subsurface_radius;
+        let wrapped_dot_normal = ((light_dot_normal + wrap) / (1.0 + wrap)).max(0.0);
+        let diffuse = effective_colour.mul(self.diffuse).mul(wrapped_dot_normal);
+
+        // light from behind shows through the surface
+        let translucent = effective_colour
+            .mul(self.translucency)
+            .mul((-light_dot_normal).max(0.0));
 
-        let mut diffuse = Colour::black();
         let mut specular = Colour::black();
         if light_dot_normal >= 0.0 {
-            diffuse = effective_colour.mul(self.diffuse).mul(light_dot_normal);
             let reflect_v = light_v.neg().reflect(norm_vec);
             let reflect_dot_eye = reflect_v.dot(eye_vec);
             if reflect_dot_eye <= 0.0 {
                 specular = Colour::black();
             } else {
                 let factor = reflect_dot_eye.pow(self.shininess);
-                specular = light.intensity.mul(self.specular).mul(factor);
+                specular = light
+                    .intensity()
+                    .mul(self.specular_tint)
+                    .mul(self.specular)
+                    .mul(factor);
             }
         }
 
-        ambient.add(diffuse).add(specular)
+        let reaching = light_intensity * light.falloff(illum_point);
+        ambient.add(diffuse.add(translucent).add(specular).mul(reaching))
     }
 }
 
@@ -197,6 +312,14 @@ impl Default for Material {
             reflectivity: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            priority: 0,
+            two_sided: false,
+            specular_tint: Colour::white(),
+            absorption: Colour::black(),
+            reflection_colour: Colour::white(),
+            translucency: Colour::black(),
+            subsurface_radius: 0.0,
+            opacity: 1.0,
         }
     }
 }
@@ -206,14 +329,31 @@ mod tests {
     use crate::{
         colour::colour::Colour,
         geometry::vector::{point, vector},
-        light::light::PointLight,
-        material::pattern::Stripe,
-        shapes::{shape::TShapeBuilder, sphere::Sphere},
+        light::light::{PointLight, SpotLight},
+        material::pattern::{solid, Stripe},
+        shapes::{plane::Plane, shape::TShapeBuilder, sphere::Sphere},
         utils::test::ApproxEq,
     };
 
     use super::Material;
 
+    #[test]
+    fn default_material_is_opaque_with_vacuum_index() {
+        let m = Material::default();
+        assert_eq!(m.transparency, 0.0);
+        assert_eq!(m.refractive_index, 1.0);
+    }
+
+    #[test]
+    fn builder_sets_transparency_and_refractive_index() {
+        let m = Material::builder()
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .build();
+        assert_eq!(m.transparency, 1.0);
+        assert_eq!(m.refractive_index, 1.5);
+    }
+
     #[test]
     fn lighting_with_eye_between_light_and_surface() {
         let m = Material::default();
@@ -227,12 +367,34 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            false,
+            1.0,
             sphere.to_trait_ref(),
         );
         sut.approx_eq(Colour::new(1.9, 1.9, 1.9));
     }
 
+    #[test]
+    fn lighting_outside_a_spotlights_cone_leaves_only_ambient() {
+        let m = Material::default();
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let sphere = Sphere::builder().build_trait();
+        let spot = |direction| {
+            let light =
+                SpotLight::new(point(0.0, 0.0, -10.0), direction, 0.1, 0.2, Colour::white());
+            m.lighting(
+                point(0.0, 0.0, 0.0),
+                &light,
+                eye_v,
+                normal_v,
+                1.0,
+                sphere.to_trait_ref(),
+            )
+        };
+        spot(vector(0.0, 0.0, 1.0)).approx_eq(Colour::new(1.9, 1.9, 1.9));
+        spot(vector(0.0, 1.0, 1.0)).approx_eq(Colour::new(0.1, 0.1, 0.1));
+    }
+
     #[test]
     fn lighting_with_eye_offset_by_45_between_light_and_surface() {
         let m = Material::default();
@@ -247,7 +409,7 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            false,
+            1.0,
             sphere.to_trait_ref(),
         );
         sut.approx_eq(Colour::new(1.0, 1.0, 1.0));
@@ -267,7 +429,7 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            false,
+            1.0,
             sphere.to_trait_ref(),
         );
         sut.approx_eq(Colour::new(0.7364, 0.7364, 0.7364));
@@ -287,7 +449,7 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            false,
+            1.0,
             sphere.to_trait_ref(),
         );
         sut.approx_eq(Colour::new(1.6364, 1.6364, 1.6364));
@@ -307,7 +469,7 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            false,
+            1.0,
             sphere.to_trait_ref(),
         );
         sut.approx_eq(Colour::new(0.1, 0.1, 0.1));
@@ -319,7 +481,7 @@ mod tests {
         let normal_v = vector(0.0, 0.0, -1.0);
         let position = point(0.0, 0.0, 0.0);
         let light = PointLight::new(point(0.0, 0.0, -10.0), Colour::white());
-        let in_shadow = true;
+        let light_intensity = 0.0;
         let material = Material::default();
 
         let sphere = Sphere::builder().build_trait();
@@ -328,10 +490,40 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            in_shadow,
+            light_intensity,
             sphere.to_trait_ref(),
         );
-        result.approx_eq(Colour::new(0.0, 0.0, 0.0));
+        result.approx_eq(Colour::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn partial_shadow_scales_all_but_ambient_light() {
+        let light = PointLight::new(point(0.0, 0.0, -10.0), Colour::white());
+        let sphere = Sphere::builder().build_trait();
+        let lit = |light_intensity| {
+            Material::default().lighting(
+                point(0.0, 0.0, -1.0),
+                &light,
+                vector(0.0, 0.0, -1.0),
+                vector(0.0, 0.0, -1.0),
+                light_intensity,
+                sphere.to_trait_ref(),
+            )
+        };
+        lit(1.0).approx_eq(Colour::new(1.9, 1.9, 1.9));
+        lit(0.5).approx_eq(Colour::new(1.0, 1.0, 1.0));
+        lit(0.0).approx_eq(Colour::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn plain_colour_shades_like_a_solid_pattern() {
+        let colour = Colour::new(0.8, 0.3, 0.1);
+        let plain = Material::with_colour(colour);
+        let patterned = Material::builder().with_pattern(solid(colour)).build();
+        let sphere = Sphere::builder().build_trait();
+        let p = point(0.3, 0.5, -0.2);
+        assert_eq!(plain.colour_at(sphere.to_trait_ref(), p), colour);
+        assert_eq!(patterned.colour_at(sphere.to_trait_ref(), p), colour);
     }
 
     #[test]
@@ -339,7 +531,7 @@ mod tests {
         let eye_v = vector(0.0, 0.0, -1.0);
         let normal_v = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(point(0.0, 0.0, -10.0), Colour::white());
-        let in_shadow = false;
+        let light_intensity = 1.0;
         let material = Material::builder()
             .with_ambient(1.0)
             .with_diffuse(0.0)
@@ -353,7 +545,7 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            in_shadow,
+            light_intensity,
             sphere.to_trait_ref(),
         );
         let c2 = material.lighting(
@@ -361,10 +553,96 @@ mod tests {
             &light,
             eye_v,
             normal_v,
-            in_shadow,
+            light_intensity,
             sphere.to_trait_ref(),
         );
         c1.approx_eq(Colour::new(1.0, 1.0, 1.0));
         c2.approx_eq(Colour::new(0.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn two_sided_plane_is_lit_from_below() {
+        let position = point(0.0, 0.0, 0.0);
+        let eye_v = vector(0.0, 1.0, 0.0);
+        let normal_v = vector(0.0, 1.0, 0.0);
+        let light = PointLight::new(point(0.0, -10.0, 0.0), Colour::white());
+        let plane = Plane::builder().build_trait();
+
+        let one_sided = Material::default().lighting(
+            position,
+            &light,
+            eye_v,
+            normal_v,
+            1.0,
+            plane.to_trait_ref(),
+        );
+        let two_sided = Material::builder().with_two_sided(true).build().lighting(
+            position,
+            &light,
+            eye_v,
+            normal_v,
+            1.0,
+            plane.to_trait_ref(),
+        );
+        one_sided.approx_eq(Colour::new(0.1, 0.1, 0.1));
+        two_sided.approx_eq(Colour::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn metallic_pbr_material_reflects_with_tinted_highlights() {
+        let gold = Colour::new(1.0, 0.8, 0.3);
+        let sut = Material::pbr(gold, 1.0, 0.1);
+        assert!(sut.reflectivity > 0.8);
+        assert_eq!(sut.diffuse, 0.0);
+        sut.specular_tint.approx_eq(gold);
+    }
+
+    #[test]
+    fn rough_pbr_material_has_broad_highlights() {
+        let sut = Material::pbr(Colour::white(), 0.0, 1.0);
+        assert_eq!(sut.shininess, 1.0);
+        assert_eq!(sut.reflectivity, 0.0);
+        assert!(Material::pbr(Colour::white(), 0.0, 0.2).shininess > 100.0);
+    }
+
+    #[test]
+    fn thicker_glass_absorbs_more() {
+        let glass = Material::builder()
+            .with_transparency(1.0)
+            .with_absorption(Colour::new(0.5, 0.1, 0.5))
+            .build();
+        let thin = glass.transmittance(0.2);
+        let thick = glass.transmittance(2.0);
+
+        assert!(thick.red < thin.red && thick.blue < thin.blue);
+        // green is absorbed least so the light is tinted green
+        assert!(thick.green > thick.red);
+        Material::default()
+            .transmittance(10.0)
+            .approx_eq(Colour::white());
+    }
+
+    #[test]
+    fn translucent_material_lit_from_behind_glows_on_the_front() {
+        let light_behind = |m: Material| {
+            m.lighting(
+                point(0.0, 0.0, 0.0),
+                &PointLight::new(point(0.0, 0.0, 10.0), Colour::white()),
+                vector(0.0, 0.0, -1.0),
+                vector(0.0, 0.0, -1.0),
+                1.0,
+                Sphere::builder().build_trait().to_trait_ref(),
+            )
+        };
+        let opaque = light_behind(Material::default());
+        let wax = light_behind(
+            Material::builder()
+                .with_translucency(Colour::new(0.8, 0.5, 0.3))
+                .with_subsurface_radius(0.5)
+                .build(),
+        );
+
+        opaque.approx_eq(Colour::new(0.1, 0.1, 0.1));
+        wax.approx_eq(Colour::new(0.9, 0.6, 0.4));
+    }
 }
@@ -1,2 +1,4 @@
 pub mod material;
+pub mod noise;
 pub mod pattern;
+pub mod serial;
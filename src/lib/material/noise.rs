@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::vector::Tup;
+
+const TABLE_SIZE: usize = 256;
+
+/// Seeded gradient (Perlin) noise. The same seed always produces the same noise field, so only
+/// the seed is serialised
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub struct Noise {
+    seed: u64,
+    permutation: Vec<usize>,
+}
+
+impl From<u64> for Noise {
+    fn from(seed: u64) -> Self {
+        Self::new(seed)
+    }
+}
+
+impl From<Noise> for u64 {
+    fn from(noise: Noise) -> Self {
+        noise.seed
+    }
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table: Vec<usize> = (0..TABLE_SIZE).collect();
+
+        // fisher-yates shuffle driven by a xorshift generator so the table depends only on the seed
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        for i in (1..TABLE_SIZE).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            table.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+
+        // doubled to avoid wrapping indices during lookup
+        let permutation = table.iter().chain(table.iter()).copied().collect();
+        Self { seed, permutation }
+    }
+
+    /// Noise value in roughly [-1, 1] which varies smoothly through space
+    pub fn noise(&self, point: Tup) -> f64 {
+        let cell = |v: f64| (v.floor() as i64).rem_euclid(TABLE_SIZE as i64) as usize;
+        let (xi, yi, zi) = (cell(point.0), cell(point.1), cell(point.2));
+        let (x, y, z) = (
+            point.0 - point.0.floor(),
+            point.1 - point.1.floor(),
+            point.2 - point.2.floor(),
+        );
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Sum of `octaves` layers of absolute noise, each at double the frequency and half the
+    /// amplitude of the last
+    pub fn turbulence(&self, point: Tup, octaves: u32) -> f64 {
+        (0..octaves)
+            .map(|octave| {
+                let frequency = 2.0_f64.powi(octave as i32);
+                let scaled = (
+                    point.0 * frequency,
+                    point.1 * frequency,
+                    point.2 * frequency,
+                    point.3,
+                );
+                self.noise(scaled).abs() / frequency
+            })
+            .sum()
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// dot product of the distance vector with one of twelve gradient directions picked by the hash
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::vector::point;
+
+    use super::Noise;
+
+    #[test]
+    fn noise_is_zero_on_lattice_points() {
+        let noise = Noise::new(1);
+        assert_eq!(noise.noise(point(1.0, 2.0, 3.0)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_deterministic_for_a_seed() {
+        let a = Noise::new(42);
+        let b = Noise::new(42);
+        let p = point(0.3, 1.7, -2.2);
+        assert_eq!(a.noise(p), b.noise(p));
+    }
+
+    #[test]
+    fn noise_is_bounded() {
+        let noise = Noise::new(7);
+        for i in 0..100 {
+            let t = i as f64 * 0.37;
+            let sut = noise.noise(point(t, t * 0.5, -t));
+            assert!((-1.5..=1.5).contains(&sut));
+        }
+    }
+}
@@ -1,26 +1,88 @@
 #![allow(unused)]
 use std::fmt::Debug;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    colour::colour::Colour, geometry::vector::Tup, matrix::matrix::Matrix, shapes::shape::TShape,
+    canvas::canvas::Canvas,
+    colour::colour::Colour,
+    geometry::{
+        uv::planar_map,
+        vector::{Operations, Tup},
+    },
+    matrix::matrix::Matrix,
+    shapes::shape::TShape,
 };
 
+use super::{noise::Noise, serial::TaggedPattern};
+
+// seed for the noise behind the procedural patterns so renders are repeatable
+const PROCEDURAL_SEED: u64 = 1983;
+
 pub trait TPattern: Send + Sync + Debug {
     fn transform(&self) -> &Matrix;
+    /// Copy of the pattern, so one pattern can be handed to several materials
+    fn clone_box(&self) -> Box<dyn TPattern>;
+    /// The pattern as its concrete type, for serialising boxed patterns
+    fn tagged(&self) -> TaggedPattern<'_>;
     fn pattern_at(&self, point: Tup) -> Colour;
     fn pattern_at_object(&self, object: Box<&dyn TShape>, world_point: Tup) -> Option<Colour> {
         object
-            .transform()
-            .inverse()
-            .map(|m| m.mul_tup(world_point))
+            .world_to_object(world_point)
             .and_then(|o| self.transform().inverse().map(|p| p.mul_tup(o)))
             .map(|p| self.pattern_at(p))
     }
 }
 
+impl Clone for Box<dyn TPattern> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// --- Solid Colour --- ///
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolidColour {
+    colour: Colour,
+    transform: Matrix,
+}
+
+impl TPattern for SolidColour {
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::SolidColour(self)
+    }
+
+    fn pattern_at(&self, _point: Tup) -> Colour {
+        self.colour
+    }
+
+    // the colour is the same everywhere, so there's no need to find the point in pattern space
+    fn pattern_at_object(&self, _object: Box<&dyn TShape>, _world_point: Tup) -> Option<Colour> {
+        Some(self.colour)
+    }
+}
+
+impl SolidColour {
+    pub fn new(colour: Colour) -> Self {
+        Self {
+            colour,
+            transform: Matrix::ident(),
+        }
+    }
+}
+
 /// --- Stripe --- ///
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Stripe {
     a: Colour,
     b: Colour,
@@ -32,6 +94,14 @@ impl TPattern for Stripe {
         &self.transform
     }
 
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Stripe(self)
+    }
+
     fn pattern_at(&self, point: Tup) -> Colour {
         let check = point.0.floor() % 2.0 == 0.0;
         if check {
@@ -60,7 +130,7 @@ impl Stripe {
 
 /// --- Gradient --- ///
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Gradient {
     a: Colour,
     b: Colour,
@@ -82,6 +152,14 @@ impl TPattern for Gradient {
         &self.transform
     }
 
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Gradient(self)
+    }
+
     fn pattern_at(&self, point: Tup) -> Colour {
         let distance = self.b - self.a;
         let fraction = point.0 - point.0.floor();
@@ -96,7 +174,7 @@ impl Gradient {
 }
 /// --- Ring --- ///
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ring {
     a: Colour,
     b: Colour,
@@ -118,8 +196,16 @@ impl TPattern for Ring {
         &self.transform
     }
 
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Ring(self)
+    }
+
     fn pattern_at(&self, point: Tup) -> Colour {
-        let check = ((point.0 + point.2).sqrt().floor() % 2.0) == 0.0;
+        let check = (point.0.powi(2) + point.2.powi(2)).sqrt().floor() % 2.0 == 0.0;
         if check {
             self.a
         } else {
@@ -135,7 +221,7 @@ impl Ring {
 }
 /// --- Checker --- ///
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Checker {
     a: Colour,
     b: Colour,
@@ -157,6 +243,14 @@ impl TPattern for Checker {
         &self.transform
     }
 
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Checker(self)
+    }
+
     fn pattern_at(&self, point: Tup) -> Colour {
         let check = (point.0.floor() + point.1.floor() + point.2.floor()) % 2.0 == 0.0;
         if check {
@@ -173,17 +267,319 @@ impl Checker {
     }
 }
 
+// colour of a wrapped pattern at a point in its parent's pattern space
+fn child_at(child: &dyn TPattern, point: Tup) -> Colour {
+    child
+        .transform()
+        .inverse()
+        .map_or(Colour::black(), |i| child.pattern_at(i.mul_tup(point)))
+}
+
+// texture coordinates of a world point, found with the object's uv mapping after moving the
+// point into the pattern's space
+fn object_uv(pattern: &dyn TPattern, object: &dyn TShape, world_point: Tup) -> Option<(f64, f64)> {
+    let local = object.world_to_object(world_point)?;
+    let pattern_point = pattern.transform().inverse()?.mul_tup(local);
+    Some(object.uv_at(pattern_point))
+}
+
+/// --- UV Checkers --- ///
+
+// checkers laid out in texture space, so they wrap around a shape using its uv mapping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UvCheckers {
+    width: f64,
+    height: f64,
+    a: Colour,
+    b: Colour,
+    transform: Matrix,
+}
+
+impl TPattern for UvCheckers {
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::UvCheckers(self)
+    }
+
+    fn pattern_at(&self, point: Tup) -> Colour {
+        self.uv_pattern_at(planar_map(point))
+    }
+
+    fn pattern_at_object(&self, object: Box<&dyn TShape>, world_point: Tup) -> Option<Colour> {
+        object_uv(self, *object, world_point).map(|uv| self.uv_pattern_at(uv))
+    }
+}
+
+impl UvCheckers {
+    /// `width` checkers across u and `height` along v
+    pub fn new(width: usize, height: usize, a: Colour, b: Colour, transform: Matrix) -> Self {
+        Self {
+            width: width as f64,
+            height: height as f64,
+            a,
+            b,
+            transform,
+        }
+    }
+
+    pub fn uv_pattern_at(&self, (u, v): (f64, f64)) -> Colour {
+        let cell = (u * self.width).floor() + (v * self.height).floor();
+        if cell % 2.0 == 0.0 {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+/// --- Image Map --- ///
+
+// wraps an image around a shape using its uv mapping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageMap {
+    image: Canvas,
+    transform: Matrix,
+}
+
+impl TPattern for ImageMap {
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::ImageMap(self)
+    }
+
+    fn pattern_at(&self, point: Tup) -> Colour {
+        self.uv_pattern_at(planar_map(point))
+    }
+
+    fn pattern_at_object(&self, object: Box<&dyn TShape>, world_point: Tup) -> Option<Colour> {
+        object_uv(self, *object, world_point).map(|uv| self.uv_pattern_at(uv))
+    }
+}
+
+impl ImageMap {
+    pub fn new(image: Canvas, transform: Matrix) -> Self {
+        Self { image, transform }
+    }
+
+    /// Pixel nearest to the texture coordinates. v runs up the image, so v = 1 is the top row
+    pub fn uv_pattern_at(&self, (u, v): (f64, f64)) -> Colour {
+        let pixel = |t: f64, size: usize| {
+            let last = size.saturating_sub(1);
+            ((t * last as f64).round().max(0.0) as usize).min(last)
+        };
+        let x = pixel(u, self.image.width);
+        let y = pixel(1.0 - v, self.image.height);
+        self.image.get_pixel(x, y).unwrap_or(Colour::black())
+    }
+}
+
+/// --- Blend --- ///
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    Average,
+    Multiply,
+}
+
+// overlays two patterns. Each child keeps its own transform, applied on top of the blend's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blend {
+    a: Box<dyn TPattern>,
+    b: Box<dyn TPattern>,
+    mode: BlendMode,
+    transform: Matrix,
+}
+
+impl TPattern for Blend {
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Blend(self)
+    }
+
+    fn pattern_at(&self, point: Tup) -> Colour {
+        let (a, b) = (
+            child_at(self.a.as_ref(), point),
+            child_at(self.b.as_ref(), point),
+        );
+        match self.mode {
+            BlendMode::Average => (a + b) * 0.5,
+            BlendMode::Multiply => a * b,
+        }
+    }
+}
+
+impl Blend {
+    pub fn new(
+        a: Box<dyn TPattern>,
+        b: Box<dyn TPattern>,
+        mode: BlendMode,
+        transform: Matrix,
+    ) -> Self {
+        Self {
+            a,
+            b,
+            mode,
+            transform,
+        }
+    }
+}
+
+/// --- Perturb --- ///
+
+// jitters the lookup point with noise before asking the wrapped pattern, so straight edges wobble
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Perturb {
+    inner: Box<dyn TPattern>,
+    /// Furthest the lookup point moves along each axis is roughly this much
+    scale: f64,
+    transform: Matrix,
+    noise: Noise,
+}
+
+impl TPattern for Perturb {
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn clone_box(&self) -> Box<dyn TPattern> {
+        Box::new(self.clone())
+    }
+
+    fn tagged(&self) -> TaggedPattern<'_> {
+        TaggedPattern::Perturb(self)
+    }
+
+    fn pattern_at(&self, point: Tup) -> Colour {
+        // sampling the field at offset points decorrelates the jitter on each axis
+        let jitter =
+            |offset: f64| self.noise.noise(point.add((offset, offset, offset, 0.0))) * self.scale;
+        let jittered = (
+            point.0 + jitter(0.0),
+            point.1 + jitter(31.7),
+            point.2 + jitter(67.3),
+            point.3,
+        );
+        child_at(self.inner.as_ref(), jittered)
+    }
+}
+
+impl Perturb {
+    pub fn new(inner: Box<dyn TPattern>, scale: f64, seed: u64, transform: Matrix) -> Self {
+        Self {
+            inner,
+            scale,
+            transform,
+            noise: Noise::new(seed),
+        }
+    }
+}
+
+/// A single colour as a pattern, ready to hand to a material
+pub fn solid(colour: Colour) -> Box<dyn TPattern> {
+    Box::new(SolidColour::new(colour))
+}
+
+/// Wood grain with default colours, ready to hand to a material. Narrow rings around the y axis
+/// wobble with noise so the grain isn't perfectly round
+pub fn wood() -> Box<dyn TPattern> {
+    let rings = Ring::new(
+        Colour::new(0.76, 0.55, 0.33),
+        Colour::new(0.45, 0.27, 0.12),
+        Matrix::scaling(0.25, 1.0, 0.25),
+    );
+    Box::new(Perturb::new(
+        Box::new(rings),
+        0.08,
+        PROCEDURAL_SEED,
+        Matrix::ident(),
+    ))
+}
+
+/// Marble with default colours, ready to hand to a material. Gradient bands along x are pushed
+/// about by noise into veins
+pub fn marble() -> Box<dyn TPattern> {
+    let bands = Gradient::new(
+        Colour::new(0.95, 0.95, 0.93),
+        Colour::new(0.3, 0.32, 0.35),
+        Matrix::scaling(0.4, 1.0, 1.0),
+    );
+    Box::new(Perturb::new(
+        Box::new(bands),
+        0.5,
+        PROCEDURAL_SEED,
+        Matrix::ident(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
+        canvas::canvas::Canvas,
         colour::colour::Colour,
-        geometry::vector::point,
+        geometry::vector::{point, Tup},
         material::pattern::{Checker, Ring, TPattern},
         matrix::matrix::Matrix,
         shapes::{shape::TShapeBuilder, sphere::Sphere},
+        utils::test::ApproxEq,
     };
 
-    use super::{Gradient, Stripe};
+    use super::{
+        marble, solid, wood, Blend, BlendMode, Gradient, ImageMap, Perturb, Stripe, UvCheckers,
+    };
+
+    #[test]
+    fn solid_pattern_is_its_colour_everywhere() {
+        let colour = Colour::new(0.2, 0.4, 0.6);
+        let pattern = solid(colour);
+        for p in [
+            point(0.0, 0.0, 0.0),
+            point(1.5, -3.0, 0.25),
+            point(-100.0, 7.0, 42.0),
+        ] {
+            assert_eq!(pattern.pattern_at(p), colour);
+        }
+        let object = Sphere::builder()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .build_trait();
+        let sut = pattern.pattern_at_object(object.to_trait_ref(), point(1.5, 0.0, 0.0));
+        assert_eq!(sut, Some(colour));
+    }
+
+    #[test]
+    fn cloned_pattern_matches_the_original() {
+        let pattern: Box<dyn TPattern> = Box::new(Stripe::new(
+            Colour::white(),
+            Colour::black(),
+            Matrix::scaling(0.5, 1.0, 1.0),
+        ));
+        let copy = pattern.clone();
+        for i in -10..10 {
+            let p = point(i as f64 * 0.3, 0.0, 0.0);
+            assert_eq!(copy.pattern_at(p), pattern.pattern_at(p));
+        }
+        assert_eq!(copy.transform(), pattern.transform());
+    }
 
     #[test]
     fn stripe_pattern_is_constant_in_y() {
@@ -277,6 +673,12 @@ mod tests {
             pattern.pattern_at(point(0.708, 0.0, 0.708)),
             Colour::black()
         );
+        // rings are round, so they carry on where x and z are negative
+        assert_eq!(
+            pattern.pattern_at(point(-0.708, 0.0, -0.708)),
+            Colour::black()
+        );
+        assert_eq!(pattern.pattern_at(point(-2.5, 0.0, 0.0)), Colour::white());
     }
     #[test]
     fn checker_should_repeat_in_x() {
@@ -299,4 +701,167 @@ mod tests {
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.99)), Colour::white());
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 1.01)), Colour::black());
     }
+
+    #[test]
+    fn checker_pattern_in_2d() {
+        let pattern = UvCheckers::new(2, 2, Colour::black(), Colour::white(), Matrix::ident());
+        for (uv, expected) in [
+            ((0.0, 0.0), Colour::black()),
+            ((0.5, 0.0), Colour::white()),
+            ((0.0, 0.5), Colour::white()),
+            ((0.5, 0.5), Colour::black()),
+            ((1.0, 1.0), Colour::black()),
+        ] {
+            assert_eq!(pattern.uv_pattern_at(uv), expected);
+        }
+    }
+
+    #[test]
+    fn uv_checkers_follow_the_spheres_mapping() {
+        let object = Sphere::builder()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .build_trait();
+        let pattern = UvCheckers::new(16, 8, Colour::black(), Colour::white(), Matrix::ident());
+        // points on the unit sphere, doubled to land on the scaled one
+        let at = |p: Tup| {
+            let p = point(p.0 * 2.0, p.1 * 2.0, p.2 * 2.0);
+            pattern.pattern_at_object(object.to_trait_ref(), p).unwrap()
+        };
+        for (p, expected) in [
+            (point(0.4315, 0.4670, 0.7719), Colour::white()),
+            (point(-0.9654, 0.2552, -0.0534), Colour::black()),
+            (point(0.1039, 0.7090, 0.6975), Colour::white()),
+            (point(-0.4986, -0.7856, -0.3663), Colour::black()),
+            (point(-0.0317, -0.9395, 0.3411), Colour::black()),
+            (point(0.4809, -0.7721, 0.4154), Colour::black()),
+            (point(0.0285, -0.9612, -0.2745), Colour::black()),
+            (point(-0.5734, -0.2162, -0.7903), Colour::white()),
+            (point(0.7688, -0.1470, 0.6223), Colour::black()),
+            (point(-0.7652, 0.2175, 0.6060), Colour::black()),
+        ] {
+            assert_eq!(at(p), expected);
+        }
+    }
+
+    #[test]
+    fn image_map_samples_the_corners_right_way_up() {
+        // each pixel's red and green channels encode its column and row
+        let mut ppm = String::from("P3\n10 10\n9\n");
+        for y in 0..10 {
+            for x in 0..10 {
+                ppm.push_str(&format!("{} {} 0\n", x, y));
+            }
+        }
+        let image = Canvas::from_ppm(&ppm).unwrap();
+        let pattern = ImageMap::new(image, Matrix::ident());
+        let pixel = |x: f64, y: f64| Colour::new(x / 9.0, y / 9.0, 0.0);
+        for (uv, expected) in [
+            ((0.0, 0.0), pixel(0.0, 9.0)),
+            ((1.0, 0.0), pixel(9.0, 9.0)),
+            ((0.0, 1.0), pixel(0.0, 0.0)),
+            ((1.0, 1.0), pixel(9.0, 0.0)),
+            ((0.5, 0.5), pixel(5.0, 5.0)),
+        ] {
+            pattern.uv_pattern_at(uv).approx_eq(expected);
+        }
+    }
+
+    #[test]
+    fn wood_and_marble_vary_across_space() {
+        for pattern in [wood(), marble()] {
+            let colours: Vec<Colour> = (0..20)
+                .map(|i| i as f64 * 0.13)
+                .map(|t| pattern.pattern_at(point(t, t * 0.5, t * 0.7)))
+                .collect();
+            assert!(colours.iter().any(|c| *c != colours[0]));
+        }
+    }
+
+    #[test]
+    fn wood_and_marble_are_deterministic() {
+        let p = point(0.35, 1.2, -0.8);
+        assert_eq!(wood().pattern_at(p), wood().pattern_at(p));
+        assert_eq!(marble().pattern_at(p), marble().pattern_at(p));
+    }
+
+    #[test]
+    fn blend_of_two_solid_colours_is_their_average() {
+        let pattern = Blend::new(
+            solid(Colour::new(1.0, 0.2, 0.0)),
+            solid(Colour::new(0.0, 0.6, 0.5)),
+            BlendMode::Average,
+            Matrix::ident(),
+        );
+        assert_eq!(
+            pattern.pattern_at(point(0.3, 0.0, 0.0)),
+            Colour::new(0.5, 0.4, 0.25)
+        );
+    }
+
+    #[test]
+    fn blend_can_multiply_its_colours() {
+        let pattern = Blend::new(
+            solid(Colour::new(1.0, 0.5, 0.0)),
+            solid(Colour::new(0.5, 0.5, 1.0)),
+            BlendMode::Multiply,
+            Matrix::ident(),
+        );
+        assert_eq!(
+            pattern.pattern_at(point(0.3, 0.0, 0.0)),
+            Colour::new(0.5, 0.25, 0.0)
+        );
+    }
+
+    #[test]
+    fn blend_respects_child_and_object_transforms() {
+        let object = Sphere::builder()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .build_trait();
+        let stripes = Box::new(Stripe::new(
+            Colour::white(),
+            Colour::black(),
+            Matrix::scaling(0.5, 1.0, 1.0),
+        ));
+        let pattern = Blend::new(
+            stripes,
+            solid(Colour::white()),
+            BlendMode::Average,
+            Matrix::ident(),
+        );
+        // object space x = 0.75, which the child's scaling moves into its second stripe
+        let colour = pattern.pattern_at_object(object.to_trait_ref(), point(1.5, 0.0, 0.0));
+        assert_eq!(colour.unwrap(), Colour::new(0.5, 0.5, 0.5));
+        let colour = pattern.pattern_at_object(object.to_trait_ref(), point(0.5, 0.0, 0.0));
+        assert_eq!(colour.unwrap(), Colour::white());
+    }
+
+    #[test]
+    fn perturbing_by_zero_matches_the_inner_pattern() {
+        let pattern = Perturb::new(Box::new(Stripe::default()), 0.0, 3, Matrix::ident());
+        for i in -20..20 {
+            let p = point(i as f64 * 0.1, 0.37, -0.52);
+            assert_eq!(pattern.pattern_at(p), Stripe::default().pattern_at(p));
+        }
+    }
+
+    #[test]
+    fn perturbing_moves_a_stripe_boundary_by_a_bounded_amount() {
+        let pattern = Perturb::new(Box::new(Stripe::default()), 0.1, 3, Matrix::ident());
+        let colours: Vec<Colour> = (0..200)
+            .map(|i| point(0.8 + i as f64 * 0.002, i as f64 * 0.05, 0.3))
+            .map(|p| pattern.pattern_at(p))
+            .collect();
+        let unperturbed = |i: usize| {
+            if i < 100 {
+                Colour::white()
+            } else {
+                Colour::black()
+            }
+        };
+        // noise stays within 1.5, so points more than 0.15 from x = 1 keep their stripe
+        for i in (0..25).chain(175..200) {
+            assert_eq!(colours[i], unperturbed(i));
+        }
+        assert!((0..200).any(|i| colours[i] != unperturbed(i)));
+    }
 }
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::pattern::{
+    Blend, Checker, Gradient, ImageMap, Perturb, Ring, SolidColour, Stripe, TPattern, UvCheckers,
+};
+
+/// A pattern borrowed as its concrete type, so boxed patterns can be written out with a `type`
+/// field naming what they are
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum TaggedPattern<'a> {
+    SolidColour(&'a SolidColour),
+    Stripe(&'a Stripe),
+    Gradient(&'a Gradient),
+    Ring(&'a Ring),
+    Checker(&'a Checker),
+    UvCheckers(&'a UvCheckers),
+    ImageMap(&'a ImageMap),
+    Blend(&'a Blend),
+    Perturb(&'a Perturb),
+}
+
+// mirrors `TaggedPattern` to read patterns back
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum OwnedPattern {
+    SolidColour(SolidColour),
+    Stripe(Stripe),
+    Gradient(Gradient),
+    Ring(Ring),
+    Checker(Checker),
+    UvCheckers(UvCheckers),
+    ImageMap(ImageMap),
+    Blend(Blend),
+    Perturb(Perturb),
+}
+
+impl Serialize for dyn TPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn TPattern> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OwnedPattern::deserialize(deserializer)? {
+            OwnedPattern::SolidColour(p) => Box::new(p),
+            OwnedPattern::Stripe(p) => Box::new(p),
+            OwnedPattern::Gradient(p) => Box::new(p),
+            OwnedPattern::Ring(p) => Box::new(p),
+            OwnedPattern::Checker(p) => Box::new(p),
+            OwnedPattern::UvCheckers(p) => Box::new(p),
+            OwnedPattern::ImageMap(p) => Box::new(p),
+            OwnedPattern::Blend(p) => Box::new(p),
+            OwnedPattern::Perturb(p) => Box::new(p),
+        })
+    }
+}
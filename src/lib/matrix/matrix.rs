@@ -1,34 +1,177 @@
 #![allow(dead_code, unused_variables)]
 
-use crate::geometry::vector::{Operations, Tup, Vector};
+use std::{
+    fmt,
+    ops::Mul,
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{
+    vector::{vector, Operations, Tup, Vector},
+    EPSILON,
+};
 
 type MatrixVec = Vec<Vec<f64>>;
 
-#[derive(PartialEq, Debug, Clone)]
+// serialised as its rows, which are checked with `try_new` when read back
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "MatrixVec", into = "MatrixVec")]
 pub struct Matrix {
     matrix: MatrixVec,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixError {
+    /// The matrix has no rows, or its rows have no columns
+    Empty,
+    /// A row's length differs from the first row's
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Empty => write!(f, "matrix is empty"),
+            MatrixError::Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "matrix row {} has {} columns, expected {}",
+                row, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
 pub enum Axis {
     X,
     Y,
     Z,
 }
+impl From<[[f64; 4]; 4]> for Matrix {
+    fn from(rows: [[f64; 4]; 4]) -> Self {
+        Matrix::from_array(rows)
+    }
+}
+
 impl Default for Matrix {
     fn default() -> Self {
         Matrix::ident()
     }
 }
 
+// the operators let transforms be composed as `a * b * c`, which applies `c` first
+
+impl Mul<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        Matrix::mul(self, rhs)
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Matrix) -> Matrix {
+        Matrix::mul(&self, &rhs)
+    }
+}
+
+impl Mul<&Matrix> for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        Matrix::mul(&self, rhs)
+    }
+}
+
+impl Mul<Tup> for &Matrix {
+    type Output = Tup;
+
+    fn mul(self, rhs: Tup) -> Tup {
+        self.mul_tup(rhs)
+    }
+}
+
+impl Mul<Tup> for Matrix {
+    type Output = Tup;
+
+    fn mul(self, rhs: Tup) -> Tup {
+        self.mul_tup(rhs)
+    }
+}
+
+impl TryFrom<MatrixVec> for Matrix {
+    type Error = MatrixError;
+
+    fn try_from(matrix: MatrixVec) -> Result<Self, MatrixError> {
+        Self::try_new(matrix)
+    }
+}
+
+impl From<Matrix> for MatrixVec {
+    fn from(m: Matrix) -> Self {
+        m.matrix
+    }
+}
+
 impl Matrix {
     pub fn new(matrix: MatrixVec) -> Self {
+        debug_assert_eq!(Self::check_shape(&matrix), Ok(()));
         Self { matrix }
     }
 
+    /// Checks the rows are non-empty and all the same length, such as for matrices read from
+    /// scene files
+    pub fn try_new(matrix: MatrixVec) -> Result<Self, MatrixError> {
+        Self::check_shape(&matrix)?;
+        Ok(Self { matrix })
+    }
+
+    fn check_shape(matrix: &MatrixVec) -> Result<(), MatrixError> {
+        let expected = matrix.first().map_or(0, |row| row.len());
+        if expected == 0 {
+            return Err(MatrixError::Empty);
+        }
+        match matrix.iter().position(|row| row.len() != expected) {
+            Some(row) => Err(MatrixError::Ragged {
+                row,
+                expected,
+                found: matrix[row].len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.matrix[0].len()
     }
 
+    pub fn from_array(rows: [[f64; 4]; 4]) -> Self {
+        Self::new(rows.iter().map(|row| row.to_vec()).collect())
+    }
+
+    /// Rows of the matrix as fixed size arrays, `None` unless the matrix is 4x4
+    pub fn to_array(&self) -> Option<[[f64; 4]; 4]> {
+        let rows: Vec<[f64; 4]> = self
+            .matrix
+            .iter()
+            .map(|row| row.as_slice().try_into().ok())
+            .collect::<Option<_>>()?;
+        rows.try_into().ok()
+    }
+
     pub fn ident() -> Self {
         Self {
             matrix: vec![
@@ -40,6 +183,18 @@ impl Matrix {
         }
     }
 
+    /// Transform which places the eye at `from`, looking toward `to` with `up` roughly upward
+    ///
+    /// ```
+    /// use module_lib::{geometry::vector::{point, vector}, matrix::matrix::Matrix};
+    ///
+    /// let sut = Matrix::view_transform(
+    ///     point(0.0, 0.0, 8.0),
+    ///     point(0.0, 0.0, 0.0),
+    ///     vector(0.0, 1.0, 0.0),
+    /// );
+    /// assert_eq!(sut, Matrix::translation(0.0, 0.0, -8.0));
+    /// ```
     pub fn view_transform(from: Tup, to: Tup, up: Tup) -> Self {
         let forward = (to.sub(from)).norm();
         let upn = up.norm();
@@ -68,7 +223,7 @@ impl Matrix {
     }
 
     pub fn scale(&self, x: f64, y: f64, z: f64) -> Self {
-        Matrix::scaling(x, y, z).mul(&self)
+        Matrix::scaling(x, y, z).mul(self)
     }
 
     pub fn translation(x: f64, y: f64, z: f64) -> Self {
@@ -83,7 +238,7 @@ impl Matrix {
     }
 
     pub fn translate(&self, x: f64, y: f64, z: f64) -> Self {
-        Matrix::translation(x, y, z).mul(&self)
+        Matrix::translation(x, y, z).mul(self)
     }
 
     pub fn get(&self, row: usize, col: usize) -> f64 {
@@ -233,10 +388,10 @@ impl Matrix {
     }
 
     pub fn rotate(&self, around: Axis, radians: f64) -> Self {
-        Matrix::rotation(around, radians).mul(&self)
+        Matrix::rotation(around, radians).mul(self)
     }
 
-    fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Self {
+    pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Self {
         Self {
             matrix: vec![
                 vec![1.0, xy, xz, 0.0],
@@ -248,7 +403,101 @@ impl Matrix {
     }
 
     pub fn shear(&self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Self {
-        Matrix::shearing(xy, xz, yx, yz, zx, zy).mul(&self)
+        Matrix::shearing(xy, xz, yx, yz, zx, zy).mul(self)
+    }
+
+    /// Splits an affine transform into translation, rotation and scale vectors, such that
+    /// `ident().scale(s).rotate(X, r.x).rotate(Y, r.y).rotate(Z, r.z).translate(t)` rebuilds it.
+    /// Returns `None` for transforms which can't be expressed this way, such as shears
+    pub fn decompose(&self) -> Option<(Tup, Tup, Tup)> {
+        let m = &self.matrix;
+        if m[3][0].abs() > EPSILON
+            || m[3][1].abs() > EPSILON
+            || m[3][2].abs() > EPSILON
+            || (m[3][3] - 1.0).abs() > EPSILON
+        {
+            return None;
+        }
+        let translation = vector(m[0][3], m[1][3], m[2][3]);
+
+        // each column of the upper 3x3 is a rotated axis stretched by that axis' scale
+        let mut columns = [0, 1, 2].map(|c| vector(m[0][c], m[1][c], m[2][c]));
+        let mut scale = columns.map(|c| c.length());
+        if scale.iter().any(|s| *s < EPSILON) {
+            return None;
+        }
+        columns = [0, 1, 2].map(|i| columns[i].div(scale[i]));
+        if columns[0].dot(columns[1]).abs() > EPSILON
+            || columns[0].dot(columns[2]).abs() > EPSILON
+            || columns[1].dot(columns[2]).abs() > EPSILON
+        {
+            return None;
+        }
+        // a mirrored transform is a rotation with a negative scale
+        if columns[0].cross_prod(columns[1]).dot(columns[2]) < 0.0 {
+            scale[0] = -scale[0];
+            columns[0] = columns[0].neg();
+        }
+
+        // angles for the rotation rz * ry * rx, read from r = [columns]
+        let r = |row: usize, col: usize| match row {
+            0 => columns[col].0,
+            1 => columns[col].1,
+            _ => columns[col].2,
+        };
+        let y = (-r(2, 0)).clamp(-1.0, 1.0).asin();
+        let (x, z) = if r(2, 0).abs() < 1.0 - EPSILON {
+            (r(2, 1).atan2(r(2, 2)), r(1, 0).atan2(r(0, 0)))
+        } else {
+            // gimbal lock, where only the sum of the x and z rotations matters
+            ((-r(1, 2)).atan2(r(1, 1)), 0.0)
+        };
+
+        Some((
+            translation,
+            vector(x, y, z),
+            vector(scale[0], scale[1], scale[2]),
+        ))
+    }
+}
+
+// matrices an inverse was worked out from, the outer one of a product first
+type InverseKey = (Option<Matrix>, Matrix);
+
+/// Remembers the inverse of the last matrix it was asked about. Comparing against that matrix is
+/// far cheaper than inverting, and means a changed transform is never given a stale inverse.
+/// The inverse is shared rather than copied out, so a cache hit doesn't allocate
+#[derive(Debug, Default)]
+pub struct InverseCache {
+    cached: RwLock<Option<(InverseKey, Option<Arc<Matrix>>)>>,
+}
+
+impl InverseCache {
+    pub fn inverse_of(&self, matrix: &Matrix) -> Option<Arc<Matrix>> {
+        self.cached_or_else(None, matrix, || matrix.inverse())
+    }
+
+    /// Inverse of `outer * inner`. The product is only multiplied out when either matrix has
+    /// changed since the last call
+    pub fn inverse_of_product(&self, outer: &Matrix, inner: &Matrix) -> Option<Arc<Matrix>> {
+        self.cached_or_else(Some(outer), inner, || outer.mul(inner).inverse())
+    }
+
+    fn cached_or_else(
+        &self,
+        outer: Option<&Matrix>,
+        inner: &Matrix,
+        invert: impl FnOnce() -> Option<Matrix>,
+    ) -> Option<Arc<Matrix>> {
+        if let Some(((o, i), inverse)) = self.cached.read().unwrap().as_ref() {
+            if o.as_ref() == outer && i == inner {
+                return inverse.clone();
+            }
+        }
+        let inverse = invert().map(Arc::new);
+        let key = (outer.cloned(), inner.clone());
+        *self.cached.write().unwrap() = Some((key, inverse.clone()));
+        inverse
     }
 }
 
@@ -265,7 +514,9 @@ mod tests {
         utils::test::ApproxEq,
     };
 
-    use super::{Axis, Matrix};
+    use std::sync::Arc;
+
+    use super::{Axis, InverseCache, Matrix, MatrixError};
 
     #[test]
     fn matrix_elements_are_correct() {
@@ -367,6 +618,70 @@ mod tests {
         assert_eq!(sut, expected);
     }
 
+    #[test]
+    fn try_new_accepts_a_rectangular_matrix() {
+        let sut = Matrix::try_new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        assert_eq!(
+            sut,
+            Ok(Matrix::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]))
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_a_ragged_matrix() {
+        let sut = Matrix::try_new(vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0]]);
+        assert_eq!(
+            sut,
+            Err(MatrixError::Ragged {
+                row: 2,
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_an_empty_matrix() {
+        assert_eq!(Matrix::try_new(vec![]), Err(MatrixError::Empty));
+        assert_eq!(Matrix::try_new(vec![vec![]]), Err(MatrixError::Empty));
+    }
+
+    #[test]
+    fn matrix_is_serialised_as_its_rows() {
+        let m = Matrix::translation(1.0, 2.0, 3.0);
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(
+            json,
+            "[[1.0,0.0,0.0,1.0],[0.0,1.0,0.0,2.0],[0.0,0.0,1.0,3.0],[0.0,0.0,0.0,1.0]]"
+        );
+        assert_eq!(serde_json::from_str::<Matrix>(&json).unwrap(), m);
+        assert!(serde_json::from_str::<Matrix>("[[1.0,2.0],[3.0]]").is_err());
+    }
+
+    #[test]
+    fn matrix_can_be_multiplied_together_with_the_operator() {
+        let m1: Matrix = Matrix::new(vec![
+            vec![1.0, 2.0, 3.0, 4.0],
+            vec![5.0, 6.0, 7.0, 8.0],
+            vec![9.0, 8.0, 7.0, 6.0],
+            vec![5.0, 4.0, 3.0, 2.0],
+        ]);
+        let m2: Matrix = Matrix::new(vec![
+            vec![-2.0, 1.0, 2.0, 3.0],
+            vec![3.0, 2.0, 1.0, -1.0],
+            vec![4.0, 3.0, 6.0, 5.0],
+            vec![1.0, 2.0, 7.0, 8.0],
+        ]);
+        let expected: Matrix = Matrix::new(vec![
+            vec![20.0, 22.0, 50.0, 48.0],
+            vec![44.0, 54.0, 114.0, 108.0],
+            vec![40.0, 58.0, 110.0, 102.0],
+            vec![16.0, 26.0, 46.0, 42.0],
+        ]);
+        assert_eq!(&m1 * &m2, expected);
+        assert_eq!(m1 * m2, expected);
+    }
+
     #[test]
     fn matrix_can_be_multiplied_by_tuple() {
         let matrix: Matrix = Matrix::new(vec![
@@ -747,6 +1062,16 @@ mod tests {
         expected.approx_eq(point(15.0, 0.0, 7.0));
     }
 
+    #[test]
+    fn chained_transformations_compose_with_the_operator() {
+        let p1 = point(1.0, 0.0, 1.0);
+        let a = Matrix::rotation(Axis::X, PI / 2.0);
+        let b = Matrix::scaling(5.0, 5.0, 5.0);
+        let c = Matrix::translation(10.0, 5.0, 7.0);
+        (&c * &(&b * &a) * p1).approx_eq(point(15.0, 0.0, 7.0));
+        (c * b * a * p1).approx_eq(point(15.0, 0.0, 7.0));
+    }
+
     #[test]
     fn fluid_interface_can_be_used_to_create_transform() {
         let p1 = point(1.0, 0.0, 1.0);
@@ -798,4 +1123,85 @@ mod tests {
 
         sut.approx_eq(matrix);
     }
+
+    #[test]
+    fn decomposing_translated_scale_recovers_parts() {
+        let m = Matrix::translation(1.0, 2.0, 3.0).mul(&Matrix::scaling(2.0, 2.0, 2.0));
+        let (translation, rotation, scale) = m.decompose().unwrap();
+        translation.approx_eq(vector(1.0, 2.0, 3.0));
+        rotation.approx_eq(vector(0.0, 0.0, 0.0));
+        scale.approx_eq(vector(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn decomposed_rotation_rebuilds_the_matrix() {
+        let m = Matrix::ident()
+            .scale(1.0, 2.0, 3.0)
+            .rotate(Axis::X, 0.3)
+            .rotate(Axis::Y, -0.7)
+            .rotate(Axis::Z, 1.2)
+            .translate(4.0, 5.0, 6.0);
+        let (t, r, s) = m.decompose().unwrap();
+        let sut = Matrix::ident()
+            .scale(s.0, s.1, s.2)
+            .rotate(Axis::X, r.0)
+            .rotate(Axis::Y, r.1)
+            .rotate(Axis::Z, r.2)
+            .translate(t.0, t.1, t.2);
+        sut.approx_eq(m);
+    }
+
+    #[test]
+    fn sheared_matrix_does_not_decompose() {
+        let m = Matrix::ident().shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        assert_eq!(m.decompose(), None);
+    }
+
+    #[test]
+    fn matrix_round_trips_through_arrays() {
+        let rows = [
+            [1.0, 2.0, 3.0, 4.0],
+            [5.5, 6.5, 7.5, 8.5],
+            [9.0, 8.0, 7.0, 6.0],
+            [5.0, 4.0, 3.0, 2.0],
+        ];
+        let m = Matrix::from_array(rows);
+        assert_eq!(m.to_array(), Some(rows));
+        assert_eq!(Matrix::from(rows), m);
+    }
+
+    #[test]
+    fn only_4x4_matrices_convert_to_arrays() {
+        let m = Matrix::new(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(m.to_array(), None);
+    }
+
+    #[test]
+    fn inverse_cache_follows_a_changed_matrix() {
+        let cache = InverseCache::default();
+        let inverse = |m: &Matrix| cache.inverse_of(m).map(|i| (*i).clone());
+        let mut m = Matrix::translation(1.0, 2.0, 3.0);
+        assert_eq!(inverse(&m), m.inverse());
+        assert_eq!(inverse(&m), m.inverse());
+
+        m = Matrix::scaling(2.0, 2.0, 2.0);
+        assert_eq!(inverse(&m), m.inverse());
+        assert_eq!(inverse(&Matrix::scaling(0.0, 1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn inverse_cache_follows_either_side_of_a_product() {
+        let cache = InverseCache::default();
+        let outer = Matrix::translation(1.0, 2.0, 3.0);
+        let inner = Matrix::scaling(2.0, 2.0, 2.0);
+        let inverse = |o: &Matrix, i: &Matrix| cache.inverse_of_product(o, i).map(|m| (*m).clone());
+
+        assert_eq!(inverse(&outer, &inner), outer.mul(&inner).inverse());
+        let first = cache.inverse_of_product(&outer, &inner).unwrap();
+        let second = cache.inverse_of_product(&outer, &inner).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        assert_eq!(inverse(&inner, &outer), inner.mul(&outer).inverse());
+        assert_eq!(inverse(&outer, &outer), outer.mul(&outer).inverse());
+    }
 }
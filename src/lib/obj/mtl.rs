@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use crate::{colour::colour::Colour, material::material::Material};
+
+/// The parts of an `.mtl` material which map onto the phong model
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    /// `Ka`
+    pub ambient: Colour,
+    /// `Kd`
+    pub diffuse: Colour,
+    /// `Ks`
+    pub specular: Colour,
+    /// `Ns`
+    pub shininess: f64,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        let material = Material::default();
+        Self {
+            ambient: Colour::white() * material.ambient,
+            diffuse: material.colour,
+            specular: Colour::white() * material.specular,
+            shininess: material.shininess,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Materials can't be shared between shapes, so every shape using this gets its own copy.
+    /// The ambient and specular colours become the strength of those terms, with the specular
+    /// colour kept as the tint of highlights
+    pub fn to_material(&self) -> Material {
+        let strength = |c: Colour| c.red.max(c.green).max(c.blue);
+        let specular = strength(self.specular);
+        let specular_tint = if specular > 0.0 {
+            self.specular * (1.0 / specular)
+        } else {
+            Colour::white()
+        };
+        Material::builder()
+            .with_colour(self.diffuse)
+            .with_ambient(strength(self.ambient))
+            .with_specular(specular)
+            .with_specular_tint(specular_tint)
+            .with_shininess(self.shininess)
+            .build()
+    }
+}
+
+/// Named materials read from the contents of an `.mtl` file
+#[derive(Debug, Default)]
+pub struct MtlLibrary {
+    pub materials: HashMap<String, MtlMaterial>,
+    /// Lines which were not understood, these are ignored rather than failing the parse
+    pub skipped: usize,
+}
+
+impl MtlLibrary {
+    pub fn parse(contents: &str) -> Self {
+        let mut library = MtlLibrary::default();
+        let mut current: Option<String> = None;
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let values: Vec<&str> = words.collect();
+
+            if keyword == "newmtl" && !values.is_empty() {
+                let name = values.join(" ");
+                library
+                    .materials
+                    .insert(name.clone(), MtlMaterial::default());
+                current = Some(name);
+                continue;
+            }
+
+            let material = current
+                .as_ref()
+                .and_then(|name| library.materials.get_mut(name));
+            let parsed = match (keyword, material) {
+                ("Ka", Some(m)) => parse_colour(&values).map(|c| m.ambient = c),
+                ("Kd", Some(m)) => parse_colour(&values).map(|c| m.diffuse = c),
+                ("Ks", Some(m)) => parse_colour(&values).map(|c| m.specular = c),
+                ("Ns", Some(m)) => parse_floats(&values)
+                    .filter(|v| v.len() == 1)
+                    .map(|v| m.shininess = v[0]),
+                _ => None,
+            };
+            if parsed.is_none() {
+                library.skipped += 1;
+            }
+        }
+        library
+    }
+
+    pub fn get(&self, name: &str) -> Option<Material> {
+        self.materials.get(name).map(MtlMaterial::to_material)
+    }
+}
+
+fn parse_floats(values: &[&str]) -> Option<Vec<f64>> {
+    values.iter().map(|v| v.parse::<f64>().ok()).collect()
+}
+
+fn parse_colour(values: &[&str]) -> Option<Colour> {
+    parse_floats(values)
+        .filter(|v| v.len() == 3)
+        .map(|v| Colour::new(v[0], v[1], v[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colour::colour::Colour;
+
+    use super::MtlLibrary;
+
+    const MTL: &str = "
+# two materials
+newmtl red
+Kd 1.0 0.0 0.0
+Ks 0.5 0.5 0.25
+Ns 50
+
+newmtl blue
+Kd 0.0 0.0 1.0
+Ka 0.2 0.2 0.2
+illum 2
+";
+
+    #[test]
+    fn parses_each_named_material() {
+        let sut = MtlLibrary::parse(MTL);
+        assert_eq!(sut.materials.len(), 2);
+        assert_eq!(sut.materials["red"].diffuse, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(sut.materials["red"].shininess, 50.0);
+        assert_eq!(sut.materials["blue"].ambient, Colour::new(0.2, 0.2, 0.2));
+    }
+
+    #[test]
+    fn unrecognised_lines_are_skipped() {
+        let sut = MtlLibrary::parse(MTL);
+        assert_eq!(sut.skipped, 1);
+    }
+
+    #[test]
+    fn specular_colour_becomes_strength_and_tint() {
+        let sut = MtlLibrary::parse(MTL).get("red").unwrap();
+        assert_eq!(sut.colour, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(sut.specular, 0.5);
+        assert_eq!(sut.specular_tint, Colour::new(1.0, 1.0, 0.5));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::{
+    geometry::{
+        mesh,
+        vector::{point, vector, Tup},
+    },
+    shapes::{
+        group::Group, shape::TShapeBuilder, smooth_triangle::SmoothTriangle, triangle::Triangle,
+    },
+};
+
+use super::mtl::MtlLibrary;
+
+// vertex indices of the corners of a triangle, with their normals when the face had them
+type FaceTriangle = ([usize; 3], Option<[Tup; 3]>);
+
+/// One triangle of a face read from an OBJ file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjTriangle {
+    pub points: [Tup; 3],
+    /// Indices into `ParsedObj::vertices` of the corners
+    pub corners: [usize; 3],
+    /// Present when every vertex of the face named a vertex normal
+    pub normals: Option<[Tup; 3]>,
+    /// Name of the material in use (`usemtl`) when the face was read
+    pub material: Option<String>,
+}
+
+/// Geometry read from the contents of an OBJ file
+#[derive(Debug, Default)]
+pub struct ParsedObj {
+    pub vertices: Vec<Tup>,
+    pub normals: Vec<Tup>,
+    pub triangles: Vec<ObjTriangle>,
+    /// Lines which were not understood, these are ignored rather than failing the parse
+    pub skipped: usize,
+}
+
+impl ParsedObj {
+    /// Gives every triangle without vertex normals ones averaged from the faces around its
+    /// corners, so that it becomes a smooth triangle in `to_group`. Faces meeting at more than
+    /// `max_angle` radians keep the crease between them
+    pub fn smooth_normals(&mut self, max_angle: f64) {
+        let faces: Vec<[usize; 3]> = self.triangles.iter().map(|t| t.corners).collect();
+        let smoothed = mesh::smooth_normals(&self.vertices, &faces, max_angle);
+        for (triangle, normals) in self.triangles.iter_mut().zip(smoothed) {
+            triangle.normals.get_or_insert(normals);
+        }
+    }
+
+    /// Group of the triangles, flat unless the file gave vertex normals or they were smoothed
+    pub fn to_group(&self) -> Group {
+        self.to_group_with_materials(&MtlLibrary::default())
+    }
+
+    /// Like `to_group`, but each triangle takes the material named by the `usemtl` before it.
+    /// Triangles whose material is missing from the library get the default material
+    pub fn to_group_with_materials(&self, library: &MtlLibrary) -> Group {
+        let mut group = Group::default();
+        for triangle in &self.triangles {
+            let material = triangle
+                .material
+                .as_ref()
+                .and_then(|name| library.get(name))
+                .unwrap_or_default();
+            let [p1, p2, p3] = triangle.points;
+            let shape = match triangle.normals {
+                Some([n1, n2, n3]) => SmoothTriangle::builder(p1, p2, p3, n1, n2, n3)
+                    .with_material(material)
+                    .build_trait(),
+                None => Triangle::builder(p1, p2, p3)
+                    .with_material(material)
+                    .build_trait(),
+            };
+            group.add_child(shape);
+        }
+        group
+    }
+}
+
+pub fn parse_obj(contents: &str) -> ParsedObj {
+    let mut parsed = ParsedObj::default();
+    let mut material: Option<String> = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let values: Vec<&str> = words.collect();
+
+        let understood = match keyword {
+            "v" => parse_tup(&values)
+                .map(|(x, y, z)| parsed.vertices.push(point(x, y, z)))
+                .is_some(),
+            "vn" => parse_tup(&values)
+                .map(|(x, y, z)| parsed.normals.push(vector(x, y, z)))
+                .is_some(),
+            "usemtl" if !values.is_empty() => {
+                material = Some(values.join(" "));
+                true
+            }
+            "f" => parse_face(&parsed, &values)
+                .map(|triangles| {
+                    parsed
+                        .triangles
+                        .extend(triangles.into_iter().map(|(corners, normals)| ObjTriangle {
+                            points: corners.map(|c| parsed.vertices[c]),
+                            corners,
+                            normals,
+                            material: material.clone(),
+                        }))
+                })
+                .is_some(),
+            _ => false,
+        };
+        if !understood {
+            parsed.skipped += 1;
+        }
+    }
+    parsed
+}
+
+fn parse_tup(values: &[&str]) -> Option<(f64, f64, f64)> {
+    let values: Vec<f64> = values
+        .iter()
+        .map(|v| v.parse::<f64>().ok())
+        .collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, z] => Some((x, y, z)),
+        _ => None,
+    }
+}
+
+// converts a one based OBJ index to a zero based one, if it refers to one of `items`
+fn lookup_index(items: &[Tup], index: &str) -> Option<usize> {
+    let index = index.parse::<usize>().ok()?.checked_sub(1)?;
+    (index < items.len()).then_some(index)
+}
+
+fn lookup(items: &[Tup], index: &str) -> Option<Tup> {
+    lookup_index(items, index).map(|i| items[i])
+}
+
+/// Fan triangulates a face of `v`, `v/vt` or `v/vt/vn` references, which must all refer to
+/// vertices and normals already read
+fn parse_face(parsed: &ParsedObj, values: &[&str]) -> Option<Vec<FaceTriangle>> {
+    if values.len() < 3 {
+        return None;
+    }
+    let mut vertices = vec![];
+    let mut normals = vec![];
+    for value in values {
+        let mut parts = value.split('/');
+        vertices.push(lookup_index(&parsed.vertices, parts.next()?)?);
+        match parts.nth(1).filter(|n| !n.is_empty()) {
+            Some(n) => normals.push(lookup(&parsed.normals, n)?),
+            None => normals.clear(),
+        }
+    }
+
+    // normals are only used when every vertex has one
+    let has_normals = normals.len() == vertices.len();
+    Some(
+        (1..vertices.len() - 1)
+            .map(|i| {
+                let corners = [0, i, i + 1];
+                (
+                    corners.map(|c| vertices[c]),
+                    has_normals.then(|| corners.map(|c| normals[c])),
+                )
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{
+        colour::colour::Colour,
+        geometry::{
+            mesh::face_normal,
+            vector::{point, vector, Operations, Vector},
+        },
+        obj::mtl::MtlLibrary,
+        ray::ray::Ray,
+        shapes::{group::Group, shape::TShape},
+        utils::test::ApproxEq,
+    };
+
+    use super::parse_obj;
+
+    #[test]
+    fn unrecognised_lines_are_skipped() {
+        let sut = parse_obj(
+            "There was a young lady named Bright
+who traveled much faster than light.
+v 1 0 0",
+        );
+        assert_eq!(sut.skipped, 2);
+        assert_eq!(sut.vertices, vec![point(1.0, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn faces_reference_vertices() {
+        let sut = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+f 1 2 3
+f 1 3 4",
+        );
+        assert_eq!(sut.triangles.len(), 2);
+        assert_eq!(
+            sut.triangles[1].points,
+            [
+                point(-1.0, 1.0, 0.0),
+                point(1.0, 0.0, 0.0),
+                point(1.0, 1.0, 0.0)
+            ]
+        );
+        assert_eq!(sut.triangles[1].normals, None);
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let sut = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+f 1 2 3 4 5",
+        );
+        let v = &sut.vertices;
+        let fans: Vec<_> = sut.triangles.iter().map(|t| t.points).collect();
+        assert_eq!(
+            fans,
+            vec![[v[0], v[1], v[2]], [v[0], v[2], v[3]], [v[0], v[3], v[4]]]
+        );
+    }
+
+    #[test]
+    fn faces_with_normals_keep_them() {
+        let sut = parse_obj(
+            "v 0 1 0
+v -1 0 0
+v 1 0 0
+vn -1 0 0
+vn 1 0 0
+vn 0 1 0
+f 1//3 2//1 3//2
+f 1/0/3 2/102/1 3/14/2",
+        );
+        let expected = [
+            vector(0.0, 1.0, 0.0),
+            vector(-1.0, 0.0, 0.0),
+            vector(1.0, 0.0, 0.0),
+        ];
+        assert_eq!(sut.triangles[0].normals, Some(expected));
+        assert_eq!(sut.triangles[1].normals, Some(expected));
+    }
+
+    #[test]
+    fn faces_referring_to_missing_vertices_are_skipped() {
+        let sut = parse_obj(
+            "v 0 1 0
+f 1 2 3",
+        );
+        assert!(sut.triangles.is_empty());
+        assert_eq!(sut.skipped, 1);
+    }
+
+    #[test]
+    fn group_holds_every_triangle() {
+        let sut = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+f 1 2 3 4",
+        )
+        .to_group();
+        assert_eq!(sut.children().len(), 2);
+    }
+
+    #[test]
+    fn triangles_take_the_material_in_use() {
+        let library = MtlLibrary::parse(
+            "newmtl red
+Kd 1 0 0
+newmtl blue
+Kd 0 0 1",
+        );
+        let sut = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 3 4
+f 1 2 4",
+        )
+        .to_group_with_materials(&library);
+        let colours: Vec<Colour> = sut.children().iter().map(|c| c.material().colour).collect();
+        assert_eq!(
+            colours,
+            vec![
+                Colour::new(1.0, 0.0, 0.0),
+                Colour::new(0.0, 0.0, 1.0),
+                Colour::new(0.0, 0.0, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn smoothed_faces_interpolate_their_normals() {
+        // two faces meeting at a shallow crease along the z axis
+        let mut parsed = parse_obj(
+            "v 0 0 0
+v 0 0 1
+v 1 -0.1 0
+v -1 -0.1 0
+f 1 3 2
+f 1 2 4",
+        );
+        parsed.smooth_normals(PI / 6.0);
+        let sut = parsed.to_group();
+
+        // a quarter of the way from the crease to the outer corner of the first face
+        let ray = Ray::new(point(0.25, 10.0, 0.25), vector(0.0, -1.0, 0.0));
+        let xs = sut.intersect(&ray);
+        let hit = xs.iter().find(|x| x.at > 0.0).unwrap();
+        let normal = hit.object.normal_at_hit(ray.position(hit.at), hit).unwrap();
+
+        let flat = face_normal(&parsed.vertices, &[0, 2, 1]);
+        normal.approx_eq(vector(0.0, 1.0, 0.0).mul(0.75).add(flat.mul(0.25)).norm());
+    }
+
+    #[test]
+    fn smoothing_a_flat_mesh_keeps_the_triangle_normals() {
+        let quad = "v -1 0 1
+v -1 0 -1
+v 1 0 -1
+v 1 0 1
+f 1 2 3 4";
+        let ray = Ray::new(point(0.2, 10.0, 0.3), vector(0.0, -1.0, 0.0));
+        let normal = |group: &Group| {
+            let xs = group.intersect(&ray);
+            let hit = xs.iter().find(|x| x.at > 0.0).unwrap();
+            hit.object.normal_at_hit(ray.position(hit.at), hit).unwrap()
+        };
+
+        let flat = parse_obj(quad).to_group();
+        let mut smoothed = parse_obj(quad);
+        smoothed.smooth_normals(PI / 6.0);
+
+        normal(&smoothed.to_group()).approx_eq(normal(&flat));
+    }
+}
@@ -1,10 +1,16 @@
 #![allow(dead_code, unused_variables, unreachable_patterns)]
 
+use std::cmp::Ordering;
+use std::fmt;
+
 use crate::colour::colour::Colour;
-use crate::light::light::PointLight;
+use crate::light::light::TLight;
 use crate::shapes::shape::TShape;
 use crate::{
-    geometry::vector::{Operations, Tup, Vector},
+    geometry::{
+        vector::{Operations, Tup, Vector},
+        EPSILON,
+    },
     matrix::matrix::Matrix,
 };
 
@@ -17,35 +23,69 @@ pub struct Intersection<'a> {
     /// Where on an object a ray intersects
     pub at: f64,
     pub object: Box<&'a (dyn TShape + 'a)>,
+    /// Barycentric coordinates of the hit, only recorded by shapes which interpolate across
+    /// their surface such as smooth triangles
+    pub u: Option<f64>,
+    pub v: Option<f64>,
 }
 
 impl<'a> Intersection<'a> {
     pub fn new(at: f64, object: Box<&'a (dyn TShape + 'a)>) -> Self {
-        Self { at, object }
+        Self {
+            at,
+            object,
+            u: None,
+            v: None,
+        }
     }
+
+    pub fn with_uv(at: f64, object: Box<&'a (dyn TShape + 'a)>, u: f64, v: f64) -> Self {
+        Self {
+            at,
+            object,
+            u: Some(u),
+            v: Some(v),
+        }
+    }
+
+    /// Orders intersections by distance along the ray. NaN distances, which come from degenerate
+    /// rays, are ordered after every real distance
+    pub fn compare(&self, other: &Intersection) -> Ordering {
+        match (self.at.is_nan(), other.at.is_nan()) {
+            (false, false) => self.at.total_cmp(&other.at),
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+        }
+    }
+}
+
+/// Sorts intersections from nearest to farthest
+pub fn sort_intersections(xs: &mut [Intersection]) {
+    xs.sort_by(|a, b| a.compare(b));
 }
 
 pub trait Hit {
     type Output;
 
-    fn hit(&self) -> Option<&Self::Output>;
+    fn hit(&self) -> Option<&Self::Output> {
+        self.hit_with_index().map(|(_, hit)| hit)
+    }
+
+    /// The hit along with its position in the list, so that callers walking a sorted list can
+    /// tell which intersections come before it
+    fn hit_with_index(&self) -> Option<(usize, &Self::Output)>;
 }
 
-impl<'a> Hit for Vec<Intersection<'a>> {
+impl<'a> Hit for [Intersection<'a>] {
     type Output = Intersection<'a>;
 
-    fn hit(&self) -> Option<&Self::Output> {
-        if self.len() == 0 {
-            return None;
-        };
-
-        let mut positive_intersections: Vec<&Self::Output> =
-            self.into_iter().filter(|i| i.at > 0.0).collect();
-        if positive_intersections.len() == 0 {
-            return None;
-        }
-        positive_intersections.sort_by(|a, b| a.at.total_cmp(&b.at));
-        Some(&positive_intersections[0])
+    fn hit_with_index(&self) -> Option<(usize, &Self::Output)> {
+        // the first of equally near intersections wins, as it would after a stable sort
+        self.iter()
+            .enumerate()
+            .filter(|(_, i)| i.at > 0.0)
+            .min_by(|(_, a), (_, b)| a.compare(b))
     }
 }
 
@@ -54,28 +94,129 @@ pub struct PreComp<'a> {
     pub object: Box<&'a (dyn TShape + 'a)>,
     pub point: Tup,
     pub over_point: Tup,
+    /// Just beneath the surface, where refracted rays start
+    pub under_point: Tup,
     eye_v: Tup,
     norm_v: Tup,
     inside: bool,
     pub reflect_v: Tup,
-    n1: f64,
-    n2: f64,
+    /// Refractive index of the material the ray is leaving
+    pub n1: f64,
+    /// Refractive index of the material the ray is entering
+    pub n2: f64,
 }
 
 impl<'a> PreComp<'a> {
-    pub fn shade_hit(&self, light_source: &PointLight, is_shadow: bool) -> Colour {
+    /// Direction back toward the eye
+    pub fn eye(&self) -> Tup {
+        self.eye_v
+    }
+
+    /// Surface normal, flipped to face the eye when the hit is inside the object
+    pub fn normal(&self) -> Tup {
+        self.norm_v
+    }
+
+    pub fn is_inside(&self) -> bool {
+        self.inside
+    }
+
+    pub fn shade_hit(&self, light_source: &dyn TLight, light_intensity: f64) -> Colour {
         self.object.material().lighting(
             self.point,
             light_source,
             self.eye_v,
             self.norm_v,
-            is_shadow,
+            light_intensity,
             self.object.to_trait_ref(),
         )
     }
+
+    /// Schlick's approximation of the fraction of light reflected at the hit, the rest being
+    /// refracted. Total internal reflection reflects everything
+    pub fn schlick(&self) -> f64 {
+        let mut cos = self.eye_v.dot(self.norm_v);
+        if self.n1 > self.n2 {
+            let n = self.n1 / self.n2;
+            let sin2_t = n.powi(2) * (1.0 - cos.powi(2));
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+            // going into the less dense material the angle of the refracted ray is used
+            cos = (1.0 - sin2_t).sqrt();
+        }
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
+}
+
+/// Walks the sorted intersections up to the hit, tracking which objects the ray is inside of, to
+/// find the refractive indices either side of the hit. Where objects overlap the container with
+/// the highest material priority governs, ties going to the most recently entered
+fn refractive_indices(hit: &Intersection, xs: &[&Intersection]) -> (f64, f64) {
+    fn governing_index(containers: &[&dyn TShape]) -> f64 {
+        containers
+            .iter()
+            .max_by_key(|c| c.material().priority)
+            .map(|c| c.material().refractive_index)
+            .unwrap_or(1.0)
+    }
+
+    // a tangent ray touches the object without passing inside it, so neither intersection of a
+    // grazing pair changes which objects the ray is in
+    let grazes = |i: &Intersection| {
+        xs.iter().any(|other| {
+            !std::ptr::eq(*other, i)
+                && std::ptr::addr_eq(*other.object.as_ref(), *i.object.as_ref())
+                && (other.at - i.at).abs() < EPSILON
+        })
+    };
+
+    let mut containers: Vec<&dyn TShape> = vec![];
+    for i in xs {
+        let is_hit = std::ptr::eq(*i, hit);
+        let n1 = governing_index(&containers);
+
+        let object: &dyn TShape = *i.object.as_ref();
+        if !grazes(i) {
+            match containers
+                .iter()
+                .position(|c| std::ptr::addr_eq(*c, object))
+            {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push(object),
+            }
+        }
+
+        if is_hit {
+            return (n1, governing_index(&containers));
+        }
+    }
+    (1.0, 1.0)
 }
 
 // ----------- Ray ----------- //
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayError {
+    /// The direction has no length, so the ray goes nowhere
+    ZeroDirection,
+    /// The origin or direction has an infinite or NaN component
+    NonFinite,
+}
+
+impl fmt::Display for RayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RayError::ZeroDirection => write!(f, "ray direction has zero length"),
+            RayError::NonFinite => write!(f, "ray has a non-finite component"),
+        }
+    }
+}
+
+impl std::error::Error for RayError {}
+
 #[derive(Debug)]
 pub struct Ray {
     pub origin: Tup,
@@ -87,6 +228,18 @@ impl Ray {
         Self { origin, direction }
     }
 
+    /// Like `new`, but rejects rays which would fill intersection maths with NaNs
+    pub fn try_new(origin: Tup, direction: Tup) -> Result<Self, RayError> {
+        let finite = |t: Tup| t.0.is_finite() && t.1.is_finite() && t.2.is_finite();
+        if !finite(origin) || !finite(direction) {
+            return Err(RayError::NonFinite);
+        }
+        if direction.length() == 0.0 {
+            return Err(RayError::ZeroDirection);
+        }
+        Ok(Self { origin, direction })
+    }
+
     pub fn position(&self, t: f64) -> Tup {
         self.direction.mul(t).add(self.origin)
     }
@@ -98,7 +251,7 @@ impl Ray {
         let mut result: Vec<Intersection<'a>> =
             shapes.into_iter().flat_map(|o| o.intersect(self)).collect();
 
-        result.sort_by(|a, b| a.at.total_cmp(&b.at));
+        sort_intersections(&mut result);
         result
     }
 
@@ -107,26 +260,30 @@ impl Ray {
         intersection: &Intersection<'a>,
         xs: &Vec<&Intersection<'a>>,
     ) -> Option<PreComp> {
+        let (n1, n2) = refractive_indices(intersection, xs);
         let object = intersection.object.to_trait_ref();
         let p = self.position(intersection.at);
         let eye_v = self.direction.neg();
-        let maybe_norm_v = object.normal_at(p);
+        let maybe_norm_v = object.normal_at_hit(p, intersection);
 
         maybe_norm_v.map(|norm_v| {
-            // if hit occurs inside the shape then we must invert the normal
-            let is_inside = norm_v.dot(eye_v) < 0.0;
+            // if hit occurs inside the shape then we must invert the normal. Grazing hits, where
+            // the eye is perpendicular to the normal, are always taken as outside
+            let is_inside = norm_v.dot(eye_v) < -EPSILON;
             let norm_v_result = if is_inside { norm_v.neg() } else { norm_v };
 
             PreComp {
                 object,
                 point: p,
-                over_point: p.add(norm_v_result.mul(0.00001)),
+                // nudged off the surface so rays starting there don't hit it again
+                over_point: p.add(norm_v_result.mul(EPSILON)),
+                under_point: p.sub(norm_v_result.mul(EPSILON)),
                 eye_v,
                 norm_v: norm_v_result,
                 inside: is_inside,
                 reflect_v: self.direction.reflect(norm_v.neg()),
-                n1: 1.1,
-                n2: 1.2,
+                n1,
+                n2,
             }
         })
     }
@@ -143,7 +300,11 @@ impl Ray {
 mod tests {
     use crate::{
         colour::colour::Colour,
-        geometry::vector::{point, vector},
+        geometry::{
+            vector::{point, vector, Operations, Vector},
+            EPSILON,
+        },
+        light::light::PointLight,
         material::material::Material,
         matrix::matrix::Matrix,
         shapes::{
@@ -151,9 +312,25 @@ mod tests {
             shape::{TShape, TShapeBuilder},
             sphere::Sphere,
         },
+        utils::test::ApproxEq,
     };
 
-    use super::{Hit, Intersection, Ray};
+    use super::{sort_intersections, Hit, Intersection, PreComp, Ray, RayError};
+
+    #[test]
+    fn degenerate_rays_are_rejected() {
+        let origin = point(1.0, 2.0, 3.0);
+        assert_eq!(
+            Ray::try_new(origin, vector(0.0, 0.0, 0.0)).unwrap_err(),
+            RayError::ZeroDirection
+        );
+        assert_eq!(
+            Ray::try_new(origin, vector(f64::NAN, 0.0, 1.0)).unwrap_err(),
+            RayError::NonFinite
+        );
+        let sut = Ray::try_new(origin, vector(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(sut.direction, vector(0.0, 0.0, 1.0));
+    }
 
     fn glass_sphere(transform: Matrix, ref_index: f64) -> Sphere {
         Sphere::builder()
@@ -217,6 +394,24 @@ mod tests {
         assert_eq!(xs[1].at, 5.0);
     }
 
+    #[test]
+    fn tangent_hit_is_shaded_as_the_outer_surface() {
+        let ray = Ray::new(point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0));
+        let sphere = glass_sphere(Matrix::ident(), 1.5);
+
+        let xs = sphere.intersect(&ray);
+        let refs: Vec<&Intersection> = xs.iter().collect();
+        for i in &xs {
+            let comps = ray.prep_comp(i, &refs).unwrap();
+            comps.norm_v.approx_eq(vector(0.0, 1.0, 0.0));
+            assert!(!comps.inside);
+            assert_eq!((comps.n1, comps.n2), (1.0, 1.0));
+
+            let colour = comps.shade_hit(&PointLight::default(), 1.0);
+            assert!(colour.red.is_finite() && colour.green.is_finite() && colour.blue.is_finite());
+        }
+    }
+
     #[test]
     fn ray_misses_sphere() {
         let origin = point(0.0, 2.0, -5.0);
@@ -285,7 +480,7 @@ mod tests {
         let s: Box<dyn TShape> = Sphere::builder().build_trait();
         let i1 = Intersection::new(1.0, s.to_trait_ref());
         let i2 = Intersection::new(2.0, s.to_trait_ref());
-        let xs = vec![i1, i2];
+        let xs = [i1, i2];
         let sut = xs.hit().unwrap();
         assert!(std::ptr::eq(&xs[0], sut));
     }
@@ -295,7 +490,7 @@ mod tests {
         let s: Box<dyn TShape> = Sphere::builder().build_trait();
         let i1 = Intersection::new(-1.0, s.to_trait_ref());
         let i2 = Intersection::new(1.0, s.to_trait_ref());
-        let xs = vec![i1, i2];
+        let xs = [i1, i2];
         let sut = xs.hit().unwrap();
         assert!(std::ptr::eq(&xs[1], sut));
     }
@@ -305,7 +500,7 @@ mod tests {
         let s: Box<dyn TShape> = Sphere::builder().build_trait();
         let i1 = Intersection::new(-1.0, s.to_trait_ref());
         let i2 = Intersection::new(-1.0, s.to_trait_ref());
-        let xs = vec![i1, i2];
+        let xs = [i1, i2];
         let sut = xs.hit();
         assert!(sut.is_none());
     }
@@ -317,11 +512,45 @@ mod tests {
         let i2 = Intersection::new(7.0, s.to_trait_ref());
         let i3 = Intersection::new(-3.0, s.to_trait_ref());
         let i4 = Intersection::new(2.0, s.to_trait_ref());
-        let xs = vec![i1, i2, i3, i4];
+        let xs = [i1, i2, i3, i4];
         let sut = xs.hit().unwrap();
         assert!(std::ptr::eq(&xs[3], sut));
     }
 
+    #[test]
+    fn hit_index_points_at_the_hit_in_an_unsorted_list() {
+        let s: Box<dyn TShape> = Sphere::builder().build_trait();
+        for (ts, expected) in [
+            (vec![5.0, 7.0, -3.0, 2.0], Some(3)),
+            (vec![-1.0, 1.0], Some(1)),
+            (vec![1.0, 2.0], Some(0)),
+            (vec![-4.0, 3.0, -1.0, 0.5, 6.0], Some(3)),
+            (vec![-2.0, -1.0], None),
+            (vec![], None),
+        ] {
+            let xs: Vec<Intersection> = ts
+                .iter()
+                .map(|&t| Intersection::new(t, s.to_trait_ref()))
+                .collect();
+            let sut = xs.hit_with_index();
+            assert_eq!(sut.map(|(index, _)| index), expected);
+            if let Some((index, hit)) = sut {
+                assert!(std::ptr::eq(&xs[index], hit));
+            }
+        }
+    }
+
+    #[test]
+    fn hit_index_prefers_the_first_of_equal_intersections() {
+        let s: Box<dyn TShape> = Sphere::builder().build_trait();
+        let xs = [
+            Intersection::new(-1.0, s.to_trait_ref()),
+            Intersection::new(2.0, s.to_trait_ref()),
+            Intersection::new(2.0, s.to_trait_ref()),
+        ];
+        assert_eq!(xs.hit_with_index().map(|(index, _)| index), Some(1));
+    }
+
     #[test]
     fn ray_can_be_translated() {
         let r1 = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
@@ -364,10 +593,7 @@ mod tests {
     fn precomputing_intersection_state() {
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let shape: Box<dyn TShape> = Sphere::builder().build_trait();
-        let i = Intersection {
-            at: 4.0,
-            object: shape.to_trait_ref(),
-        };
+        let i = Intersection::new(4.0, shape.to_trait_ref());
         let comps = ray.prep_comp(&i, &vec![&i]).unwrap();
         let comps_obj = comps.object;
         let intersect_obj = i.object;
@@ -442,6 +668,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn precomp_accessors_for_a_hit_from_outside() {
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let shape = Sphere::builder().build_trait();
+        let i = Intersection::new(4.0, shape.to_trait_ref());
+        let sut = ray.prep_comp(&i, &vec![&i]).unwrap();
+        assert!(!sut.is_inside());
+        assert_eq!(sut.eye(), vector(0.0, 0.0, -1.0));
+        assert!(sut.normal().dot(ray.origin.sub(sut.point)) > 0.0);
+    }
+
+    #[test]
+    fn precomp_hit_should_offset_the_point() {
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let shape = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 0.0, 1.0))
+            .build();
+        let i = Intersection::new(5.0, shape.to_trait_ref());
+        let comps = ray.prep_comp(&i, &vec![&i]).unwrap();
+        assert!(comps.over_point.2 < -EPSILON / 2.0);
+        assert!(comps.point.2 > comps.over_point.2);
+    }
+
+    #[test]
+    fn precomp_under_point_is_offset_below_the_surface() {
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let shape = glass_sphere(Matrix::translation(0.0, 0.0, 1.0), 1.5);
+        let i = Intersection::new(5.0, shape.to_trait_ref());
+        let comps = ray.prep_comp(&i, &vec![&i]).unwrap();
+        assert!(comps.under_point.2 > EPSILON / 2.0);
+        assert!(comps.under_point.2 > comps.over_point.2);
+        assert!(comps.over_point.2 < comps.point.2 && comps.point.2 < comps.under_point.2);
+    }
+
+    // reflectance where a ray inside a glass sphere hits the surface at the given intersection
+    fn schlick_from_inside(ray: Ray, ats: [f64; 2], hit: usize) -> f64 {
+        let shape = glass_sphere(Matrix::ident(), 1.5);
+        let intersections = ats.map(|at| Intersection::new(at, shape.to_trait_ref()));
+        let xs: Vec<&Intersection> = intersections.iter().collect();
+        ray.prep_comp(xs[hit], &xs).unwrap().schlick()
+    }
+
+    #[test]
+    fn schlick_under_total_internal_reflection() {
+        let root2 = 2.0_f64.sqrt() / 2.0;
+        let ray = Ray::new(point(0.0, 0.0, root2), vector(0.0, 1.0, 0.0));
+        assert_eq!(schlick_from_inside(ray, [-root2, root2], 1), 1.0);
+    }
+
+    #[test]
+    fn schlick_with_a_perpendicular_ray() {
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        schlick_from_inside(ray, [-1.0, 1.0], 1).approx_eq(0.04);
+    }
+
+    #[test]
+    fn schlick_with_small_angle_and_n2_greater_than_n1() {
+        let shape = glass_sphere(Matrix::ident(), 1.5);
+        let ray = Ray::new(point(0.0, 0.99, -2.0), vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(1.8589, shape.to_trait_ref());
+        let sut = ray.prep_comp(&i, &vec![&i]).unwrap().schlick();
+        sut.approx_eq(0.48873);
+    }
+
     #[test]
     fn finding_n1_and_n1_at_various_intersections() {
         let a = glass_sphere(Matrix::scaling(2.0, 2.0, 2.0), 1.5);
@@ -459,10 +749,13 @@ mod tests {
         ];
 
         let i_ref: Vec<&Intersection> = intersections.iter().collect();
-        let xs = intersections
+        let xs: Vec<PreComp> = intersections
             .iter()
             .map(|i| ray.prep_comp(i, &i_ref))
-            .filter_map(|x| x);
+            .filter_map(|x| x)
+            .collect();
+        // every hit must be checked, zip alone would stop quietly at a missing one
+        assert_eq!(xs.len(), 6);
 
         let expected = vec![
             (1.0, 1.5),
@@ -473,9 +766,69 @@ mod tests {
             (1.5, 1.0),
         ];
 
-        xs.zip(expected).for_each(|val| {
+        xs.iter().zip(expected).for_each(|val| {
             assert_eq!(val.0.n1, val.1 .0);
             assert_eq!(val.0.n2, val.1 .1)
         });
     }
+
+    #[test]
+    fn higher_priority_glass_governs_overlap_with_water() {
+        let water = Sphere::builder()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .with_material(
+                Material::builder()
+                    .with_transparency(1.0)
+                    .with_refractive_index(1.33)
+                    .with_priority(0)
+                    .build(),
+            )
+            .build();
+        let glass = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 0.0, -2.5))
+            .with_material(
+                Material::builder()
+                    .with_transparency(1.0)
+                    .with_refractive_index(1.5)
+                    .with_priority(1)
+                    .build(),
+            )
+            .build();
+
+        let ray = Ray::new(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
+        let intersections: Vec<Intersection> = vec![
+            Intersection::new(0.5, glass.to_trait_ref()),
+            Intersection::new(2.0, water.to_trait_ref()),
+            Intersection::new(2.5, glass.to_trait_ref()),
+            Intersection::new(6.0, water.to_trait_ref()),
+        ];
+        let i_ref: Vec<&Intersection> = intersections.iter().collect();
+
+        let sut: Vec<(f64, f64)> = intersections
+            .iter()
+            .filter_map(|i| ray.prep_comp(i, &i_ref))
+            .map(|comps| (comps.n1, comps.n2))
+            .collect();
+
+        // the water surface inside the glass is not a real interface
+        assert_eq!(sut, vec![(1.0, 1.5), (1.5, 1.5), (1.5, 1.33), (1.33, 1.0)]);
+    }
+
+    #[test]
+    fn sorting_intersections_orders_by_distance() {
+        let s = Sphere::builder().build_trait();
+        let mut xs = vec![
+            Intersection::new(3.0, s.to_trait_ref()),
+            Intersection::new(-1.5, s.to_trait_ref()),
+            Intersection::new(f64::NAN, s.to_trait_ref()),
+            Intersection::new(0.5, s.to_trait_ref()),
+            Intersection::new(-4.0, s.to_trait_ref()),
+            Intersection::new(2.0, s.to_trait_ref()),
+        ];
+        sort_intersections(&mut xs);
+
+        let sut: Vec<f64> = xs.iter().map(|i| i.at).collect();
+        assert_eq!(sut[..5], [-4.0, -1.5, 0.5, 2.0, 3.0]);
+        assert!(sut[5].is_nan());
+    }
 }
@@ -0,0 +1,271 @@
+use crate::{
+    geometry::{
+        vector::{point, Tup},
+        EPSILON,
+    },
+    matrix::matrix::Matrix,
+    ray::ray::Ray,
+};
+
+/// Axis aligned box enclosing a shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Tup,
+    pub max: Tup,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::infinite()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Tup, max: Tup) -> Self {
+        Self { min, max }
+    }
+
+    pub fn infinite() -> Self {
+        Self {
+            min: point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.min, self.max]
+            .iter()
+            .all(|p| p.0.is_finite() && p.1.is_finite() && p.2.is_finite())
+    }
+
+    /// Smallest box containing both boxes
+    pub fn merge(&self, other: &BoundingBox) -> Self {
+        Self {
+            min: point(
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            max: point(
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        }
+    }
+
+    pub fn contains_point(&self, p: Tup) -> bool {
+        (self.min.0..=self.max.0).contains(&p.0)
+            && (self.min.1..=self.max.1).contains(&p.1)
+            && (self.min.2..=self.max.2).contains(&p.2)
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    /// Whether the ray passes through the box ahead of its origin, by clipping it against the
+    /// slab between each pair of faces in turn
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        let (o, d) = (ray.origin, ray.direction);
+        let (xmin, xmax) = check_axis(o.0, d.0, self.min.0, self.max.0);
+        let (ymin, ymax) = check_axis(o.1, d.1, self.min.1, self.max.1);
+        let (zmin, zmax) = check_axis(o.2, d.2, self.min.2, self.max.2);
+        let tmin = xmin.max(ymin).max(zmin);
+        let tmax = xmax.min(ymax).min(zmax);
+        tmin <= tmax && tmax >= 0.0
+    }
+
+    /// Halves of the box either side of the midpoint of its longest axis
+    pub fn split(&self) -> (Self, Self) {
+        let (dx, dy, dz) = (
+            self.max.0 - self.min.0,
+            self.max.1 - self.min.1,
+            self.max.2 - self.min.2,
+        );
+        let (mut left_max, mut right_min) = (self.max, self.min);
+        if dx >= dy && dx >= dz {
+            left_max.0 = self.min.0 + dx / 2.0;
+            right_min.0 = left_max.0;
+        } else if dy >= dz {
+            left_max.1 = self.min.1 + dy / 2.0;
+            right_min.1 = left_max.1;
+        } else {
+            left_max.2 = self.min.2 + dz / 2.0;
+            right_min.2 = left_max.2;
+        }
+        (
+            Self::new(self.min, left_max),
+            Self::new(right_min, self.max),
+        )
+    }
+
+    pub fn corners(&self) -> [Tup; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            point(min.0, min.1, min.2),
+            point(min.0, min.1, max.2),
+            point(min.0, max.1, min.2),
+            point(min.0, max.1, max.2),
+            point(max.0, min.1, min.2),
+            point(max.0, min.1, max.2),
+            point(max.0, max.1, min.2),
+            point(max.0, max.1, max.2),
+        ]
+    }
+
+    /// Smallest axis aligned box containing this box once transformed. Unbounded boxes stay
+    /// unbounded as infinite corners can't be transformed meaningfully
+    pub fn transform(&self, matrix: &Matrix) -> Self {
+        if !self.is_finite() {
+            return Self::infinite();
+        }
+        let corners = self.corners().map(|c| matrix.mul_tup(c));
+        let (min, max) = corners
+            .iter()
+            .skip(1)
+            .fold((corners[0], corners[0]), |(min, max), c| {
+                (
+                    point(min.0.min(c.0), min.1.min(c.1), min.2.min(c.2)),
+                    point(max.0.max(c.0), max.1.max(c.1), max.2.max(c.2)),
+                )
+            });
+        Self { min, max }
+    }
+}
+
+// distances along the ray at which it enters and leaves the slab between min and max on one axis
+fn check_axis(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+    let (tmin_numerator, tmax_numerator) = (min - origin, max - origin);
+    let (tmin, tmax) = if direction.abs() >= EPSILON {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f64::INFINITY,
+            tmax_numerator * f64::INFINITY,
+        )
+    };
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector, Vector},
+        matrix::matrix::Matrix,
+        ray::ray::Ray,
+        utils::test::ApproxEq,
+    };
+
+    use super::BoundingBox;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn box_contains_points_inside_and_on_its_faces() {
+        let sut = BoundingBox::new(point(5.0, -2.0, 0.0), point(11.0, 4.0, 7.0));
+        for (p, expected) in [
+            (point(5.0, -2.0, 0.0), true),
+            (point(11.0, 4.0, 7.0), true),
+            (point(8.0, 1.0, 3.0), true),
+            (point(3.0, 0.0, 3.0), false),
+            (point(8.0, -4.0, 3.0), false),
+            (point(8.0, 1.0, -1.0), false),
+            (point(13.0, 1.0, 3.0), false),
+            (point(8.0, 5.0, 3.0), false),
+            (point(8.0, 1.0, 8.0), false),
+        ] {
+            assert_eq!(sut.contains_point(p), expected);
+        }
+    }
+
+    #[test]
+    fn box_contains_boxes_within_it() {
+        let sut = BoundingBox::new(point(5.0, -2.0, 0.0), point(11.0, 4.0, 7.0));
+        for (min, max, expected) in [
+            (point(5.0, -2.0, 0.0), point(11.0, 4.0, 7.0), true),
+            (point(6.0, -1.0, 1.0), point(10.0, 3.0, 6.0), true),
+            (point(4.0, -3.0, -1.0), point(10.0, 3.0, 6.0), false),
+            (point(6.0, -1.0, 1.0), point(12.0, 5.0, 8.0), false),
+        ] {
+            assert_eq!(sut.contains_box(&BoundingBox::new(min, max)), expected);
+        }
+    }
+
+    #[test]
+    fn ray_intersects_a_box() {
+        let sut = unit_box();
+        for (origin, direction, expected) in [
+            (point(5.0, 0.5, 0.0), vector(-1.0, 0.0, 0.0), true),
+            (point(-5.0, 0.5, 0.0), vector(1.0, 0.0, 0.0), true),
+            (point(0.5, 5.0, 0.0), vector(0.0, -1.0, 0.0), true),
+            (point(0.0, 0.5, -5.0), vector(0.0, 0.0, 1.0), true),
+            (point(0.0, 0.5, 0.0), vector(0.0, 0.0, 1.0), true),
+            (point(-2.0, 0.0, 0.0), vector(2.0, 4.0, 6.0), false),
+            (point(0.0, -2.0, 0.0), vector(6.0, 2.0, 4.0), false),
+            (point(2.0, 0.0, 2.0), vector(0.0, 0.0, -1.0), false),
+            (point(0.0, 2.0, 2.0), vector(0.0, -1.0, 0.0), false),
+            (point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0), false),
+        ] {
+            assert_eq!(
+                sut.intersects(&Ray::new(origin, direction.norm())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ray_intersects_an_infinite_box() {
+        let ray = Ray::new(point(3.0, 40.0, -7.0), vector(0.0, 1.0, 0.0));
+        assert!(BoundingBox::infinite().intersects(&ray));
+    }
+
+    #[test]
+    fn transforming_a_box_encloses_its_corners() {
+        let sut = unit_box().transform(
+            &Matrix::ident()
+                .scale(2.0, 1.0, 1.0)
+                .translate(5.0, 0.0, 0.0),
+        );
+        sut.min.approx_eq(point(3.0, -1.0, -1.0));
+        sut.max.approx_eq(point(7.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn transforming_an_infinite_box_stays_infinite() {
+        let sut = BoundingBox::infinite().transform(&Matrix::translation(1.0, 0.0, 0.0));
+        assert!(!sut.is_finite());
+    }
+
+    #[test]
+    fn splitting_a_box_halves_its_longest_axis() {
+        for (max, left_max, right_min) in [
+            (
+                point(9.0, 5.0, 7.0),
+                point(4.0, 5.0, 7.0),
+                point(4.0, -2.0, -3.0),
+            ),
+            (
+                point(5.0, 8.0, 7.0),
+                point(5.0, 3.0, 7.0),
+                point(-1.0, 3.0, -3.0),
+            ),
+            (
+                point(5.0, 3.0, 7.0),
+                point(5.0, 3.0, 2.0),
+                point(-1.0, -2.0, 2.0),
+            ),
+        ] {
+            let sut = BoundingBox::new(point(-1.0, -2.0, -3.0), max);
+            let (left, right) = sut.split();
+            assert_eq!(left, BoundingBox::new(sut.min, left_max));
+            assert_eq!(right, BoundingBox::new(right_min, max));
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, vector, Tup},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+    utils::{math_ext::Square, serde_ext::unbounded},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+use crate::geometry::EPSILON;
+
+pub struct ConeBuilder {
+    transform: Matrix,
+    material: Material,
+    minimum: f64,
+    maximum: f64,
+    closed: bool,
+}
+
+impl Default for ConeBuilder {
+    fn default() -> Self {
+        Self {
+            transform: Default::default(),
+            material: Default::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl ConeBuilder {
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = maximum;
+        self
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+}
+
+impl TShapeBuilder for ConeBuilder {
+    type ConcreteOutput = Cone;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        Cone {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            minimum: self.minimum,
+            maximum: self.maximum,
+            closed: self.closed,
+        }
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Double napped cone around the y axis, with its tips meeting at the origin and its radius equal
+/// to the distance from the origin along y, truncated to `minimum < y < maximum`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cone {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    #[serde(with = "unbounded")]
+    pub minimum: f64,
+    #[serde(with = "unbounded")]
+    pub maximum: f64,
+    /// Whether the ends of a truncated cone are capped
+    pub closed: bool,
+}
+
+impl Default for Cone {
+    fn default() -> Self {
+        Cone::builder().build()
+    }
+}
+
+impl Cone {
+    pub fn builder() -> ConeBuilder {
+        ConeBuilder::default()
+    }
+
+    // whether the ray at t is within the radius of a cap at height y
+    fn within_cap(ray: &Ray, t: f64, y: f64) -> bool {
+        let x = ray.origin.0 + t * ray.direction.0;
+        let z = ray.origin.2 + t * ray.direction.2;
+        x.squared() + z.squared() <= y.squared()
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || ray.direction.1.abs() < EPSILON {
+            return;
+        }
+        for cap in [self.minimum, self.maximum] {
+            let t = (cap - ray.origin.1) / ray.direction.1;
+            if Cone::within_cap(ray, t, cap) {
+                xs.push(Intersection::new(t, self.to_trait_ref()));
+            }
+        }
+    }
+}
+
+impl TShape for Cone {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    fn shape_normal_at(&self, local_point: Tup) -> Tup {
+        let dist = local_point.0.squared() + local_point.2.squared();
+        if dist < self.maximum.squared() && local_point.1 >= self.maximum - EPSILON {
+            vector(0.0, 1.0, 0.0)
+        } else if dist < self.minimum.squared() && local_point.1 <= self.minimum + EPSILON {
+            vector(0.0, -1.0, 0.0)
+        } else {
+            // the wall slopes at 45 degrees, away from the axis above the tip and toward it below
+            let y = dist.sqrt();
+            let y = if local_point.1 > 0.0 { -y } else { y };
+            vector(local_point.0, y, local_point.2)
+        }
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = vec![];
+        let (o, d) = (ray.origin, ray.direction);
+
+        let a = d.0.squared() - d.1.squared() + d.2.squared();
+        let b = 2.0 * (o.0 * d.0 - o.1 * d.1 + o.2 * d.2);
+        let c = o.0.squared() - o.1.squared() + o.2.squared();
+
+        let wall_hits = if a.abs() < EPSILON {
+            // parallel to one nappe, so the ray crosses the other once at most
+            if b.abs() < EPSILON {
+                vec![]
+            } else {
+                vec![-c / (2.0 * b)]
+            }
+        } else {
+            let discriminant = b.squared() - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return vec![];
+            }
+            vec![
+                (-b - discriminant.sqrt()) / (2.0 * a),
+                (-b + discriminant.sqrt()) / (2.0 * a),
+            ]
+        };
+        for t in wall_hits {
+            let y = o.1 + t * d.1;
+            if self.minimum < y && y < self.maximum {
+                xs.push(Intersection::new(t, self.to_trait_ref()));
+            }
+        }
+
+        self.intersect_caps(ray, &mut xs);
+        xs
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let radius = self.minimum.abs().max(self.maximum.abs());
+        BoundingBox::new(
+            point(-radius, self.minimum, -radius),
+            point(radius, self.maximum, radius),
+        )
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Cone(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector, Vector},
+        ray::ray::Ray,
+        shapes::shape::{TShape, TShapeBuilder},
+        utils::test::ApproxEq,
+    };
+
+    use super::Cone;
+
+    #[test]
+    fn ray_strikes_cone() {
+        let cone = Cone::default();
+        let cases = [
+            (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 5.0, 5.0),
+            (
+                point(0.0, 0.0, -5.0),
+                vector(1.0, 1.0, 1.0),
+                8.66025,
+                8.66025,
+            ),
+            (
+                point(1.0, 1.0, -5.0),
+                vector(-0.5, -1.0, 1.0),
+                4.55006,
+                49.44994,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases {
+            let xs = cone.shape_intersect(&Ray::new(origin, direction.norm()));
+            assert_eq!(xs.len(), 2);
+            xs[0].at.approx_eq(t0);
+            xs[1].at.approx_eq(t1);
+        }
+    }
+
+    #[test]
+    fn ray_parallel_to_one_half_hits_the_other_once() {
+        let cone = Cone::default();
+        let ray = Ray::new(point(0.0, 0.0, -1.0), vector(0.0, 1.0, 1.0).norm());
+        let xs = cone.shape_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        xs[0].at.approx_eq(0.35355);
+    }
+
+    #[test]
+    fn intersecting_the_caps_of_a_closed_cone() {
+        let cone = Cone::builder()
+            .with_minimum(-0.5)
+            .with_maximum(0.5)
+            .with_closed(true)
+            .build();
+        let cases = [
+            (point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0), 0),
+            (point(0.0, 0.0, -0.25), vector(0.0, 1.0, 1.0), 2),
+            (point(0.0, 0.0, -0.25), vector(0.0, 1.0, 0.0), 4),
+        ];
+        for (origin, direction, count) in cases {
+            let xs = cone.shape_intersect(&Ray::new(origin, direction.norm()));
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn normal_on_cone_wall() {
+        let cone = Cone::default();
+        cone.shape_normal_at(point(0.0, 0.0, 0.0))
+            .approx_eq(vector(0.0, 0.0, 0.0));
+        cone.shape_normal_at(point(1.0, 1.0, 1.0))
+            .approx_eq(vector(1.0, -(2.0_f64.sqrt()), 1.0));
+        cone.shape_normal_at(point(-1.0, -1.0, 0.0))
+            .approx_eq(vector(-1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn normal_on_cone_caps() {
+        let cone = Cone::builder()
+            .with_minimum(-1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .build();
+        cone.shape_normal_at(point(0.5, -1.0, 0.0))
+            .approx_eq(vector(0.0, -1.0, 0.0));
+        cone.shape_normal_at(point(0.0, 2.0, 1.5))
+            .approx_eq(vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_of_cones() {
+        assert!(!Cone::default().bounds().is_finite());
+
+        let sut = Cone::builder()
+            .with_minimum(-5.0)
+            .with_maximum(3.0)
+            .build()
+            .bounds();
+        assert_eq!(sut.min, point(-5.0, -5.0, -5.0));
+        assert_eq!(sut.max, point(5.0, 3.0, 5.0));
+    }
+}
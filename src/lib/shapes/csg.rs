@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::Tup,
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{sort_intersections, Intersection, Ray},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    /// Whether a hit on one child is part of the combined surface, given which child was hit and
+    /// whether the ray is currently inside each child
+    pub fn intersection_allowed(
+        &self,
+        left_hit: bool,
+        inside_left: bool,
+        inside_right: bool,
+    ) -> bool {
+        match self {
+            CsgOperation::Union => (left_hit && !inside_right) || (!left_hit && !inside_left),
+            CsgOperation::Intersection => (left_hit && inside_right) || (!left_hit && inside_left),
+            CsgOperation::Difference => (left_hit && !inside_right) || (!left_hit && inside_left),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CsgBuilder {
+    transform: Matrix,
+    material: Material,
+    operation: CsgOperation,
+    left: Box<dyn TShape>,
+    right: Box<dyn TShape>,
+}
+
+impl TShapeBuilder for CsgBuilder {
+    type ConcreteOutput = Csg;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        let mut csg = Csg {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            operation: self.operation,
+            left: self.left,
+            right: self.right,
+        };
+        csg.set_parent_transform(Matrix::ident());
+        csg
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Constructive solid geometry: the surface left after combining two shapes with an operation.
+/// Like a group it is never hit itself, only its children are
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "CsgBuilder")]
+pub struct Csg {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    // the transform and both sides are only set by the builder, which places the sides in the
+    // csg's space
+    transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    pub operation: CsgOperation,
+    left: Box<dyn TShape>,
+    right: Box<dyn TShape>,
+}
+
+// read back through the builder so both sides are placed in the csg
+impl From<CsgBuilder> for Csg {
+    fn from(builder: CsgBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Csg {
+    pub fn builder(
+        operation: CsgOperation,
+        left: Box<dyn TShape>,
+        right: Box<dyn TShape>,
+    ) -> CsgBuilder {
+        CsgBuilder {
+            transform: Matrix::ident(),
+            material: Material::default(),
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn left(&self) -> &dyn TShape {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn TShape {
+        self.right.as_ref()
+    }
+
+    /// Keeps the intersections, which must be sorted, that lie on the combined surface
+    pub fn filter_intersections<'a>(&self, xs: Vec<Intersection<'a>>) -> Vec<Intersection<'a>> {
+        // every hit on a child flips whether the ray is inside that child
+        let mut inside_left = false;
+        let mut inside_right = false;
+        xs.into_iter()
+            .filter(|i| {
+                let left_hit = self.left.includes(i.object.id());
+                let allowed =
+                    self.operation
+                        .intersection_allowed(left_hit, inside_left, inside_right);
+                if left_hit {
+                    inside_left = !inside_left;
+                } else {
+                    inside_right = !inside_right;
+                }
+                allowed
+            })
+            .collect()
+    }
+}
+
+impl TShape for Csg {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+        let world_transform = self.world_transform();
+        self.left.set_parent_transform(world_transform.clone());
+        self.right.set_parent_transform(world_transform);
+    }
+
+    fn includes(&self, id: Uuid) -> bool {
+        self.id == id || self.left.includes(id) || self.right.includes(id)
+    }
+
+    fn shape_normal_at(&self, _local_point: Tup) -> Tup {
+        unreachable!("csg shapes are never the object of an intersection")
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = self.left.intersect(ray);
+        xs.extend(self.right.intersect(ray));
+        sort_intersections(&mut xs);
+        self.filter_intersections(xs)
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        self.left.divide(threshold);
+        self.right.divide(threshold);
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let left = self.left.transformed_bounds();
+        match self.operation {
+            // nothing outside the left shape survives a difference
+            CsgOperation::Difference => left,
+            _ => left.merge(&self.right.transformed_bounds()),
+        }
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Csg(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector},
+        matrix::matrix::Matrix,
+        ray::ray::{Intersection, Ray},
+        shapes::{
+            shape::{TShape, TShapeBuilder},
+            sphere::Sphere,
+        },
+    };
+
+    use super::{Csg, CsgOperation};
+
+    // (left_hit, inside_left, inside_right) for every combination, in the same order as the
+    // expected results below
+    const CASES: [(bool, bool, bool); 8] = [
+        (true, true, true),
+        (true, true, false),
+        (true, false, true),
+        (true, false, false),
+        (false, true, true),
+        (false, true, false),
+        (false, false, true),
+        (false, false, false),
+    ];
+
+    fn allowed(operation: CsgOperation) -> Vec<bool> {
+        CASES
+            .iter()
+            .map(|(l, il, ir)| operation.intersection_allowed(*l, *il, *ir))
+            .collect()
+    }
+
+    #[test]
+    fn union_keeps_hits_outside_the_other_shape() {
+        assert_eq!(
+            allowed(CsgOperation::Union),
+            vec![false, true, false, true, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn intersection_keeps_hits_inside_the_other_shape() {
+        assert_eq!(
+            allowed(CsgOperation::Intersection),
+            vec![true, false, true, false, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn difference_keeps_left_outside_right_and_right_inside_left() {
+        assert_eq!(
+            allowed(CsgOperation::Difference),
+            vec![false, true, false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn filtering_a_list_of_intersections() {
+        for (operation, first, second) in [
+            (CsgOperation::Union, 1.0, 4.0),
+            (CsgOperation::Intersection, 2.0, 3.0),
+            (CsgOperation::Difference, 1.0, 2.0),
+        ] {
+            let csg = Csg::builder(
+                operation,
+                Sphere::builder().build_trait(),
+                Sphere::builder().build_trait(),
+            )
+            .build();
+            let (s1, s2) = (csg.left.to_trait_ref(), csg.right.to_trait_ref());
+            let xs = vec![
+                Intersection::new(1.0, s1.clone()),
+                Intersection::new(2.0, s2.clone()),
+                Intersection::new(3.0, s1),
+                Intersection::new(4.0, s2),
+            ];
+            let sut: Vec<f64> = csg.filter_intersections(xs).iter().map(|i| i.at).collect();
+            assert_eq!(sut, vec![first, second]);
+        }
+    }
+
+    #[test]
+    fn ray_misses_a_csg_object() {
+        let csg = Csg::builder(
+            CsgOperation::Union,
+            Sphere::builder().build_trait(),
+            Sphere::builder().build_trait(),
+        )
+        .build();
+        let r = Ray::new(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(csg.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_through_a_sphere_with_a_bite_taken_out() {
+        let csg = Csg::builder(
+            CsgOperation::Difference,
+            Sphere::builder().build_trait(),
+            Sphere::builder()
+                .with_transform(Matrix::translation(0.0, 0.0, -1.0))
+                .build_trait(),
+        )
+        .build();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = csg.intersect(&r);
+
+        // the ray enters where the bite ends at z = 0 and leaves through the far side of the
+        // left sphere, so the first hit belongs to the right sphere
+        let sut: Vec<f64> = xs.iter().map(|i| i.at).collect();
+        assert_eq!(sut, vec![5.0, 6.0]);
+        assert_eq!(xs[0].object.id(), csg.right.id());
+        assert_eq!(xs[1].object.id(), csg.left.id());
+    }
+}
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, vector, Tup},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+    utils::{math_ext::Square, serde_ext::unbounded},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+use crate::geometry::EPSILON;
+
+pub struct CylinderBuilder {
+    transform: Matrix,
+    material: Material,
+    minimum: f64,
+    maximum: f64,
+    closed: bool,
+}
+
+impl Default for CylinderBuilder {
+    fn default() -> Self {
+        Self {
+            transform: Default::default(),
+            material: Default::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl CylinderBuilder {
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = maximum;
+        self
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+}
+
+impl TShapeBuilder for CylinderBuilder {
+    type ConcreteOutput = Cylinder;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        Cylinder {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            minimum: self.minimum,
+            maximum: self.maximum,
+            closed: self.closed,
+        }
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Cylinder of radius one around the y axis, truncated to `minimum < y < maximum`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cylinder {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    #[serde(with = "unbounded")]
+    pub minimum: f64,
+    #[serde(with = "unbounded")]
+    pub maximum: f64,
+    /// Whether the ends of a truncated cylinder are capped
+    pub closed: bool,
+}
+
+impl Default for Cylinder {
+    fn default() -> Self {
+        Cylinder::builder().build()
+    }
+}
+
+impl Cylinder {
+    pub fn builder() -> CylinderBuilder {
+        CylinderBuilder::default()
+    }
+
+    // whether the ray at t is within the unit radius of the caps
+    fn within_cap(ray: &Ray, t: f64) -> bool {
+        let x = ray.origin.0 + t * ray.direction.0;
+        let z = ray.origin.2 + t * ray.direction.2;
+        x.squared() + z.squared() <= 1.0
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || ray.direction.1.abs() < EPSILON {
+            return;
+        }
+        for cap in [self.minimum, self.maximum] {
+            let t = (cap - ray.origin.1) / ray.direction.1;
+            if Cylinder::within_cap(ray, t) {
+                xs.push(Intersection::new(t, self.to_trait_ref()));
+            }
+        }
+    }
+}
+
+impl TShape for Cylinder {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    fn shape_normal_at(&self, local_point: Tup) -> Tup {
+        let dist = local_point.0.squared() + local_point.2.squared();
+        if dist < 1.0 && local_point.1 >= self.maximum - EPSILON {
+            vector(0.0, 1.0, 0.0)
+        } else if dist < 1.0 && local_point.1 <= self.minimum + EPSILON {
+            vector(0.0, -1.0, 0.0)
+        } else {
+            vector(local_point.0, 0.0, local_point.2)
+        }
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = vec![];
+
+        let a = ray.direction.0.squared() + ray.direction.2.squared();
+        // rays parallel to the y axis can only hit the caps
+        if a.abs() >= EPSILON {
+            let b = 2.0 * ray.origin.0 * ray.direction.0 + 2.0 * ray.origin.2 * ray.direction.2;
+            let c = ray.origin.0.squared() + ray.origin.2.squared() - 1.0;
+            let discriminant = b.squared() - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return vec![];
+            }
+
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+            for t in [t0, t1] {
+                let y = ray.origin.1 + t * ray.direction.1;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(Intersection::new(t, self.to_trait_ref()));
+                }
+            }
+        }
+
+        self.intersect_caps(ray, &mut xs);
+        xs
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            point(-1.0, self.minimum, -1.0),
+            point(1.0, self.maximum, 1.0),
+        )
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Cylinder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector, Vector},
+        ray::ray::Ray,
+        shapes::shape::{TShape, TShapeBuilder},
+        utils::test::ApproxEq,
+    };
+
+    use super::Cylinder;
+
+    #[test]
+    fn ray_misses_unbounded_cylinder() {
+        let cyl = Cylinder::default();
+        let rays = [
+            Ray::new(point(1.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)),
+            Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)),
+            Ray::new(point(0.0, 0.0, -5.0), vector(1.0, 1.0, 1.0).norm()),
+        ];
+        for ray in rays {
+            assert!(cyl.shape_intersect(&ray).is_empty());
+        }
+    }
+
+    #[test]
+    fn ray_strikes_unbounded_cylinder() {
+        let cyl = Cylinder::default();
+        let cases = [
+            (point(1.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 5.0, 5.0),
+            (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 4.0, 6.0),
+            (
+                point(0.5, 0.0, -5.0),
+                vector(0.1, 1.0, 1.0),
+                6.80798,
+                7.08872,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases {
+            let xs = cyl.shape_intersect(&Ray::new(origin, direction.norm()));
+            assert_eq!(xs.len(), 2);
+            xs[0].at.approx_eq(t0);
+            xs[1].at.approx_eq(t1);
+        }
+    }
+
+    #[test]
+    fn normal_on_cylinder_wall_is_radial() {
+        let cyl = Cylinder::default();
+        cyl.shape_normal_at(point(1.0, 0.0, 0.0))
+            .approx_eq(vector(1.0, 0.0, 0.0));
+        cyl.shape_normal_at(point(0.0, 5.0, -1.0))
+            .approx_eq(vector(0.0, 0.0, -1.0));
+        cyl.shape_normal_at(point(-1.0, 1.0, 0.0))
+            .approx_eq(vector(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn intersecting_a_truncated_cylinder() {
+        let cyl = Cylinder::builder()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .build();
+        let cases = [
+            (point(0.0, 1.5, 0.0), vector(0.1, 1.0, 0.0), 0),
+            (point(0.0, 3.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+            (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+            (point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+            (point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+            (point(0.0, 1.5, -2.0), vector(0.0, 0.0, 1.0), 2),
+        ];
+        for (origin, direction, count) in cases {
+            let xs = cyl.shape_intersect(&Ray::new(origin, direction.norm()));
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn intersecting_the_caps_of_a_closed_cylinder() {
+        let cyl = Cylinder::builder()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .build();
+        let cases = [
+            (point(0.0, 3.0, 0.0), vector(0.0, -1.0, 0.0), 2),
+            (point(0.0, 3.0, -2.0), vector(0.0, -1.0, 2.0), 2),
+            (point(0.0, 4.0, -2.0), vector(0.0, -1.0, 1.0), 2),
+            (point(0.0, 0.0, -2.0), vector(0.0, 1.0, 2.0), 2),
+            (point(0.0, -1.0, -2.0), vector(0.0, 1.0, 1.0), 2),
+        ];
+        for (origin, direction, count) in cases {
+            let xs = cyl.shape_intersect(&Ray::new(origin, direction.norm()));
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn normal_on_cylinder_caps() {
+        let cyl = Cylinder::builder()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .build();
+        cyl.shape_normal_at(point(0.0, 1.0, 0.0))
+            .approx_eq(vector(0.0, -1.0, 0.0));
+        cyl.shape_normal_at(point(0.5, 1.0, 0.0))
+            .approx_eq(vector(0.0, -1.0, 0.0));
+        cyl.shape_normal_at(point(0.0, 2.0, 0.5))
+            .approx_eq(vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_of_cylinders() {
+        let unbounded = Cylinder::default().bounds();
+        assert!(!unbounded.is_finite());
+        assert_eq!(unbounded.min.0, -1.0);
+
+        let sut = Cylinder::builder()
+            .with_minimum(-5.0)
+            .with_maximum(3.0)
+            .build()
+            .bounds();
+        assert_eq!(sut.min, point(-1.0, -5.0, -1.0));
+        assert_eq!(sut.max, point(1.0, 3.0, 1.0));
+    }
+}
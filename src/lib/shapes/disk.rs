@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, vector, Tup},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+    utils::math_ext::Square,
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+use crate::geometry::EPSILON;
+
+pub struct DiskBuilder {
+    transform: Matrix,
+    material: Material,
+    inner_radius: f64,
+    outer_radius: f64,
+}
+
+impl Default for DiskBuilder {
+    fn default() -> Self {
+        Self {
+            transform: Default::default(),
+            material: Default::default(),
+            inner_radius: 0.0,
+            outer_radius: 1.0,
+        }
+    }
+}
+
+impl DiskBuilder {
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+
+    pub fn with_outer_radius(mut self, outer_radius: f64) -> Self {
+        self.outer_radius = outer_radius;
+        self
+    }
+}
+
+impl TShapeBuilder for DiskBuilder {
+    type ConcreteOutput = Disk;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        Disk {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            inner_radius: self.inner_radius,
+            outer_radius: self.outer_radius,
+        }
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Flat ring in the xz plane around the origin, covering `inner_radius <= r <= outer_radius`.
+/// An inner radius of zero gives a solid disk
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Disk {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Disk::builder().build()
+    }
+}
+
+impl Disk {
+    pub fn builder() -> DiskBuilder {
+        DiskBuilder::default()
+    }
+}
+
+impl TShape for Disk {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    fn shape_normal_at(&self, _local_point: Tup) -> Tup {
+        vector(0.0, 1.0, 0.0)
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        if ray.direction.1.abs() <= EPSILON {
+            return vec![];
+        }
+        let t = -ray.origin.1 / ray.direction.1;
+        let x = ray.origin.0 + t * ray.direction.0;
+        let z = ray.origin.2 + t * ray.direction.2;
+        let distance = x.squared() + z.squared();
+        if distance < self.inner_radius.squared() || distance > self.outer_radius.squared() {
+            return vec![];
+        }
+        vec![Intersection::new(t, self.to_trait_ref())]
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let r = self.outer_radius;
+        BoundingBox::new(point(-r, 0.0, -r), point(r, 0.0, r))
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Disk(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector},
+        ray::ray::Ray,
+        shapes::shape::{TShape, TShapeBuilder},
+        utils::test::ApproxEq,
+    };
+
+    use super::Disk;
+
+    fn ring() -> Disk {
+        Disk::builder()
+            .with_inner_radius(0.5)
+            .with_outer_radius(2.0)
+            .build()
+    }
+
+    #[test]
+    fn ray_hits_the_ring() {
+        let sut = ring();
+        for origin in [
+            point(1.0, 3.0, 0.0),
+            point(0.0, 3.0, -1.9),
+            point(-0.5, 3.0, 0.0),
+        ] {
+            let xs = sut.shape_intersect(&Ray::new(origin, vector(0.0, -1.0, 0.0)));
+            assert_eq!(xs.len(), 1);
+            xs[0].at.approx_eq(3.0);
+        }
+    }
+
+    #[test]
+    fn ray_misses_through_the_hole() {
+        let sut = ring();
+        let ray = Ray::new(point(0.2, 3.0, 0.2), vector(0.0, -1.0, 0.0));
+        assert!(sut.shape_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_outside_the_outer_radius() {
+        let sut = ring();
+        let ray = Ray::new(point(1.5, 3.0, 1.5), vector(0.0, -1.0, 0.0));
+        assert!(sut.shape_intersect(&ray).is_empty());
+        let parallel = Ray::new(point(1.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(sut.shape_intersect(&parallel).is_empty());
+    }
+
+    #[test]
+    fn normal_is_always_up() {
+        let sut = ring();
+        for p in [point(1.0, 0.0, 0.0), point(0.0, 0.0, -1.5)] {
+            sut.shape_normal_at(p).approx_eq(vector(0.0, 1.0, 0.0));
+        }
+    }
+}
@@ -0,0 +1,504 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::Tup,
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+#[derive(Default, Deserialize)]
+pub struct GroupBuilder {
+    transform: Matrix,
+    material: Material,
+    children: Vec<Box<dyn TShape>>,
+}
+
+impl GroupBuilder {
+    pub fn with_child(mut self, child: Box<dyn TShape>) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+impl TShapeBuilder for GroupBuilder {
+    type ConcreteOutput = Group;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        let mut group = Group {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            children: vec![],
+            bounds: BoundingBox::infinite(),
+        };
+        for child in self.children {
+            group.add_child(child);
+        }
+        group
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Collection of shapes which are transformed together. A group is never hit itself, only its
+/// children are, so its material is unused
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "GroupBuilder")]
+pub struct Group {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    /// Changing this after children have been added leaves them in the old position, set it via
+    /// the builder instead
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    // only changed through `add_child`, which keeps `bounds` up to date
+    children: Vec<Box<dyn TShape>>,
+    #[serde(skip)]
+    bounds: BoundingBox,
+}
+
+// read back through the builder so children are placed in the group and its bounds computed
+impl From<GroupBuilder> for Group {
+    fn from(builder: GroupBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group::builder().build()
+    }
+}
+
+impl Group {
+    pub fn builder() -> GroupBuilder {
+        GroupBuilder::default()
+    }
+
+    pub fn children(&self) -> &[Box<dyn TShape>] {
+        &self.children
+    }
+
+    /// Adds a shape to the group, placing it in the group's space
+    pub fn add_child(&mut self, mut child: Box<dyn TShape>) {
+        child.set_parent_transform(self.world_transform());
+        let child_bounds = child.transformed_bounds();
+        self.bounds = if self.children.is_empty() {
+            child_bounds
+        } else {
+            self.bounds.merge(&child_bounds)
+        };
+        self.children.push(child);
+    }
+
+    /// Takes the children which fit wholly inside one half of the group's bounds out into a new
+    /// subgroup for that half, which is not yet added back. Children straddling the split, or
+    /// unbounded, stay where they are
+    fn partition_children(&mut self) -> (Option<Group>, Option<Group>) {
+        let Some(finite) = self
+            .children
+            .iter()
+            .map(|c| c.transformed_bounds())
+            .filter(|b| b.is_finite())
+            .reduce(|acc, b| acc.merge(&b))
+        else {
+            return (None, None);
+        };
+        let (left_box, right_box) = finite.split();
+        let count = self.children.len();
+        let (mut left, mut right, mut remaining) = (vec![], vec![], vec![]);
+        for child in std::mem::take(&mut self.children) {
+            let b = child.transformed_bounds();
+            if left_box.contains_box(&b) {
+                left.push(child);
+            } else if right_box.contains_box(&b) {
+                right.push(child);
+            } else {
+                remaining.push(child);
+            }
+        }
+        // a subgroup holding every child would be no better than this group, and dividing it
+        // again would never end
+        let mut subgroup = |half: Vec<Box<dyn TShape>>| {
+            if half.len() == count {
+                remaining = half;
+                None
+            } else if half.is_empty() {
+                None
+            } else {
+                let mut subgroup = Group::default();
+                for child in half {
+                    subgroup.add_child(child);
+                }
+                Some(subgroup)
+            }
+        };
+        let halves = (subgroup(left), subgroup(right));
+        self.children = vec![];
+        for child in remaining {
+            self.add_child(child);
+        }
+        halves
+    }
+}
+
+impl TShape for Group {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+        let world_transform = self.world_transform();
+        for child in self.children.iter_mut() {
+            child.set_parent_transform(world_transform.clone());
+        }
+    }
+
+    fn includes(&self, id: Uuid) -> bool {
+        self.id == id || self.children.iter().any(|c| c.includes(id))
+    }
+
+    // a group has no surface of its own, normals always come from the child that was hit
+    fn shape_normal_at(&self, _local_point: Tup) -> Tup {
+        unreachable!("groups are never the object of an intersection")
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        if !self.bounds.intersects(ray) {
+            return vec![];
+        }
+        ray.intersect_objects(&self.children)
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        let (left, right) = if self.children.len() > threshold {
+            self.partition_children()
+        } else {
+            (None, None)
+        };
+        for child in self.children.iter_mut() {
+            child.divide(threshold);
+        }
+        // the halves share no children, so their subtrees are built concurrently. Subgroups are
+        // added back in a fixed order so the tree doesn't depend on which finishes first
+        let divided = |half: Option<Group>| {
+            half.map(|mut g| {
+                g.divide(threshold);
+                g
+            })
+        };
+        let (left, right) = rayon::join(|| divided(left), || divided(right));
+        for subgroup in [left, right].into_iter().flatten() {
+            self.add_child(Box::new(subgroup));
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Group(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{
+        geometry::vector::{point, vector, Operations, Vector},
+        matrix::matrix::{Axis, Matrix},
+        ray::ray::Ray,
+        shapes::{
+            shape::{TShape, TShapeBuilder},
+            sphere::Sphere,
+            triangle::Triangle,
+        },
+        utils::test::ApproxEq,
+    };
+    use uuid::Uuid;
+
+    use super::Group;
+
+    #[test]
+    fn intersecting_an_empty_group_misses() {
+        let g = Group::default();
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        assert!(g.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn intersecting_a_group_sorts_hits_across_children() {
+        let mut g = Group::default();
+        g.add_child(Sphere::builder().build_trait());
+        g.add_child(
+            Sphere::builder()
+                .with_transform(Matrix::translation(0.0, 0.0, -3.0))
+                .build_trait(),
+        );
+        g.add_child(
+            Sphere::builder()
+                .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+                .build_trait(),
+        );
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let sut: Vec<f64> = g.intersect(&r).iter().map(|i| i.at).collect();
+        assert_eq!(sut, vec![1.0, 3.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn intersecting_a_transformed_group() {
+        let g = Group::builder()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .with_child(
+                Sphere::builder()
+                    .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+                    .build_trait(),
+            )
+            .build();
+        let r = Ray::new(point(10.0, 0.0, -10.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn normal_of_a_child_accounts_for_enclosing_groups() {
+        let inner = Group::builder()
+            .with_transform(Matrix::scaling(1.0, 2.0, 3.0))
+            .with_child(
+                Sphere::builder()
+                    .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+                    .build_trait(),
+            )
+            .build_trait();
+        let outer = Group::builder()
+            .with_transform(Matrix::rotation(Axis::Y, PI / 2.0))
+            .with_child(inner)
+            .build();
+
+        // cast from the sphere's centre so the only hit in front of the ray is the target point
+        let root3 = 3.0_f64.sqrt();
+        let target = point(root3, 2.0 / root3, -5.0 - 1.0 / root3);
+        let centre = point(0.0, 0.0, -5.0);
+        let r = Ray::new(centre, target.sub(centre));
+        let xs = outer.intersect(&r);
+        let hit = xs.iter().find(|i| i.at > 0.0).unwrap();
+        let sut = hit.object.normal_at(r.position(hit.at)).unwrap();
+        sut.approx_eq(vector(2.0 / 7.0, 3.0 / 7.0, -6.0 / 7.0));
+    }
+
+    // the book's sphere, translated inside a scaled group inside a rotated group, and found by
+    // casting a ray at it since the group only hands out its children as trait objects
+    fn nested_sphere(inner_scaling: Matrix) -> Group {
+        let inner = Group::builder()
+            .with_transform(inner_scaling)
+            .with_child(sphere_at(5.0, 0.0, 0.0))
+            .build_trait();
+        Group::builder()
+            .with_transform(Matrix::rotation(Axis::Y, PI / 2.0))
+            .with_child(inner)
+            .build()
+    }
+
+    #[test]
+    fn converting_a_point_from_world_to_object_space() {
+        let g = nested_sphere(Matrix::scaling(2.0, 2.0, 2.0));
+        let xs = g.intersect(&Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, -1.0)));
+        let sut = xs[0]
+            .object
+            .world_to_object(point(-2.0, 0.0, -10.0))
+            .unwrap();
+        sut.approx_eq(point(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn converting_a_normal_from_object_to_world_space() {
+        let g = nested_sphere(Matrix::scaling(1.0, 2.0, 3.0));
+        let xs = g.intersect(&Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, -1.0)));
+        let root3 = 3.0_f64.sqrt() / 3.0;
+        let sut = xs[0]
+            .object
+            .normal_to_world(vector(root3, root3, root3))
+            .unwrap();
+        sut.approx_eq(vector(2.0 / 7.0, 3.0 / 7.0, -6.0 / 7.0));
+    }
+
+    #[test]
+    fn group_bounds_are_the_union_of_transformed_children() {
+        let g = Group::builder()
+            .with_child(
+                Sphere::builder()
+                    .with_transform(Matrix::scaling(2.0, 2.0, 2.0).translate(2.0, 5.0, -3.0))
+                    .build_trait(),
+            )
+            .with_child(
+                Sphere::builder()
+                    .with_transform(Matrix::translation(-4.0, -1.0, 4.0))
+                    .build_trait(),
+            )
+            .build();
+        let sut = g.bounds();
+        sut.min.approx_eq(point(-5.0, -2.0, -5.0));
+        sut.max.approx_eq(point(4.0, 7.0, 5.0));
+    }
+
+    fn sphere_at(x: f64, y: f64, z: f64) -> Box<dyn TShape> {
+        Sphere::builder()
+            .with_transform(Matrix::translation(x, y, z))
+            .build_trait()
+    }
+
+    #[test]
+    fn dividing_moves_children_into_subgroups_for_each_half() {
+        let (left, right, straddling) = (
+            sphere_at(-2.0, 0.0, 0.0),
+            sphere_at(2.0, 0.0, 0.0),
+            Sphere::new(),
+        );
+        let ids = (left.id(), right.id(), straddling.id());
+        let mut g = Group::builder()
+            .with_child(left)
+            .with_child(right)
+            .with_child(Box::new(straddling))
+            .build();
+        g.divide(1);
+
+        assert_eq!(g.children.len(), 3);
+        assert_eq!(g.children[0].id(), ids.2);
+        assert!(g.children[1].id() != ids.0 && g.children[1].includes(ids.0));
+        assert!(g.children[2].id() != ids.1 && g.children[2].includes(ids.1));
+    }
+
+    #[test]
+    fn dividing_leaves_groups_at_or_under_the_threshold_alone() {
+        let mut g = Group::builder()
+            .with_child(sphere_at(-2.0, 0.0, 0.0))
+            .with_child(sphere_at(2.0, 0.0, 0.0))
+            .with_child(sphere_at(0.0, 2.0, 0.0))
+            .build();
+        let before: Vec<Uuid> = g.children.iter().map(|c| c.id()).collect();
+        g.divide(3);
+        let after: Vec<Uuid> = g.children.iter().map(|c| c.id()).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn dividing_preserves_intersections() {
+        let mut g = Group::builder()
+            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+            .build();
+        for i in 0..64 {
+            let (x, y, z) = ((i % 4) as f64, ((i / 4) % 4) as f64, (i / 16) as f64);
+            g.add_child(sphere_at(x * 3.0, y * 3.0, z * 3.0));
+        }
+        let rays = [
+            Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)),
+            Ray::new(point(1.5, 1.5, -5.0), vector(0.0, 0.0, 1.0)),
+            Ray::new(point(-2.0, 0.2, 0.1), vector(1.0, 0.1, 0.2).norm()),
+            Ray::new(point(-2.0, -2.0, -2.0), vector(1.0, 1.0, 1.0).norm()),
+            Ray::new(point(20.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)),
+        ];
+        let hits = |g: &Group| -> Vec<Vec<(f64, Uuid)>> {
+            rays.iter()
+                .map(|r| {
+                    g.intersect(r)
+                        .iter()
+                        .map(|i| (i.at, i.object.id()))
+                        .collect()
+                })
+                .collect()
+        };
+        let before = hits(&g);
+        assert!(before.iter().filter(|xs| !xs.is_empty()).count() >= 3);
+
+        g.divide(4);
+        assert!(g.children.len() < 64);
+        assert_eq!(hits(&g), before);
+    }
+
+    // 1000 small triangles spread through a 10x10x10 lattice
+    fn triangle_lattice() -> Group {
+        let mut g = Group::default();
+        for i in 0..1000 {
+            let corner = point((i % 10) as f64, ((i / 10) % 10) as f64, (i / 100) as f64);
+            g.add_child(Box::new(Triangle::new(
+                corner,
+                corner.add(vector(0.5, 0.0, 0.0)),
+                corner.add(vector(0.0, 0.5, 0.0)),
+            )));
+        }
+        g
+    }
+
+    #[test]
+    fn parallel_division_matches_sequential_division() {
+        let mut parallel = triangle_lattice();
+        parallel.divide(4);
+        // a single thread runs both sides of each join in turn
+        let mut sequential = triangle_lattice();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| sequential.divide(4));
+
+        assert_eq!(parallel.children.len(), sequential.children.len());
+        let eye = point(4.5, 4.5, -20.0);
+        for i in 0..100 {
+            let target = point((i % 10) as f64 + 0.1, (i / 10) as f64 + 0.1, 5.0);
+            let r = Ray::new(eye, target.sub(eye).norm());
+            let hits = |g: &Group| -> Vec<f64> { g.intersect(&r).iter().map(|x| x.at).collect() };
+            let expected = hits(&sequential);
+            assert!(!expected.is_empty());
+            assert_eq!(hits(&parallel), expected);
+        }
+    }
+}
@@ -1,3 +1,13 @@
+pub mod bounds;
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disk;
+pub mod group;
 pub mod plane;
+pub mod serial;
 pub mod shape;
+pub mod smooth_triangle;
 pub mod sphere;
+pub mod torus;
+pub mod triangle;
@@ -2,29 +2,32 @@
 
 use std::ops::Neg;
 
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 use crate::{
-    geometry::vector::{vector, Tup},
+    geometry::{
+        vector::{point, vector, Tup},
+        EPSILON,
+    },
     material::material::Material,
     matrix::matrix::Matrix,
     ray::ray::{Intersection, Ray},
 };
 
-use super::{shape::TShape, sphere::SphereBuilder};
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape},
+    sphere::SphereBuilder,
+};
 
+#[derive(Default)]
 pub struct PlaneBuilder {
     material: Material,
     transform: Matrix,
 }
 
-impl Default for PlaneBuilder {
-    fn default() -> Self {
-        Self {
-            material: Default::default(),
-            transform: Default::default(),
-        }
-    }
-}
-
 impl PlaneBuilder {
     pub fn new() -> Self {
         PlaneBuilder::default()
@@ -42,22 +45,34 @@ impl PlaneBuilder {
 
     pub fn build(self) -> Plane {
         Plane {
+            id: Uuid::new_v4(),
             transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: self.material,
         }
     }
     pub fn build_trait(self) -> Box<dyn TShape> {
         Box::new(Plane {
+            id: Uuid::new_v4(),
             transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: self.material,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Plane {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
     material: Material,
     transform: Matrix,
+    #[serde(skip)]
+    parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
 }
 
 impl Plane {
@@ -69,13 +84,20 @@ impl Plane {
 impl Default for Plane {
     fn default() -> Self {
         Self {
+            id: Uuid::new_v4(),
             transform: Default::default(),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: Default::default(),
         }
     }
 }
 
 impl TShape for Plane {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
@@ -84,18 +106,41 @@ impl TShape for Plane {
         &self.transform
     }
 
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
     fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection> {
-        if ray.direction.1.abs() <= 0.00001 {
+        if ray.direction.1.abs() <= EPSILON {
             return vec![];
         };
         let t = ray.origin.1.neg() / ray.direction.1;
         return vec![Intersection::new(t, self.to_trait_ref())];
     }
 
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            point(f64::INFINITY, 0.0, f64::INFINITY),
+        )
+    }
+
     fn to_trait_ref(&self) -> Box<&dyn TShape> {
         Box::new(self)
     }
 
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Plane(self)
+    }
+
     fn shape_normal_at(&self, local_point: Tup) -> Tup {
         vector(0.0, 1.0, 0.0) // normal is constant for plane
     }
@@ -161,4 +206,11 @@ mod tests {
         let object = *i.object.as_ref();
         assert!(std::ptr::eq(*i.object.as_ref(), *p1.to_trait_ref()));
     }
+
+    #[test]
+    fn plane_is_flat_and_unbounded_in_x_and_z() {
+        let sut = Plane::default().bounds();
+        assert_eq!(sut.min, point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY));
+        assert_eq!(sut.max, point(f64::INFINITY, 0.0, f64::INFINITY));
+    }
 }
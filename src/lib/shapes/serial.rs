@@ -0,0 +1,64 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    cone::Cone, csg::Csg, cylinder::Cylinder, disk::Disk, group::Group, plane::Plane,
+    shape::TShape, smooth_triangle::SmoothTriangle, sphere::Sphere, torus::Torus,
+    triangle::Triangle,
+};
+
+/// A shape borrowed as its concrete type, so boxed shapes can be written out with a `type` field
+/// naming what they are. Ids and parent transforms are left out: shapes read back get new ids,
+/// and groups place their children again as they are rebuilt
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum TaggedShape<'a> {
+    Sphere(&'a Sphere),
+    Plane(&'a Plane),
+    Cone(&'a Cone),
+    Cylinder(&'a Cylinder),
+    Disk(&'a Disk),
+    Torus(&'a Torus),
+    Triangle(&'a Triangle),
+    SmoothTriangle(&'a SmoothTriangle),
+    Group(&'a Group),
+    Csg(&'a Csg),
+}
+
+// mirrors `TaggedShape` to read shapes back
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum OwnedShape {
+    Sphere(Sphere),
+    Plane(Plane),
+    Cone(Cone),
+    Cylinder(Cylinder),
+    Disk(Disk),
+    Torus(Torus),
+    Triangle(Triangle),
+    SmoothTriangle(SmoothTriangle),
+    Group(Group),
+    Csg(Csg),
+}
+
+impl Serialize for dyn TShape {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn TShape> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OwnedShape::deserialize(deserializer)? {
+            OwnedShape::Sphere(s) => Box::new(s),
+            OwnedShape::Plane(s) => Box::new(s),
+            OwnedShape::Cone(s) => Box::new(s),
+            OwnedShape::Cylinder(s) => Box::new(s),
+            OwnedShape::Disk(s) => Box::new(s),
+            OwnedShape::Torus(s) => Box::new(s),
+            OwnedShape::Triangle(s) => Box::new(s),
+            OwnedShape::SmoothTriangle(s) => Box::new(s),
+            OwnedShape::Group(s) => Box::new(s),
+            OwnedShape::Csg(s) => Box::new(s),
+        })
+    }
+}
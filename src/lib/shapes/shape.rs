@@ -1,40 +1,101 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
+
+use uuid::Uuid;
 
 use crate::{
-    geometry::vector::{Tup, Vector},
+    geometry::{
+        uv::planar_map,
+        vector::{Tup, Vector},
+    },
     material::material::Material,
-    matrix::matrix::Matrix,
+    matrix::matrix::{InverseCache, Matrix},
     ray::ray::{Intersection, Ray},
 };
 
+use super::{bounds::BoundingBox, serial::TaggedShape};
+
+/// Inverses of a shape's own and world transforms, kept so they are not recomputed for every
+/// ray and normal
+#[derive(Debug, Default)]
+pub struct ShapeInverses {
+    transform: InverseCache,
+    world: InverseCache,
+}
+
 pub trait TShape: Sync + Send + Debug {
+    fn id(&self) -> Uuid;
     fn material(&self) -> &Material;
     fn transform(&self) -> &Matrix;
 
+    /// Combined transform of every group enclosing the shape, identity when it isn't in a group
+    fn parent_transform(&self) -> &Matrix;
+
+    /// Called by a group when the shape is added to it, or when the group itself is moved
+    fn set_parent_transform(&mut self, matrix: Matrix);
+
+    /// Transform from object space to world space through the whole parent chain
+    fn world_transform(&self) -> Matrix {
+        self.parent_transform().mul(self.transform())
+    }
+
+    fn inverses(&self) -> &ShapeInverses;
+
+    fn transform_inverse(&self) -> Option<Arc<Matrix>> {
+        self.inverses().transform.inverse_of(self.transform())
+    }
+
+    fn world_inverse(&self) -> Option<Arc<Matrix>> {
+        self.inverses()
+            .world
+            .inverse_of_product(self.parent_transform(), self.transform())
+    }
+
+    /// Whether the shape is, or contains, the shape with the given id
+    fn includes(&self, id: Uuid) -> bool {
+        self.id() == id
+    }
+
+    /// Converts a world space point to object space through every enclosing group
+    fn world_to_object(&self, world_point: Tup) -> Option<Tup> {
+        self.world_inverse().map(|m| m.mul_tup(world_point))
+    }
+
+    /// Converts an object space normal to a unit world space normal through every enclosing
+    /// group. Normals go back through the transpose of the inverse, not the transform itself
+    fn normal_to_world(&self, object_normal: Tup) -> Option<Tup> {
+        let p = self.world_inverse()?.transpose().mul_tup(object_normal);
+        Some((p.0, p.1, p.2, 0.0).norm())
+    }
+
     fn normal_at(&self, world_point: Tup) -> Option<Tup> {
-        let maybe_local_normal = self
-            .transform()
-            .inverse()
-            .map(|m| m.mul_tup(world_point))
-            .map(|p| self.shape_normal_at(p)); // delegate to shape specific implementation
+        let local_point = self.world_to_object(world_point)?;
+        self.normal_to_world(self.shape_normal_at(local_point))
+    }
 
-        let world_normal = maybe_local_normal.and_then(|object_norm| {
-            self.transform()
-                .transpose()
-                .inverse()
-                .map(|p| p.mul_tup(object_norm))
-        });
-        world_normal.map(|p| (p.0, p.1, p.2, 0.0).norm())
+    /// Like `normal_at`, but lets shapes use what the intersection recorded about the hit
+    fn normal_at_hit(&self, world_point: Tup, hit: &Intersection) -> Option<Tup> {
+        let local_point = self.world_to_object(world_point)?;
+        self.normal_to_world(self.shape_normal_at_hit(local_point, hit))
     }
 
     fn shape_normal_at(&self, local_point: Tup) -> Tup;
 
+    fn shape_normal_at_hit(&self, local_point: Tup, _hit: &Intersection) -> Tup {
+        self.shape_normal_at(local_point)
+    }
+
     fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection>;
 
+    /// Texture coordinates in [0, 1) of a point in object space, mapped onto the xz plane
+    /// unless the shape has a more natural mapping
+    fn uv_at(&self, local_point: Tup) -> (f64, f64) {
+        planar_map(local_point)
+    }
+
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
         // applies the shapes transform to the ray before passing this ray to the Shape specific
         // implementation of intersect 'shape_intersect'
-        let maybe_shape_transform = self.transform().inverse();
+        let maybe_shape_transform = self.transform_inverse();
         if let Some(shape_transform) = maybe_shape_transform {
             let local_ray = ray.transform(&shape_transform);
             return self.shape_intersect(&local_ray);
@@ -42,8 +103,26 @@ pub trait TShape: Sync + Send + Debug {
         return vec![];
     }
 
+    /// Bounds of the shape in its own object space. Shapes are unbounded unless they say otherwise
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::infinite()
+    }
+
+    /// Bounds of the shape once its transform has been applied
+    fn transformed_bounds(&self) -> BoundingBox {
+        self.bounds().transform(self.transform())
+    }
+
+    /// Reorganises any children into a hierarchy of groups holding no more than `threshold`
+    /// shapes each where possible, so rays can skip whole subtrees. Leaf shapes have nothing to
+    /// divide
+    fn divide(&mut self, _threshold: usize) {}
+
     /// required to pass self to intersection, which must accept a reference to any shape
     fn to_trait_ref(&self) -> Box<&dyn TShape>;
+
+    /// The shape as its concrete type, for serialising boxed shapes
+    fn tagged(&self) -> TaggedShape<'_>;
 }
 
 pub trait TShapeBuilder {
@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, Operations, Tup, Vector},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+    triangle::intersect_triangle,
+};
+
+#[derive(Deserialize)]
+pub struct SmoothTriangleBuilder {
+    p1: Tup,
+    p2: Tup,
+    p3: Tup,
+    n1: Tup,
+    n2: Tup,
+    n3: Tup,
+    transform: Matrix,
+    material: Material,
+}
+
+impl TShapeBuilder for SmoothTriangleBuilder {
+    type ConcreteOutput = SmoothTriangle;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        let mut triangle =
+            SmoothTriangle::new(self.p1, self.p2, self.p3, self.n1, self.n2, self.n3);
+        triangle.transform = self.transform;
+        triangle.material = self.material;
+        triangle
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Triangle whose normal is interpolated between normals given at each corner, so that a mesh
+/// of them shades as a smooth surface
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "SmoothTriangleBuilder")]
+pub struct SmoothTriangle {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    pub p1: Tup,
+    pub p2: Tup,
+    pub p3: Tup,
+    pub n1: Tup,
+    pub n2: Tup,
+    pub n3: Tup,
+    #[serde(skip)]
+    pub e1: Tup,
+    #[serde(skip)]
+    pub e2: Tup,
+    #[serde(skip)]
+    pub normal: Tup,
+}
+
+// read back through the builder so the edges and normal are worked out from the corners
+impl From<SmoothTriangleBuilder> for SmoothTriangle {
+    fn from(builder: SmoothTriangleBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl SmoothTriangle {
+    pub fn new(p1: Tup, p2: Tup, p3: Tup, n1: Tup, n2: Tup, n3: Tup) -> Self {
+        let e1 = p2.sub(p1);
+        let e2 = p3.sub(p1);
+        Self {
+            id: Uuid::new_v4(),
+            transform: Matrix::ident(),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: Material::default(),
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1,
+            e2,
+            normal: e2.cross_prod(e1).norm(),
+        }
+    }
+
+    pub fn builder(p1: Tup, p2: Tup, p3: Tup, n1: Tup, n2: Tup, n3: Tup) -> SmoothTriangleBuilder {
+        SmoothTriangleBuilder {
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            transform: Matrix::ident(),
+            material: Material::default(),
+        }
+    }
+}
+
+impl TShape for SmoothTriangle {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    // without a hit to interpolate at, fall back to the flat face normal
+    fn shape_normal_at(&self, _local_point: Tup) -> Tup {
+        self.normal
+    }
+
+    fn shape_normal_at_hit(&self, local_point: Tup, hit: &Intersection) -> Tup {
+        match (hit.u, hit.v) {
+            (Some(u), Some(v)) => self
+                .n2
+                .mul(u)
+                .add(self.n3.mul(v))
+                .add(self.n1.mul(1.0 - u - v)),
+            _ => self.shape_normal_at(local_point),
+        }
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        intersect_triangle(ray, self.p1, self.e1, self.e2)
+            .map(|(t, u, v)| vec![Intersection::with_uv(t, self.to_trait_ref(), u, v)])
+            .unwrap_or_default()
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let (a, b, c) = (self.p1, self.p2, self.p3);
+        BoundingBox::new(
+            point(
+                a.0.min(b.0).min(c.0),
+                a.1.min(b.1).min(c.1),
+                a.2.min(b.2).min(c.2),
+            ),
+            point(
+                a.0.max(b.0).max(c.0),
+                a.1.max(b.1).max(c.1),
+                a.2.max(b.2).max(c.2),
+            ),
+        )
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::SmoothTriangle(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector, Vector},
+        ray::ray::Ray,
+        shapes::shape::TShape,
+        utils::test::ApproxEq,
+    };
+
+    use super::SmoothTriangle;
+
+    fn smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            point(0.0, 1.0, 0.0),
+            point(-1.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+            vector(-1.0, 0.0, 0.0),
+            vector(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn intersection_stores_u_and_v() {
+        let t = smooth_triangle();
+        let ray = Ray::new(point(-0.2, 0.3, -2.0), vector(0.0, 0.0, 1.0));
+        let xs = t.shape_intersect(&ray);
+        xs[0].u.unwrap().approx_eq(0.45);
+        xs[0].v.unwrap().approx_eq(0.25);
+    }
+
+    #[test]
+    fn normal_is_interpolated_from_u_and_v() {
+        let t = smooth_triangle();
+        let ray = Ray::new(point(-0.2, 0.3, -2.0), vector(0.0, 0.0, 1.0));
+        let xs = t.intersect(&ray);
+        let sut = t.normal_at_hit(point(0.0, 0.0, 0.0), &xs[0]).unwrap();
+        sut.approx_eq(vector(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn normal_at_centroid_blends_all_corners() {
+        let t = smooth_triangle();
+        let centroid = point(0.0, 1.0 / 3.0, 0.0);
+        let ray = Ray::new(point(0.0, 1.0 / 3.0, -2.0), vector(0.0, 0.0, 1.0));
+        let xs = t.intersect(&ray);
+        let sut = t.normal_at_hit(centroid, &xs[0]).unwrap();
+        sut.approx_eq(vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn precomputed_normal_is_interpolated() {
+        let t = smooth_triangle();
+        let ray = Ray::new(point(-0.2, 0.3, -2.0), vector(0.0, 0.0, 1.0));
+        let xs = t.intersect(&ray);
+        let comps = ray.prep_comp(&xs[0], &vec![&xs[0]]).unwrap();
+        comps
+            .normal()
+            .approx_eq(vector(-0.5547, 0.83205, 0.0).norm());
+    }
+
+    #[test]
+    fn edges_are_rebuilt_when_deserialising() {
+        let mut json = serde_json::to_value(smooth_triangle()).unwrap();
+        assert!(json.get("e1").is_none());
+        json["p1"] = serde_json::json!([0.0, 2.0, 0.0, 1.0]);
+
+        let sut: SmoothTriangle = serde_json::from_value(json).unwrap();
+
+        assert_eq!(sut.e1, vector(-1.0, -2.0, 0.0));
+        assert_eq!(sut.e2, vector(1.0, -2.0, 0.0));
+    }
+}
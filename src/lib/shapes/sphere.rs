@@ -1,15 +1,23 @@
 #![allow(unused_variables, dead_code)]
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    geometry::vector::{point, Operations, Tup, Vector},
+    geometry::{
+        uv::spherical_map,
+        vector::{point, Operations, Tup, Vector},
+    },
     material::material::Material,
     matrix::matrix::Matrix,
     ray::ray::{Intersection, Ray},
     utils::math_ext::Square,
 };
 
-use super::shape::{TShape, TShapeBuilder};
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
 
 pub struct SphereBuilder {
     transform: Option<Matrix>,
@@ -43,6 +51,8 @@ impl TShapeBuilder for SphereBuilder {
         Sphere {
             id: Uuid::new_v4(),
             transform: self.transform.unwrap_or(Matrix::ident()),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: self.material.unwrap_or(Material::default()),
         }
     }
@@ -51,15 +61,22 @@ impl TShapeBuilder for SphereBuilder {
         Box::new(Sphere {
             id: Uuid::new_v4(),
             transform: self.transform.unwrap_or(Matrix::ident()),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: self.material.unwrap_or(Material::default()),
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Sphere {
+    #[serde(skip, default = "Uuid::new_v4")]
     pub id: Uuid,
     pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
     pub material: Material,
 }
 
@@ -68,6 +85,8 @@ impl Default for Sphere {
         Self {
             id: Default::default(),
             transform: Default::default(),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
             material: Default::default(),
         }
     }
@@ -88,6 +107,10 @@ impl Sphere {
 }
 
 impl TShape for Sphere {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
@@ -96,6 +119,18 @@ impl TShape for Sphere {
         &self.transform
     }
 
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
     fn shape_normal_at(&self, local_point: Tup) -> Tup {
         local_point.sub(point(0.0, 0.0, 0.0))
     }
@@ -122,9 +157,21 @@ impl TShape for Sphere {
         vec![i1, i2]
     }
 
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0))
+    }
+
+    fn uv_at(&self, local_point: Tup) -> (f64, f64) {
+        spherical_map(local_point)
+    }
+
     fn to_trait_ref(&self) -> Box<&dyn TShape> {
         Box::new(self)
     }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Sphere(self)
+    }
 }
 
 #[cfg(test)]
@@ -134,6 +181,7 @@ mod tests {
     use crate::{
         geometry::vector::{point, vector},
         matrix::matrix::{Axis, Matrix},
+        ray::ray::Ray,
         shapes::shape::{TShape, TShapeBuilder},
         utils::test::ApproxEq,
     };
@@ -220,4 +268,27 @@ mod tests {
         let sut = s.normal_at(point(0.0, 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0));
         sut.unwrap().approx_eq(vector(0.0, 0.97014, -0.24254));
     }
+
+    #[test]
+    fn changing_the_transform_after_use_replaces_the_cached_inverse() {
+        let mut s = Sphere::new();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(s.intersect(&r).len(), 2);
+        s.normal_at(point(0.0, 0.0, -1.0))
+            .unwrap()
+            .approx_eq(vector(0.0, 0.0, -1.0));
+
+        s.transform = Matrix::translation(5.0, 0.0, 0.0);
+        assert!(s.intersect(&r).is_empty());
+        s.normal_at(point(5.0, 0.0, -1.0))
+            .unwrap()
+            .approx_eq(vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn sphere_is_bounded_by_the_unit_cube() {
+        let sut = Sphere::new().bounds();
+        assert_eq!(sut.min, point(-1.0, -1.0, -1.0));
+        assert_eq!(sut.max, point(1.0, 1.0, 1.0));
+    }
 }
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, vector, Tup, Vector},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+    utils::math_ext::{solve_quartic, Square},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+pub struct TorusBuilder {
+    transform: Matrix,
+    material: Material,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Default for TorusBuilder {
+    fn default() -> Self {
+        Self {
+            transform: Default::default(),
+            material: Default::default(),
+            major_radius: 1.0,
+            minor_radius: 0.25,
+        }
+    }
+}
+
+impl TorusBuilder {
+    pub fn with_major_radius(mut self, major_radius: f64) -> Self {
+        self.major_radius = major_radius;
+        self
+    }
+
+    pub fn with_minor_radius(mut self, minor_radius: f64) -> Self {
+        self.minor_radius = minor_radius;
+        self
+    }
+}
+
+impl TShapeBuilder for TorusBuilder {
+    type ConcreteOutput = Torus;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        Torus {
+            id: Uuid::new_v4(),
+            transform: self.transform,
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: self.material,
+            major_radius: self.major_radius,
+            minor_radius: self.minor_radius,
+        }
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Ring around the y axis, with its tube of `minor_radius` centred `major_radius` from the
+/// origin in the xz plane. Hits are the roots of a quartic, found numerically to well within
+/// `EPSILON` of the true distance
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Torus {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Default for Torus {
+    fn default() -> Self {
+        Torus::builder().build()
+    }
+}
+
+impl Torus {
+    pub fn builder() -> TorusBuilder {
+        TorusBuilder::default()
+    }
+}
+
+impl TShape for Torus {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    // gradient of (|p|^2 - R^2 - r^2)^2 - 4R^2 (r^2 - y^2), the implicit surface
+    fn shape_normal_at(&self, local_point: Tup) -> Tup {
+        let (x, y, z) = (local_point.0, local_point.1, local_point.2);
+        let major = self.major_radius.squared();
+        let param = x.squared() + y.squared() + z.squared() - major - self.minor_radius.squared();
+        vector(x * param, y * (param + 2.0 * major), z * param).norm()
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let (o, d) = (ray.origin, ray.direction);
+        let four_major = 4.0 * self.major_radius.squared();
+        let origin = vector(o.0, o.1, o.2);
+        let dd = d.dot(d);
+        let od = origin.dot(d);
+        let e = origin.dot(origin) - self.major_radius.squared() - self.minor_radius.squared();
+
+        solve_quartic(
+            dd.squared(),
+            4.0 * dd * od,
+            2.0 * dd * e + 4.0 * od.squared() + four_major * d.1.squared(),
+            4.0 * od * e + 2.0 * four_major * o.1 * d.1,
+            e.squared() - four_major * (self.minor_radius.squared() - o.1.squared()),
+        )
+        .into_iter()
+        .map(|t| Intersection::new(t, self.to_trait_ref()))
+        .collect()
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let (outer, r) = (self.major_radius + self.minor_radius, self.minor_radius);
+        BoundingBox::new(point(-outer, -r, -outer), point(outer, r, outer))
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Torus(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector},
+        ray::ray::Ray,
+        shapes::shape::TShape,
+        utils::test::ApproxEq,
+    };
+
+    use super::Torus;
+
+    #[test]
+    fn ray_along_the_x_axis_crosses_the_tube_twice() {
+        let sut = Torus::default();
+        let xs = sut.shape_intersect(&Ray::new(point(-5.0, 0.0, 0.0), vector(1.0, 0.0, 0.0)));
+        assert_eq!(xs.len(), 4);
+        for (i, expected) in xs.iter().zip([3.75, 4.25, 5.75, 6.25]) {
+            i.at.approx_eq(expected);
+        }
+    }
+
+    #[test]
+    fn ray_down_through_the_tube() {
+        let sut = Torus::default();
+        let xs = sut.shape_intersect(&Ray::new(point(0.0, 5.0, -1.0), vector(0.0, -1.0, 0.0)));
+        assert_eq!(xs.len(), 2);
+        xs[0].at.approx_eq(4.75);
+        xs[1].at.approx_eq(5.25);
+    }
+
+    #[test]
+    fn ray_misses_through_the_hole_and_beside_the_ring() {
+        let sut = Torus::default();
+        for origin in [point(0.0, 5.0, 0.0), point(1.5, 5.0, 0.0)] {
+            let xs = sut.shape_intersect(&Ray::new(origin, vector(0.0, -1.0, 0.0)));
+            assert!(xs.is_empty());
+        }
+    }
+
+    #[test]
+    fn normal_points_away_from_the_centre_of_the_tube() {
+        let sut = Torus::default();
+        for (p, expected) in [
+            (point(1.25, 0.0, 0.0), vector(1.0, 0.0, 0.0)),
+            (point(0.75, 0.0, 0.0), vector(-1.0, 0.0, 0.0)),
+            (point(0.0, 0.25, 1.0), vector(0.0, 1.0, 0.0)),
+            (point(0.0, 0.0, -1.25), vector(0.0, 0.0, -1.0)),
+        ] {
+            sut.shape_normal_at(p).approx_eq(expected);
+        }
+    }
+}
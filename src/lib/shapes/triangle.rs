@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    geometry::vector::{point, Operations, Tup, Vector},
+    material::material::Material,
+    matrix::matrix::Matrix,
+    ray::ray::{Intersection, Ray},
+};
+
+use super::{
+    bounds::BoundingBox,
+    serial::TaggedShape,
+    shape::{ShapeInverses, TShape, TShapeBuilder},
+};
+
+use crate::geometry::EPSILON;
+
+/// Moller-Trumbore intersection of a ray with the triangle at `p1` spanned by the edges `e1` and
+/// `e2`. Gives the distance along the ray and the barycentric `u` and `v` of the hit
+pub(crate) fn intersect_triangle(ray: &Ray, p1: Tup, e1: Tup, e2: Tup) -> Option<(f64, f64, f64)> {
+    let dir_cross_e2 = ray.direction.cross_prod(e2);
+    let det = e1.dot(dir_cross_e2);
+    if det.abs() < EPSILON {
+        // ray is parallel to the triangle
+        return None;
+    }
+
+    let f = 1.0 / det;
+    let p1_to_origin = ray.origin.sub(p1);
+    let u = f * p1_to_origin.dot(dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross_prod(e1);
+    let v = f * ray.direction.dot(origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    Some((f * e2.dot(origin_cross_e1), u, v))
+}
+
+#[derive(Deserialize)]
+pub struct TriangleBuilder {
+    p1: Tup,
+    p2: Tup,
+    p3: Tup,
+    transform: Matrix,
+    material: Material,
+}
+
+impl TShapeBuilder for TriangleBuilder {
+    type ConcreteOutput = Triangle;
+    type AbstractOutput = Box<dyn TShape>;
+
+    fn with_transform(mut self, matrix: Matrix) -> Self {
+        self.transform = matrix;
+        self
+    }
+
+    fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    fn build(self) -> Self::ConcreteOutput {
+        let mut triangle = Triangle::new(self.p1, self.p2, self.p3);
+        triangle.transform = self.transform;
+        triangle.material = self.material;
+        triangle
+    }
+
+    fn build_trait(self) -> Self::AbstractOutput {
+        Box::new(self.build())
+    }
+}
+
+/// Flat triangle between three points, whose front face is the one from which the points run
+/// anticlockwise
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "TriangleBuilder")]
+pub struct Triangle {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub transform: Matrix,
+    #[serde(skip)]
+    pub parent_transform: Matrix,
+    #[serde(skip)]
+    inverses: ShapeInverses,
+    pub material: Material,
+    pub p1: Tup,
+    pub p2: Tup,
+    pub p3: Tup,
+    #[serde(skip)]
+    pub e1: Tup,
+    #[serde(skip)]
+    pub e2: Tup,
+    #[serde(skip)]
+    pub normal: Tup,
+}
+
+// read back through the builder so the edges and normal are worked out from the corners
+impl From<TriangleBuilder> for Triangle {
+    fn from(builder: TriangleBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Triangle {
+    pub fn new(p1: Tup, p2: Tup, p3: Tup) -> Self {
+        let e1 = p2.sub(p1);
+        let e2 = p3.sub(p1);
+        Self {
+            id: Uuid::new_v4(),
+            transform: Matrix::ident(),
+            parent_transform: Matrix::ident(),
+            inverses: ShapeInverses::default(),
+            material: Material::default(),
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal: e2.cross_prod(e1).norm(),
+        }
+    }
+
+    pub fn builder(p1: Tup, p2: Tup, p3: Tup) -> TriangleBuilder {
+        TriangleBuilder {
+            p1,
+            p2,
+            p3,
+            transform: Matrix::ident(),
+            material: Material::default(),
+        }
+    }
+}
+
+impl TShape for Triangle {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    fn inverses(&self) -> &ShapeInverses {
+        &self.inverses
+    }
+
+    fn parent_transform(&self) -> &Matrix {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, matrix: Matrix) {
+        self.parent_transform = matrix;
+    }
+
+    fn shape_normal_at(&self, _local_point: Tup) -> Tup {
+        self.normal
+    }
+
+    fn shape_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        intersect_triangle(ray, self.p1, self.e1, self.e2)
+            .map(|(t, _, _)| vec![Intersection::new(t, self.to_trait_ref())])
+            .unwrap_or_default()
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let (a, b, c) = (self.p1, self.p2, self.p3);
+        BoundingBox::new(
+            point(
+                a.0.min(b.0).min(c.0),
+                a.1.min(b.1).min(c.1),
+                a.2.min(b.2).min(c.2),
+            ),
+            point(
+                a.0.max(b.0).max(c.0),
+                a.1.max(b.1).max(c.1),
+                a.2.max(b.2).max(c.2),
+            ),
+        )
+    }
+
+    fn to_trait_ref(&self) -> Box<&dyn TShape> {
+        Box::new(self)
+    }
+
+    fn tagged(&self) -> TaggedShape<'_> {
+        TaggedShape::Triangle(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::vector::{point, vector},
+        ray::ray::Ray,
+        shapes::shape::TShape,
+        utils::test::ApproxEq,
+    };
+
+    use super::Triangle;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            point(0.0, 1.0, 0.0),
+            point(-1.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn constructing_a_triangle_precomputes_edges_and_normal() {
+        let t = triangle();
+        assert_eq!(t.e1, vector(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2, vector(1.0, -1.0, 0.0));
+        t.normal.approx_eq(vector(0.0, 0.0, -1.0));
+        t.shape_normal_at(point(0.0, 0.5, 0.0))
+            .approx_eq(vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn ray_parallel_to_triangle_misses() {
+        let ray = Ray::new(point(0.0, -1.0, -2.0), vector(0.0, 1.0, 0.0));
+        assert!(triangle().shape_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p1_p3_edge() {
+        let ray = Ray::new(point(1.0, 1.0, -2.0), vector(0.0, 0.0, 1.0));
+        assert!(triangle().shape_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p1_p2_edge() {
+        let ray = Ray::new(point(-1.0, 1.0, -2.0), vector(0.0, 0.0, 1.0));
+        assert!(triangle().shape_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p2_p3_edge() {
+        let ray = Ray::new(point(0.0, -1.0, -2.0), vector(0.0, 0.0, 1.0));
+        assert!(triangle().shape_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_strikes_triangle() {
+        let ray = Ray::new(point(0.0, 0.5, -2.0), vector(0.0, 0.0, 1.0));
+        let t = triangle();
+        let xs = t.shape_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        xs[0].at.approx_eq(2.0);
+    }
+
+    #[test]
+    fn edges_and_normal_are_rebuilt_when_deserialising() {
+        let mut json = serde_json::to_value(triangle()).unwrap();
+        assert!(json.get("normal").is_none());
+        json["p3"] = serde_json::json!([0.0, 0.0, 1.0, 1.0]);
+
+        let sut: Triangle = serde_json::from_value(json).unwrap();
+
+        assert_eq!(sut.e2, vector(0.0, -1.0, 1.0));
+        sut.normal
+            .approx_eq(Triangle::new(sut.p1, sut.p2, sut.p3).normal);
+    }
+}
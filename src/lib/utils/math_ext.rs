@@ -7,3 +7,134 @@ impl Square for f64 {
         self * self
     }
 }
+
+/// Uniform numbers in [0, 1) from a generator seeded with `seed` and the bits of `values`.
+/// Seeding from positions keeps results independent of the order pixels render in
+pub fn seeded_random(seed: u64, values: &[f64]) -> impl FnMut() -> f64 {
+    let mut state = values.iter().fold(seed, |acc, v| {
+        (acc ^ v.to_bits()).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }) | 1;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Real roots of `a x^4 + b x^3 + c x^2 + d x + e`, ascending. See `real_roots` for accuracy
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    real_roots(&[a, b, c, d, e])
+}
+
+/// Real roots of the polynomial with `coefficients` from the highest power down, ascending.
+/// Each root is bracketed between turning points, found from the roots of the derivative, then
+/// bisected until the bracket is within about 1e-12 of the root relative to its size. Repeated
+/// roots, where the curve only touches zero, are kept when the polynomial at the turning point
+/// is within 1e-9 of zero relative to the largest coefficient
+pub fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    // leading zeros lower the degree
+    let Some(first) = coefficients.iter().position(|c| *c != 0.0) else {
+        return vec![];
+    };
+    let coefficients = &coefficients[first..];
+    let degree = coefficients.len() - 1;
+    match degree {
+        0 => return vec![],
+        1 => return vec![-coefficients[1] / coefficients[0]],
+        _ => {}
+    }
+
+    let eval = |x: f64| coefficients.iter().fold(0.0, |acc, c| acc * x + c);
+    let derivative: Vec<f64> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect();
+    let touching = 1e-9
+        * coefficients
+            .iter()
+            .fold(0.0, |acc: f64, c| acc.max(c.abs()));
+
+    // every real root lies within the cauchy bound
+    let bound = 1.0
+        + coefficients[1..]
+            .iter()
+            .fold(0.0, |acc: f64, c| acc.max((c / coefficients[0]).abs()));
+    let mut edges = vec![-bound];
+    edges.extend(
+        real_roots(&derivative)
+            .into_iter()
+            .filter(|x| x.abs() < bound),
+    );
+    edges.push(bound);
+
+    let mut roots: Vec<f64> = vec![];
+    let mut push = |root: f64| {
+        if roots.last().is_none_or(|last| (root - last).abs() > 1e-9) {
+            roots.push(root);
+        }
+    };
+    for (i, pair) in edges.windows(2).enumerate() {
+        let (mut lo, mut hi) = (pair[0], pair[1]);
+        if i > 0 && eval(lo).abs() <= touching {
+            push(lo);
+            continue;
+        }
+        if eval(lo).signum() == eval(hi).signum() {
+            continue;
+        }
+        for _ in 0..200 {
+            let mid = (lo + hi) / 2.0;
+            if hi - lo <= 1e-12 * mid.abs().max(1.0) {
+                break;
+            }
+            if eval(mid).signum() == eval(lo).signum() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        push((lo + hi) / 2.0);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test::ApproxEq;
+
+    use super::{real_roots, solve_quartic};
+
+    #[test]
+    fn quartic_with_four_distinct_roots() {
+        // (x + 2)(x + 1)(x - 1)(x - 3)
+        let sut = solve_quartic(1.0, -1.0, -7.0, 1.0, 6.0);
+        assert_eq!(sut.len(), 4);
+        for (root, expected) in sut.iter().zip([-2.0, -1.0, 1.0, 3.0]) {
+            root.approx_eq(expected);
+        }
+    }
+
+    #[test]
+    fn quartic_with_no_real_roots() {
+        assert!(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn repeated_root_is_found_once() {
+        // (x - 2)^2 (x + 1)
+        let sut = real_roots(&[1.0, -3.0, 0.0, 4.0]);
+        assert_eq!(sut.len(), 2);
+        sut[0].approx_eq(-1.0);
+        sut[1].approx_eq(2.0);
+    }
+
+    #[test]
+    fn leading_zeros_lower_the_degree() {
+        let sut = real_roots(&[0.0, 0.0, 1.0, -4.0, 3.0]);
+        assert_eq!(sut.len(), 2);
+        sut[0].approx_eq(1.0);
+        sut[1].approx_eq(3.0);
+    }
+}
@@ -1,2 +1,3 @@
 pub mod math_ext;
+pub mod serde_ext;
 pub mod test;
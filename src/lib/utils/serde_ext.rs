@@ -0,0 +1,77 @@
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+/// For `f64` fields which may be infinite, such as the limits of an unbounded cylinder. JSON has
+/// no number for infinity, so infinities are written as the strings "inf" and "-inf"
+pub mod unbounded {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match *value {
+            f64::INFINITY => serializer.serialize_str("inf"),
+            f64::NEG_INFINITY => serializer.serialize_str("-inf"),
+            v => serializer.serialize_f64(v),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Number(f64),
+            Named(String),
+        }
+        match Value::deserialize(deserializer)? {
+            Value::Number(v) => Ok(v),
+            Value::Named(name) => match name.as_str() {
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(D::Error::custom(format!(
+                    "expected a number, \"inf\" or \"-inf\", found {name:?}"
+                ))),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Limits {
+        #[serde(with = "super::unbounded")]
+        min: f64,
+        #[serde(with = "super::unbounded")]
+        max: f64,
+    }
+
+    #[test]
+    fn unbounded_values_round_trip_through_json() {
+        for limits in [
+            Limits {
+                min: -1.5,
+                max: 2.0,
+            },
+            Limits {
+                min: f64::NEG_INFINITY,
+                max: f64::INFINITY,
+            },
+        ] {
+            let json = serde_json::to_string(&limits).unwrap();
+            assert_eq!(serde_json::from_str::<Limits>(&json).unwrap(), limits);
+        }
+        assert_eq!(
+            serde_json::to_string(&Limits {
+                min: f64::NEG_INFINITY,
+                max: 1.0
+            })
+            .unwrap(),
+            r#"{"min":"-inf","max":1.0}"#
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert!(serde_json::from_str::<Limits>(r#"{"min":"huge","max":1.0}"#).is_err());
+    }
+}
@@ -1,6 +1,13 @@
 use core::panic;
 
-use crate::{colour::colour::Colour, geometry::vector::Tup, matrix::matrix::Matrix};
+use crate::{
+    colour::colour::Colour,
+    geometry::{
+        typed::{Point, Vector3},
+        vector::Tup,
+    },
+    matrix::matrix::Matrix,
+};
 
 trait ToU32 {
     fn to_u32(&self) -> u32;
@@ -26,6 +33,22 @@ impl ApproxEq for Tup {
     }
 }
 
+impl ApproxEq for Point {
+    type Type = Self;
+
+    fn approx_eq(self, other: Self::Type) {
+        tup_approx_eq(self.into(), other.into());
+    }
+}
+
+impl ApproxEq for Vector3 {
+    type Type = Self;
+
+    fn approx_eq(self, other: Self::Type) {
+        tup_approx_eq(self.into(), other.into());
+    }
+}
+
 impl ApproxEq for Colour {
     type Type = Self;
 
@@ -1,87 +1,665 @@
 #![allow(unused_imports, unused_variables, dead_code)]
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 use crate::{
     colour::colour::Colour,
-    geometry::vector::{point, Operations, Tup, Vector},
-    light::light::PointLight,
-    material::material::Material,
+    geometry::vector::{orthonormal_basis, point, refract, vector, Operations, Tup, Vector},
+    light::light::{towards, PointLight, TLight},
+    material::{material::Material, pattern::TPattern},
     matrix::matrix::Matrix,
-    ray::ray::{Hit, Intersection, PreComp, Ray},
+    ray::ray::{sort_intersections, Hit, Intersection, PreComp, Ray},
     shapes::{
         shape::{TShape, TShapeBuilder},
         sphere::Sphere,
     },
+    utils::math_ext::seeded_random,
 };
 
+/// How many times rays are followed off reflective and through transparent surfaces when no
+/// depth is given
+pub const DEFAULT_RECURSION_DEPTH: u32 = 5;
+
+// reflections contributing less than this fraction of a pixel's colour are not traced
+const DEFAULT_REFLECTION_EPSILON: f64 = 0.001;
+
+// seed for the hemisphere rays of the diffuse bounce so renders are repeatable
+const DEFAULT_BOUNCE_SEED: u64 = 2207;
+
+/// Counters collected while rendering a world. Counting is off by default since every render
+/// thread updating the same counters slows parallel renders down
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    pub enabled: bool,
+    rays_traced: AtomicUsize,
+    intersection_tests: AtomicUsize,
+}
+
+impl RenderStats {
+    /// Number of camera and reflection rays traced through `color_at`
+    pub fn rays_traced(&self) -> usize {
+        self.rays_traced.load(Ordering::Relaxed)
+    }
+
+    /// Number of ray-object intersection tests, including those for shadows
+    pub fn intersection_tests(&self) -> usize {
+        self.intersection_tests.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.rays_traced.store(0, Ordering::Relaxed);
+        self.intersection_tests.store(0, Ordering::Relaxed);
+    }
+
+    fn record_ray(&self) {
+        if self.enabled {
+            self.rays_traced.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_intersection_test(&self) {
+        if self.enabled {
+            self.intersection_tests.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// the exact position of a point on a light and the grid cell of a shaded point
+type ShadowKey = ([u64; 3], [i64; 3]);
+
+/// Shadow results remembered per light for each cell of a grid over world space, so scenes with
+/// static lights and objects only cast one shadow ray per cell. Points sharing a cell share a
+/// result, so the cell size should be small next to the detail of the shadows
+#[derive(Debug)]
+pub struct ShadowCache {
+    pub cell_size: f64,
+    entries: RwLock<HashMap<ShadowKey, bool>>,
+}
+
+impl ShadowCache {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+
+    fn get_or_insert_with(&self, point: Tup, light: Tup, f: impl FnOnce() -> bool) -> bool {
+        let cell = |v: f64| (v / self.cell_size).floor() as i64;
+        let l = light;
+        let key = (
+            [l.0.to_bits(), l.1.to_bits(), l.2.to_bits()],
+            [cell(point.0), cell(point.1), cell(point.2)],
+        );
+        if let Some(shadowed) = self.entries.read().unwrap().get(&key) {
+            return *shadowed;
+        }
+        let shadowed = f();
+        self.entries.write().unwrap().insert(key, shadowed);
+        shadowed
+    }
+}
+
+/// Where a ray cast into a world first hits it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitRecord {
+    pub object_id: Uuid,
+    pub point: Tup,
+    /// Surface normal at the point, facing back along the ray
+    pub normal: Tup,
+    /// Distance along the ray
+    pub t: f64,
+    /// Unlit colour of the surface at the point, including any pattern
+    pub colour: Colour,
+}
+
+/// Plane which cuts away everything on the side its normal points toward, for cutaway views
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlane {
+    pub point: Tup,
+    pub normal: Tup,
+}
+
+impl ClipPlane {
+    pub fn new(point: Tup, normal: Tup) -> Self {
+        Self { point, normal }
+    }
+
+    pub fn clips(&self, p: Tup) -> bool {
+        p.sub(self.point).dot(self.normal) > 0.0
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct World {
     pub objects: Vec<Box<dyn TShape>>,
-    pub light: PointLight,
+    /// Point, area and directional lights alike
+    pub lights: Vec<Box<dyn TLight>>,
+    /// Light reaching every surface from no particular direction, added once to each shaded
+    /// point and tinted by its colour. A material's own `ambient` instead scales the share of
+    /// each light it picks up regardless of shadows, so the two add rather than overlap. Black,
+    /// the default, adds nothing
+    pub ambient: Colour,
+    /// Colour of rays which hit nothing
+    pub background: Colour,
+    /// Looked up with the direction of rays which hit nothing, as a point on the unit sphere in
+    /// place of `background`. A gradient along y makes a simple sky
+    pub environment: Option<Box<dyn TPattern>>,
+    /// Intersections cut away by any of these planes are ignored, for rays and shadows alike
+    pub clip_planes: Vec<ClipPlane>,
+    /// Reflection stops once the product of reflectivities along a path falls below this
+    pub reflection_epsilon: f64,
+    /// Rays gathered over the hemisphere of each shaded point to pick up light bounced off
+    /// nearby diffuse surfaces. Zero, the default, turns the bounce off
+    pub diffuse_bounce_samples: usize,
+    pub diffuse_bounce_seed: u64,
+    /// Reflected paths in `color_at` whose throughput falls below this are randomly stopped
+    /// rather than traced to the reflection limit. Survivors are weighted up to make up for the
+    /// stopped ones, so the average over many seeds is unchanged. Zero, the default, turns it off
+    pub roulette_threshold: f64,
+    pub roulette_seed: u64,
+    /// Reuses shadow results between renders when set. Must be cleared whenever lights or
+    /// objects move
+    #[serde(skip)]
+    pub shadow_cache: Option<ShadowCache>,
+    #[serde(skip)]
+    pub stats: RenderStats,
 }
 
 impl World {
-    pub fn new(objects: Vec<Box<dyn TShape>>, light: PointLight) -> Self {
-        Self { objects, light }
+    pub fn new(objects: Vec<Box<dyn TShape>>, light: impl TLight + 'static) -> Self {
+        Self::with_lights(objects, vec![Box::new(light)])
+    }
+
+    pub fn with_lights(objects: Vec<Box<dyn TShape>>, lights: Vec<Box<dyn TLight>>) -> Self {
+        Self {
+            objects,
+            lights,
+            ambient: Colour::black(),
+            background: Colour::black(),
+            environment: None,
+            clip_planes: vec![],
+            reflection_epsilon: DEFAULT_REFLECTION_EPSILON,
+            diffuse_bounce_samples: 0,
+            diffuse_bounce_seed: DEFAULT_BOUNCE_SEED,
+            roulette_threshold: 0.0,
+            roulette_seed: DEFAULT_BOUNCE_SEED,
+            shadow_cache: None,
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// Keeps only the objects with the given ids, which is useful for debugging a single object
+    /// in a complex scene. Everything else renders as background and casts no shadow
+    pub fn with_only(mut self, ids: &[Uuid]) -> Self {
+        self.objects.retain(|o| ids.contains(&o.id()));
+        self
+    }
+
+    /// The world as JSON, with each shape, light and pattern tagged with its type. Caches and
+    /// render statistics are left out
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<World> {
+        serde_json::from_str(json)
+    }
+
+    /// Moves the objects and lights of `other` into this world
+    pub fn merge(&mut self, other: World) {
+        self.objects.extend(other.objects);
+        self.lights.extend(other.lights);
+    }
+
+    /// `color_at` with reflections and refractions followed to `DEFAULT_RECURSION_DEPTH`
+    pub fn color_at_default(&self, ray: &Ray) -> Colour {
+        self.color_at(ray, DEFAULT_RECURSION_DEPTH)
     }
 
     pub fn color_at(&self, ray: &Ray, ref_lim: u32) -> Colour {
-        let intersections: Vec<Intersection> = ray.intersect_objects(&self.objects);
+        self.trace(ray, ref_lim, 1.0, None)
+    }
+
+    /// Like `color_at`, but the ray is only tested against the objects flagged in `visible`,
+    /// letting the camera skip objects outside its view. Reflections and shadows still consider
+    /// every object
+    pub fn color_at_visible(&self, ray: &Ray, ref_lim: u32, visible: &[bool]) -> Colour {
+        self.trace(ray, ref_lim, 1.0, Some(visible))
+    }
 
-        let maybe_intersection = intersections.hit();
+    // throughput is the accumulated reflectivity of the path which spawned this ray
+    fn trace(&self, ray: &Ray, ref_lim: u32, throughput: f64, visible: Option<&[bool]>) -> Colour {
+        self.stats.record_ray();
+        let intersections: Vec<Intersection> = self.intersect(ray, visible);
+        let Some(comps) = nearest_comps(ray, &intersections) else {
+            return self.background_for(ray);
+        };
 
-        let maybe_precomp = maybe_intersection.and_then(|i| ray.prep_comp(i, &vec![&i]));
+        let surface = self.shade(&comps) + self.diffuse_bounce(&comps);
 
-        let is_shadowed = maybe_precomp
-            .as_ref()
-            .map(|pc| self.is_shadowed(pc.over_point))
-            .unwrap_or(false);
+        // a limit of 0 is treated like 1, shading the surface without following any more rays
+        let remaining = ref_lim.saturating_sub(1);
+        let refracted = self.refracted_colour(&comps, remaining);
+        let reflected = self.reflected_colour(&comps, remaining, throughput);
+
+        // shadowed surfaces keep their ambient colour, and can still reflect or show what is behind
+        // them
+        let colour = match World::fresnel_reflectance(&comps) {
+            Some(r) => surface + reflected * r + refracted * (1.0 - r),
+            None => surface + reflected + refracted,
+        };
+
+        // the continued ray always moves on past the surface, so it needs no depth limit
+        match World::see_through(&comps, ray) {
+            Some((opacity, behind)) => {
+                colour * opacity
+                    + self.trace(&behind, ref_lim, throughput, visible) * (1.0 - opacity)
+            }
+            None => colour,
+        }
+    }
+
+    /// What a ray which hits nothing sees
+    pub fn background_for(&self, ray: &Ray) -> Colour {
+        let Some(environment) = &self.environment else {
+            return self.background;
+        };
+        let d = ray.direction.norm();
+        environment
+            .transform()
+            .inverse()
+            .map(|i| environment.pattern_at(i.mul_tup(point(d.0, d.1, d.2))))
+            .unwrap_or(self.background)
+    }
 
-        if is_shadowed {
+    /// Opacity of a partly opaque hit and the ray carrying on unbent beyond it
+    fn see_through(comps: &PreComp, ray: &Ray) -> Option<(f64, Ray)> {
+        let opacity = comps.object.material().opacity;
+        (opacity < 1.0).then(|| (opacity, Ray::new(comps.under_point, ray.direction)))
+    }
+
+    /// Light arriving at the point after one bounce off other surfaces, gathered from cosine
+    /// weighted rays over the hemisphere around the normal. Only direct lighting is taken from
+    /// the surfaces those rays hit, so the bounce never recurses
+    fn diffuse_bounce(&self, comps: &PreComp) -> Colour {
+        let samples = self.diffuse_bounce_samples;
+        let material = comps.object.material();
+        if samples == 0 || material.diffuse == 0.0 {
+            return Colour::black();
+        }
+
+        let p = comps.over_point;
+        let mut random = seeded_random(self.diffuse_bounce_seed, &[p.0, p.1, p.2]);
+
+        let (tangent, bitangent, n) = orthonormal_basis(comps.normal());
+
+        let incoming = (0..samples).fold(Colour::black(), |acc, _| {
+            let (r1, r2) = (random(), random());
+            let phi = 2.0 * std::f64::consts::PI * r1;
+            let r = r2.sqrt();
+            let direction = tangent
+                .mul(r * phi.cos())
+                .add(bitangent.mul(r * phi.sin()))
+                .add(n.mul((1.0 - r2).sqrt()));
+            acc + self.direct_colour(&Ray::new(p, direction))
+        });
+        incoming
+            * (1.0 / samples as f64)
+            * material.colour_at(comps.object.to_trait_ref(), comps.point)
+            * material.diffuse
+    }
+
+    // direct lighting of whatever the ray hits, without reflections or bounced light
+    fn direct_colour(&self, ray: &Ray) -> Colour {
+        self.stats.record_ray();
+        let intersections = self.intersect(ray, None);
+        let Some(comps) = nearest_comps(ray, &intersections) else {
             return Colour::black();
+        };
+        self.shade(&comps)
+    }
+
+    // direct lighting of the hit from every light, plus the world's ambient light
+    fn shade(&self, comps: &PreComp) -> Colour {
+        let ambient = if self.ambient == Colour::black() {
+            Colour::black()
+        } else {
+            self.ambient
+                * comps
+                    .object
+                    .material()
+                    .colour_at(comps.object.to_trait_ref(), comps.point)
+        };
+        self.lights.iter().fold(ambient, |acc, light| {
+            let light = light.as_ref();
+            acc + comps.shade_hit(light, self.surface_light_intensity(comps, light))
+        })
+    }
+
+    // shadows are tested from just off the side of the surface the light is on. That is always
+    // the eye's side except for two sided materials, which are also lit from behind. Light from
+    // behind a translucent surface, or wrapping past the terminator, passes through the object
+    // itself so only other objects can block it
+    fn surface_light_intensity(&self, comps: &PreComp, light: &dyn TLight) -> f64 {
+        let behind = light.direction_from(comps.point).dot(comps.normal()) < 0.0;
+        let material = comps.object.material();
+        if !behind {
+            self.light_intensity(comps.over_point, light)
+        } else if material.two_sided {
+            self.light_intensity(comps.under_point, light)
+        } else if material.translucency != Colour::black() || material.subsurface_radius > 0.0 {
+            self.light_intensity_through(comps.over_point, light, comps.object.id())
+        } else {
+            self.light_intensity(comps.over_point, light)
         }
+    }
+
+    /// Same result as `color_at`, but follows reflections with an explicit stack of rays instead
+    /// of recursing, so deep reflection limits can't grow the call stack
+    pub fn color_at_iterative(&self, ray: &Ray, ref_lim: u32) -> Colour {
+        // each pending ray carries the weight of its contribution, the tint picked up from the
+        // surfaces it reflected off, its remaining depth, and the throughput `trace` would have
+        // given it, which decides when reflections stop
+        let mut pending = vec![(
+            Ray::new(ray.origin, ray.direction),
+            1.0,
+            Colour::white(),
+            ref_lim,
+            1.0,
+        )];
+        let mut colour = Colour::black();
 
-        // passing is shadow into shade hit seems slightly reduntant now
+        while let Some((ray, weight, tint, ref_lim, throughput)) = pending.pop() {
+            self.stats.record_ray();
+            let intersections = self.intersect(&ray, None);
+            let Some(comps) = nearest_comps(&ray, &intersections) else {
+                colour = colour + self.background_for(&ray) * tint * weight;
+                continue;
+            };
 
-        let maybe_surface = maybe_precomp
-            .as_ref()
-            .map(|pc| pc.shade_hit(&self.light, is_shadowed));
+            let material = comps.object.material();
+            let weight = match World::see_through(&comps, &ray) {
+                Some((opacity, behind)) => {
+                    let behind_weight = weight * (1.0 - opacity);
+                    pending.push((behind, behind_weight, tint, ref_lim, throughput));
+                    weight * opacity
+                }
+                None => weight,
+            };
 
-        let reflected = self.reflected_colour(maybe_precomp, ref_lim - 1);
+            let surface = self.shade(&comps) + self.diffuse_bounce(&comps);
+            colour = colour + surface * tint * weight;
 
-        // if in shadow should this just return black?
-        maybe_surface
-            .map(|surface| surface + reflected)
-            .unwrap_or(Colour::black())
+            let reflectance = World::fresnel_reflectance(&comps);
+            let reflected_throughput = throughput * material.reflectivity;
+            if ref_lim > 1
+                && material.reflectivity != 0.0
+                && reflected_throughput >= self.reflection_epsilon
+            {
+                let reflect_ray = Ray::new(comps.over_point, comps.reflect_v);
+                if let Some(boost) = self.roulette(reflected_throughput, &reflect_ray) {
+                    let reflected_weight =
+                        weight * material.reflectivity * reflectance.unwrap_or(1.0) * boost;
+                    let reflected_tint = tint * material.reflection_colour;
+                    pending.push((
+                        reflect_ray,
+                        reflected_weight,
+                        reflected_tint,
+                        ref_lim - 1,
+                        reflected_throughput * boost,
+                    ));
+                }
+            }
+            // refracted rays start a new path, as `refracted_colour` does
+            if ref_lim > 1 && material.transparency != 0.0 {
+                if let Some(refract_ray) = World::refracted_ray(&comps) {
+                    let refracted_tint = tint * self.absorbed_along(&comps, &refract_ray);
+                    let refracted_weight = weight
+                        * material.transparency
+                        * reflectance.map(|r| 1.0 - r).unwrap_or(1.0);
+                    pending.push((
+                        refract_ray,
+                        refracted_weight,
+                        refracted_tint,
+                        ref_lim - 1,
+                        1.0,
+                    ));
+                }
+            }
+        }
+        colour
     }
 
-    fn is_shadowed(&self, point: Tup) -> bool {
-        let v = self.light.position.sub(point);
-        let distance = v.length();
-        let direction = v.norm();
+    /// Share of light reflected rather than refracted, for materials that do both
+    fn fresnel_reflectance(comps: &PreComp) -> Option<f64> {
+        let material = comps.object.material();
+        (material.reflectivity > 0.0 && material.transparency > 0.0).then(|| comps.schlick())
+    }
+
+    /// Nearest hit along the ray, without shading it
+    pub fn cast(&self, ray: &Ray) -> Option<HitRecord> {
+        let (t, comps) = self.hit_comps(ray)?;
+        Some(HitRecord {
+            object_id: comps.object.id(),
+            point: comps.point,
+            normal: comps.normal(),
+            t,
+            colour: comps
+                .object
+                .material()
+                .colour_at(comps.object.to_trait_ref(), comps.point),
+        })
+    }
+
+    /// Distance to and precomputed values of the nearest hit along the ray, without shading it
+    pub fn hit_comps<'a>(&'a self, ray: &'a Ray) -> Option<(f64, PreComp<'a>)> {
+        let intersections = self.intersect(ray, None);
+        let at = intersections.hit()?.at;
+        nearest_comps(ray, &intersections).map(|comps| (at, comps))
+    }
+
+    /// Intersects the ray with every object, or only those flagged in `visible` if given, ordered
+    /// from nearest to farthest
+    fn intersect(&self, ray: &Ray, visible: Option<&[bool]>) -> Vec<Intersection<'_>> {
+        let mut result: Vec<Intersection> = self
+            .objects
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| visible.map(|v| v[*i]).unwrap_or(true))
+            .flat_map(|(_, o)| {
+                self.stats.record_intersection_test();
+                o.intersect(ray)
+            })
+            .filter(|i| !self.is_clipped(ray, i.at))
+            .collect();
+        sort_intersections(&mut result);
+        result
+    }
+
+    /// Forgets every cached shadow result, for when lights or objects have moved
+    pub fn clear_shadow_cache(&self) {
+        if let Some(cache) = &self.shadow_cache {
+            cache.clear();
+        }
+    }
+
+    /// Fraction of the light's samples visible from the point, 0 in full shadow and 1 when
+    /// nothing blocks the light. A light without samples gives 0
+    pub fn light_intensity(&self, point: Tup, light: &dyn TLight) -> f64 {
+        let samples = light.samples(point);
+        // jittered samples differ from point to point, so cached results would never be reused
+        let lit = samples
+            .iter()
+            .filter(|sample| {
+                if light.jittered() {
+                    !self.cast_shadow_ray(point, **sample)
+                } else {
+                    !self.is_shadowed_from(point, **sample)
+                }
+            })
+            .count();
+        visible_fraction(lit, samples.len())
+    }
+
+    // like `light_intensity`, but the given object never blocks the light. The shadow cache is
+    // skipped since its results don't say which objects were left out
+    fn light_intensity_through(&self, point: Tup, light: &dyn TLight, object_id: Uuid) -> f64 {
+        let samples = light.samples(point);
+        let lit = samples
+            .iter()
+            .filter(|sample| {
+                let (direction, distance) = towards(point, **sample);
+                !self.occluded(&Ray::new(point, direction), distance, Some(object_id))
+            })
+            .count();
+        visible_fraction(lit, samples.len())
+    }
+
+    fn is_shadowed(&self, point: Tup, light: &dyn TLight) -> bool {
+        self.is_shadowed_from(point, light.position())
+    }
+
+    // whether anything lies between the point and a single point on a light, or anything at all
+    // in the direction of a light at infinity
+    fn is_shadowed_from(&self, point: Tup, light_position: Tup) -> bool {
+        match &self.shadow_cache {
+            Some(cache) => cache.get_or_insert_with(point, light_position, || {
+                self.cast_shadow_ray(point, light_position)
+            }),
+            None => self.cast_shadow_ray(point, light_position),
+        }
+    }
+
+    fn cast_shadow_ray(&self, point: Tup, light_position: Tup) -> bool {
+        let (direction, distance) = towards(point, light_position);
 
         // cast ray between light source and ray intersection point
         let ray = Ray::new(point, direction);
 
-        let maybe_intersect = ray.intersect_objects(&self.objects);
-        let maybe_hit = maybe_intersect.hit();
+        self.first_occluder(&ray, distance)
+    }
+
+    /// True if anything lies along the ray closer than `max_distance`. Stops at the first such
+    /// object rather than collecting and sorting every intersection
+    pub fn first_occluder(&self, ray: &Ray, max_distance: f64) -> bool {
+        self.occluded(ray, max_distance, None)
+    }
+
+    fn occluded(&self, ray: &Ray, max_distance: f64, ignored: Option<Uuid>) -> bool {
+        self.objects.iter().any(|o| {
+            self.stats.record_intersection_test();
+            o.intersect(ray).iter().any(|i| {
+                i.at > 0.0
+                    && i.at < max_distance
+                    && Some(i.object.id()) != ignored
+                    && !self.is_clipped(ray, i.at)
+            })
+        })
+    }
 
-        maybe_hit.map(|h| h.at < distance).unwrap_or(false)
+    fn is_clipped(&self, ray: &Ray, at: f64) -> bool {
+        if self.clip_planes.is_empty() {
+            return false;
+        }
+        let p = ray.position(at);
+        self.clip_planes.iter().any(|c| c.clips(p))
     }
 
-    fn reflected_colour(&self, comps: Option<PreComp>, ref_lim: u32) -> Colour {
-        if ref_lim == 0 {
+    /// Colour seen through a transparent surface, following the ray bent by Snell's law into
+    /// or out of the object. Black for opaque surfaces, once `remaining` runs out, or under total
+    /// internal reflection
+    pub fn refracted_colour(&self, comps: &PreComp, remaining: u32) -> Colour {
+        let transparency = comps.object.material().transparency;
+        if remaining == 0 || transparency == 0.0 {
             return Colour::black();
         }
-        if let Some(comps) = comps {
-            if comps.object.material().reflectivity == 0.0 {
-                Colour::black()
-            } else {
-                let reflect_ray = Ray::new(comps.over_point, comps.reflect_v);
-                let colour = self.color_at(&reflect_ray, ref_lim);
-                colour * comps.object.material().reflectivity
+        match World::refracted_ray(comps) {
+            Some(ray) => {
+                self.color_at(&ray, remaining) * transparency * self.absorbed_along(comps, &ray)
             }
-        } else {
-            Colour::black()
+            None => Colour::black(),
         }
     }
+
+    // ray continuing through the surface from just beneath it, none under total internal
+    // reflection
+    fn refracted_ray(comps: &PreComp) -> Option<Ray> {
+        refract(comps.eye().neg(), comps.normal(), comps.n1 / comps.n2)
+            .map(|direction| Ray::new(comps.under_point, direction))
+    }
+
+    // light surviving the trip through the object's material along a ray entering it, which
+    // ends at the next surface the ray meets
+    fn absorbed_along(&self, comps: &PreComp, ray: &Ray) -> Colour {
+        let material = comps.object.material();
+        if comps.is_inside() || material.absorption == Colour::black() {
+            return Colour::white();
+        }
+        self.hit_comps(ray)
+            .map(|(distance, _)| material.transmittance(distance))
+            .unwrap_or(Colour::white())
+    }
+
+    /// Weight a path of the given throughput continues along `ray` with, or `None` if russian
+    /// roulette stops it. Dim paths survive with probability proportional to their throughput
+    fn roulette(&self, throughput: f64, ray: &Ray) -> Option<f64> {
+        if throughput >= self.roulette_threshold {
+            return Some(1.0);
+        }
+        let survival = throughput / self.roulette_threshold;
+        let (o, d) = (ray.origin, ray.direction);
+        let mut random = seeded_random(self.roulette_seed, &[o.0, o.1, o.2, d.0, d.1, d.2]);
+        (random() < survival).then(|| 1.0 / survival)
+    }
+
+    fn reflected_colour(&self, comps: &PreComp, ref_lim: u32, throughput: f64) -> Colour {
+        let reflectivity = comps.object.material().reflectivity;
+        let reflected_throughput = throughput * reflectivity;
+        if ref_lim == 0 || reflectivity == 0.0 || reflected_throughput < self.reflection_epsilon {
+            return Colour::black();
+        }
+        let reflect_ray = Ray::new(comps.over_point, comps.reflect_v);
+        let Some(boost) = self.roulette(reflected_throughput, &reflect_ray) else {
+            return Colour::black();
+        };
+        let colour = self.trace(&reflect_ray, ref_lim, reflected_throughput * boost, None);
+        colour * reflectivity * boost * comps.object.material().reflection_colour
+    }
+}
+
+fn visible_fraction(lit: usize, samples: usize) -> f64 {
+    if samples == 0 {
+        return 0.0;
+    }
+    lit as f64 / samples as f64
+}
+
+// precomputes the nearest hit among sorted intersections, passing those up to it along so the
+// refractive indices either side of the surface come from the objects the ray is inside
+fn nearest_comps<'a>(ray: &'a Ray, xs: &[Intersection<'a>]) -> Option<PreComp<'a>> {
+    let (index, hit) = xs.hit_with_index()?;
+    ray.prep_comp(hit, &xs[..=index].iter().collect())
 }
 
 impl Default for World {
@@ -99,40 +677,52 @@ impl Default for World {
         let s2 = Sphere::builder()
             .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
             .build_trait();
-        Self {
-            objects: vec![s1, s2],
-            light: PointLight::default(),
-        }
+        World::new(vec![s1, s2], PointLight::default())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::f64::consts::PI;
 
     use crate::{
         colour::colour::Colour,
-        geometry::vector::{point, vector},
-        light::{self, light::PointLight},
-        material::material::Material,
-        matrix::matrix::Matrix,
-        ray::ray::{Intersection, Ray},
+        geometry::{
+            vector::{point, vector, Operations, Vector},
+            EPSILON,
+        },
+        light::{
+            self,
+            light::{AreaLight, DirectionalLight, PointLight, TLight},
+        },
+        material::{
+            material::Material,
+            pattern::{marble, Perturb, Stripe},
+        },
+        matrix::matrix::{Axis, Matrix},
+        ray::ray::{Hit, Intersection, Ray},
         shapes::{
+            csg::{Csg, CsgOperation},
+            cylinder::Cylinder,
+            group::Group,
             plane::Plane,
             shape::{TShape, TShapeBuilder},
             sphere::Sphere,
+            triangle::Triangle,
         },
         utils::test::ApproxEq,
         world,
     };
 
-    use super::World;
+    use super::{ClipPlane, HitRecord, ShadowCache, World, DEFAULT_RECURSION_DEPTH};
 
     #[test]
     fn default_world() {
         let world = World::default();
         assert_eq!(world.objects.len(), 2);
-        assert_eq!(world.light.intensity, Colour::white());
-        assert_eq!(world.light.position, point(-10.0, 10.0, -10.0));
+        assert_eq!(world.lights.len(), 1);
+        assert_eq!(world.lights[0].intensity(), Colour::white());
+        assert_eq!(world.lights[0].position(), point(-10.0, 10.0, -10.0));
         let s1 = &world.objects[0];
         let s2 = &world.objects[1];
 
@@ -160,18 +750,21 @@ mod test {
         let shape = &w.objects[0];
         let i = Intersection::new(4.0, shape.to_trait_ref());
         let comp = r.prep_comp(&i, &vec![&i]).unwrap();
-        let c = comp.shade_hit(&w.light, false);
+        let c = comp.shade_hit(w.lights[0].as_ref(), 1.0);
         c.approx_eq(Colour::new(0.38066, 0.47583, 0.2855));
     }
     #[test]
     fn shading_at_intersection_is_correct_from_inside() {
         let mut w = World::default();
-        w.light = PointLight::new(point(0.0, 0.25, 0.0), Colour::white());
+        w.lights = vec![Box::new(PointLight::new(
+            point(0.0, 0.25, 0.0),
+            Colour::white(),
+        ))];
         let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
         let shape = &w.objects[1];
         let i = Intersection::new(0.5, shape.to_trait_ref());
         let comp = r.prep_comp(&i, &vec![&i]).unwrap();
-        let c = comp.shade_hit(&w.light, false);
+        let c = comp.shade_hit(w.lights[0].as_ref(), 1.0);
         c.approx_eq(Colour::new(0.90498, 0.90498, 0.90498));
     }
 
@@ -192,8 +785,24 @@ mod test {
         let ray = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
         let intersect = Intersection::new(4.0, s2_copy.to_trait_ref());
         let comps = ray.prep_comp(&intersect, &vec![&intersect]).unwrap();
-        let shade_hit = comps.shade_hit(&light.clone(), world.is_shadowed(comps.point));
-        shade_hit.approx_eq(Colour::new(0.0, 0.0, 0.0));
+        let shade_hit = comps.shade_hit(&light, world.light_intensity(comps.point, &light));
+        shade_hit.approx_eq(Colour::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn shadowed_sphere_keeps_its_ambient_colour() {
+        let s1 = Sphere::builder().build_trait();
+        let s2 = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 0.0, 10.0))
+            .build_trait();
+        let light = PointLight::new(point(0.0, 0.0, -10.0), Colour::white());
+        let world = World::new(vec![s1, s2], light);
+
+        let ray = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
+        let ambient = Material::default().ambient;
+        world
+            .color_at(&ray, 5)
+            .approx_eq(Colour::new(ambient, ambient, ambient));
     }
 
     #[test]
@@ -204,7 +813,7 @@ mod test {
             .build_trait();
         let intersection = Intersection::new(5.0, shape.to_trait_ref());
         let comps = ray.prep_comp(&intersection, &vec![&intersection]).unwrap();
-        assert!(comps.over_point.2 < (-0.00001) / 2.0);
+        assert!(comps.over_point.2 < -EPSILON / 2.0);
         assert!(comps.point.2 > comps.over_point.2);
     }
 
@@ -212,30 +821,55 @@ mod test {
     fn no_shadow_with_object_collinear_with_point_and_light() {
         let w = World::default();
         let p = point(0.0, 10.0, 0.0);
-        let sut = w.is_shadowed(p);
+        let sut = w.is_shadowed(p, w.lights[0].as_ref());
         assert_eq!(sut, false)
     }
     #[test]
     fn shadow_with_object_between_point_and_light() {
         let w = World::default();
         let p = point(10.0, -10.0, 10.0);
-        let sut = w.is_shadowed(p);
+        let sut = w.is_shadowed(p, w.lights[0].as_ref());
         assert_eq!(sut, true)
     }
     #[test]
     fn no_shadow_when_object_behind_the_light() {
         let w = World::default();
         let p = point(-20.0, 20.0, -20.0);
-        let sut = w.is_shadowed(p);
+        let sut = w.is_shadowed(p, w.lights[0].as_ref());
         assert_eq!(sut, false)
     }
     #[test]
     fn no_shadow_when_object_behind_the_point() {
         let w = World::default();
         let p = point(-2.0, 2.0, -2.0);
-        let sut = w.is_shadowed(p);
+        let sut = w.is_shadowed(p, w.lights[0].as_ref());
         assert_eq!(sut, false)
     }
+    #[test]
+    fn first_occluder_agrees_with_nearest_hit() {
+        let w = World::default();
+        let light = w.lights[0].position();
+        let points = [
+            point(0.0, 10.0, 0.0),
+            point(10.0, -10.0, 10.0),
+            point(-20.0, 20.0, -20.0),
+            point(-2.0, 2.0, -2.0),
+            point(0.0, 0.0, 0.0),
+            point(0.5, -3.0, 0.5),
+            point(0.0, 0.0, 5.0),
+        ];
+        for p in points {
+            let v = light.sub(p);
+            let ray = Ray::new(p, v.norm());
+            let expected = w
+                .intersect(&ray, None)
+                .hit()
+                .map(|h| h.at < v.length())
+                .unwrap_or(false);
+            assert_eq!(w.first_occluder(&ray, v.length()), expected);
+        }
+    }
+
     #[test]
     fn reflected_colour_for_non_reflective_material() {
         let s1 = Sphere::builder()
@@ -257,8 +891,8 @@ mod test {
         let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
 
         let i = Intersection::new(1.0, world.objects[1].to_trait_ref());
-        let comps = r.prep_comp(&i, &vec![&i]);
-        let colour = world.reflected_colour(comps, 5);
+        let comps = r.prep_comp(&i, &vec![&i]).unwrap();
+        let colour = world.reflected_colour(&comps, 5, 1.0);
         assert_eq!(colour, Colour::black())
     }
     #[test]
@@ -288,11 +922,54 @@ mod test {
         );
 
         let i = Intersection::new(2.0_f64.sqrt(), world.objects[0].to_trait_ref());
-        let comps = r.prep_comp(&i, &vec![&i]);
-        let colour = world.reflected_colour(comps, 5);
+        let comps = r.prep_comp(&i, &vec![&i]).unwrap();
+        let colour = world.reflected_colour(&comps, 5, 1.0);
         colour.approx_eq(Colour::new(0.19033, 0.23791, 0.14274))
     }
 
+    // reflected colour off a half mirrored floor below the default world's spheres
+    fn reflection_off_floor(floor: Material) -> Colour {
+        let mut world = World::default();
+        world.objects.insert(
+            0,
+            Plane::builder()
+                .with_material(floor)
+                .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+                .build_trait(),
+        );
+        let r = Ray::new(
+            point(0.0, 0.0, -3.0),
+            vector(0.0, -(2.0_f64.sqrt()) / 2.0, 2.0_f64.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f64.sqrt(), world.objects[0].to_trait_ref());
+        let comps = r.prep_comp(&i, &vec![&i]).unwrap();
+        world.reflected_colour(&comps, 5, 1.0)
+    }
+
+    #[test]
+    fn reflections_are_not_tinted_by_the_diffuse_colour() {
+        // untinted, the floor reflects (0.19033, 0.23791, 0.14274)
+        let sut = reflection_off_floor(
+            Material::builder()
+                .with_reflectivity(0.5)
+                .with_colour(Colour::new(1.0, 0.0, 0.0))
+                .with_reflection_colour(Colour::new(0.5, 0.5, 1.0))
+                .build(),
+        );
+        sut.approx_eq(Colour::new(0.095165, 0.118955, 0.14274));
+    }
+
+    #[test]
+    fn reflections_are_tinted_by_the_reflection_colour() {
+        let sut = reflection_off_floor(
+            Material::builder()
+                .with_reflectivity(0.5)
+                .with_reflection_colour(Colour::new(1.0, 0.0, 0.0))
+                .build(),
+        );
+        sut.approx_eq(Colour::new(0.19033, 0.0, 0.0));
+    }
+
     #[test]
     fn reflected_colour_for_reflective_material_with_shade_hit() {
         let s1 = Sphere::builder()
@@ -326,6 +1003,78 @@ mod test {
         colour.approx_eq(Colour::new(0.87675, 0.92434, 0.82918))
     }
 
+    #[test]
+    fn iterative_reflection_matches_recursive() {
+        let s1 = Sphere::builder()
+            .with_material(
+                Material::builder()
+                    .with_diffuse(0.7)
+                    .with_specular(0.2)
+                    .with_colour(Colour::new(0.8, 1.0, 0.6))
+                    .with_reflectivity(0.3)
+                    .build(),
+            )
+            .build_trait();
+        let p1 = Plane::builder()
+            .with_material(Material::builder().with_reflectivity(0.5).build())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let world = World::new(vec![p1, s1], PointLight::default());
+
+        let rays = [
+            Ray::new(
+                point(0.0, 0.0, -3.0),
+                vector(0.0, -(2.0_f64.sqrt()) / 2.0, 2.0_f64.sqrt() / 2.0),
+            ),
+            Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)),
+            Ray::new(point(2.0, 1.0, -4.0), vector(-0.4, -0.6, 0.7).norm()),
+        ];
+        for ray in rays {
+            world
+                .color_at_iterative(&ray, 5)
+                .approx_eq(world.color_at(&ray, 5));
+        }
+    }
+
+    #[test]
+    fn reflective_scene_renders_known_colours() {
+        let s1 = Sphere::builder()
+            .with_material(
+                Material::builder()
+                    .with_diffuse(0.7)
+                    .with_specular(0.2)
+                    .with_colour(Colour::new(0.8, 1.0, 0.6))
+                    .with_reflectivity(0.3)
+                    .build(),
+            )
+            .build_trait();
+        let p1 = Plane::builder()
+            .with_material(Material::builder().with_reflectivity(0.5).build())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let world = World::new(vec![p1, s1], PointLight::default());
+
+        let head_on = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let glancing = Ray::new(point(2.0, 1.0, -4.0), vector(-0.4, -0.6, 0.7).norm());
+        world
+            .color_at(&head_on, 5)
+            .approx_eq(Colour::new(0.38066, 0.47583, 0.28550));
+        world
+            .color_at(&glancing, 5)
+            .approx_eq(Colour::new(0.85483, 0.87402, 0.83564));
+    }
+
+    #[test]
+    fn clipped_half_of_sphere_shows_background() {
+        let mut world = World::new(vec![Sphere::builder().build_trait()], PointLight::default());
+        world.clip_planes = vec![ClipPlane::new(point(0.0, 0.0, 0.0), vector(1.0, 0.0, 0.0))];
+
+        let clipped = Ray::new(point(0.5, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let kept = Ray::new(point(-0.5, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(world.color_at(&clipped, 5), Colour::black());
+        assert_ne!(world.color_at(&kept, 5), Colour::black());
+    }
+
     #[test]
     fn reflection_does_not_cause_stack_overflow() {
         let p1 = Plane::builder()
@@ -345,4 +1094,778 @@ mod test {
         let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
         let _ = world.color_at(&ray, 5);
     }
+
+    fn parallel_mirrors(reflectivity: f64) -> World {
+        let p1 = Plane::builder()
+            .with_material(Material::builder().with_reflectivity(reflectivity).build())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let p2 = Plane::builder()
+            .with_material(Material::builder().with_reflectivity(reflectivity).build())
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .build_trait();
+        World::new(
+            vec![p1, p2],
+            PointLight::new(point(0.0, 0.0, 0.0), Colour::white()),
+        )
+    }
+
+    #[test]
+    fn weak_reflections_terminate_before_max_depth() {
+        let mut world = parallel_mirrors(0.1);
+        world.reflection_epsilon = 0.01;
+        world.stats.enabled = true;
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let _ = world.color_at(&ray, 5);
+        assert_eq!(world.stats.rays_traced(), 3);
+    }
+
+    #[test]
+    fn two_sided_plane_is_lit_from_below() {
+        let plane = |two_sided| {
+            Plane::builder()
+                .with_material(Material::builder().with_two_sided(two_sided).build())
+                .build_trait()
+        };
+        let light = || PointLight::new(point(0.0, -10.0, 0.0), Colour::white());
+        let r = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        let one_sided = World::new(vec![plane(false)], light()).color_at(&r, 5);
+        let two_sided = World::new(vec![plane(true)], light()).color_at(&r, 5);
+
+        one_sided.approx_eq(Colour::new(0.1, 0.1, 0.1));
+        two_sided.approx_eq(Colour::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn translucent_sphere_lit_from_behind_is_brighter_than_an_opaque_one() {
+        let sphere = |material| Sphere::builder().with_material(material).build_trait();
+        let light = || PointLight::new(point(0.0, 0.0, 10.0), Colour::white());
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let opaque = World::new(vec![sphere(Material::default())], light()).color_at(&r, 5);
+        let wax = Material::builder()
+            .with_translucency(Colour::new(0.8, 0.5, 0.3))
+            .with_subsurface_radius(0.5)
+            .build();
+        let wax = World::new(vec![sphere(wax)], light()).color_at(&r, 5);
+
+        opaque.approx_eq(Colour::new(0.1, 0.1, 0.1));
+        wax.approx_eq(Colour::new(0.9, 0.6, 0.4));
+    }
+
+    #[test]
+    fn wrapped_light_reaches_past_the_terminator() {
+        // the light is just behind the surface where the ray hits
+        let light = || PointLight::new(point(-3.0, 0.0, 10.0), Colour::white());
+        let sphere = |material| Sphere::builder().with_material(material).build_trait();
+        let r = Ray::new(point(-0.9, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let hard = World::new(vec![sphere(Material::default())], light()).color_at(&r, 5);
+        let soft = Material::builder().with_subsurface_radius(0.5).build();
+        let soft = World::new(vec![sphere(soft)], light()).color_at(&r, 5);
+
+        hard.approx_eq(Colour::new(0.1, 0.1, 0.1));
+        assert!(soft.red > 0.2);
+    }
+
+    #[test]
+    fn stats_are_only_counted_when_enabled() {
+        let world = parallel_mirrors(1.0);
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let _ = world.color_at(&ray, 5);
+        assert_eq!(world.stats.rays_traced(), 0);
+        assert_eq!(world.stats.intersection_tests(), 0);
+    }
+
+    #[test]
+    fn mirror_reflections_use_full_depth() {
+        let mut world = parallel_mirrors(1.0);
+        world.reflection_epsilon = 0.01;
+        world.stats.enabled = true;
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let _ = world.color_at(&ray, 5);
+        assert_eq!(world.stats.rays_traced(), 5);
+    }
+
+    #[test]
+    fn isolating_an_object_renders_the_others_as_background() {
+        let world = World::default();
+        let inner_id = world.objects[1].id();
+        let world = world.with_only(&[inner_id]);
+        assert_eq!(world.objects.len(), 1);
+
+        // passes through the outer sphere only
+        let miss = Ray::new(point(0.0, 0.75, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(world.color_at(&miss, 5), Colour::black());
+
+        let hit = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_ne!(world.color_at(&hit, 5), Colour::black());
+    }
+
+    #[test]
+    fn rays_which_miss_see_the_background() {
+        let world = World {
+            background: Colour::new(0.2, 0.3, 0.9),
+            ..World::default()
+        };
+        let miss = Ray::new(point(0.0, 5.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(world.color_at(&miss, 5), Colour::new(0.2, 0.3, 0.9));
+        assert_eq!(
+            world.color_at_iterative(&miss, 5),
+            Colour::new(0.2, 0.3, 0.9)
+        );
+    }
+
+    #[test]
+    fn environment_is_looked_up_by_ray_direction() {
+        // stripes turned to run across y, white near the horizon and blue overhead
+        let world = World {
+            environment: Some(Box::new(Stripe::new(
+                Colour::white(),
+                Colour::new(0.1, 0.2, 0.8),
+                Matrix::rotation(Axis::Z, PI / 2.0),
+            ))),
+            ..World::default()
+        };
+        let sky = |direction| world.color_at(&Ray::new(point(0.0, 5.0, 0.0), direction), 5);
+        assert_eq!(sky(vector(1.0, 0.2, 0.0)), Colour::white());
+        assert_eq!(sky(vector(0.0, 1.0, 0.0)), Colour::new(0.1, 0.2, 0.8));
+    }
+
+    #[test]
+    fn merging_worlds_combines_objects_and_lights() {
+        let mut world = World::new(vec![Sphere::builder().build_trait()], PointLight::default());
+        let other = World::new(
+            vec![Sphere::builder()
+                .with_transform(Matrix::translation(0.0, 0.0, 3.0))
+                .build_trait()],
+            PointLight::new(point(10.0, 10.0, -10.0), Colour::white()),
+        );
+
+        world.merge(other);
+
+        assert_eq!(world.objects.len(), 2);
+        assert_eq!(world.lights.len(), 2);
+        assert_eq!(world.lights[1].position(), point(10.0, 10.0, -10.0));
+    }
+
+    #[test]
+    fn merging_a_world_without_lights_keeps_existing_lights() {
+        let mut world = World::new(vec![Sphere::builder().build_trait()], PointLight::default());
+        let other = World::with_lights(vec![Sphere::builder().build_trait()], vec![]);
+
+        world.merge(other);
+
+        assert_eq!(world.objects.len(), 2);
+        assert_eq!(world.lights.len(), 1);
+    }
+
+    #[test]
+    fn diffuse_bounce_picks_up_colour_from_a_nearby_wall() {
+        let floor = Plane::builder().build_trait();
+        let wall = Plane::builder()
+            .with_material(
+                Material::builder()
+                    .with_colour(Colour::new(1.0, 0.0, 0.0))
+                    .build(),
+            )
+            .with_transform(
+                Matrix::ident()
+                    .rotate(Axis::Z, PI / 2.0)
+                    .translate(1.0, 0.0, 0.0),
+            )
+            .build_trait();
+        let light = PointLight::new(point(-5.0, 10.0, 0.0), Colour::white());
+        let mut world = World::new(vec![floor, wall], light);
+        let r = Ray::new(point(0.5, 5.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        let without = world.color_at(&r, 5);
+        world.diffuse_bounce_samples = 64;
+        let with = world.color_at(&r, 5);
+
+        assert_eq!(without.red, without.green);
+        assert!(with.red > without.red);
+        assert!(with.red - with.green > 0.01);
+    }
+
+    #[test]
+    fn cached_shadows_match_uncached_shadows() {
+        let uncached = World::default();
+        let cached = World {
+            shadow_cache: Some(ShadowCache::new(0.001)),
+            ..World::default()
+        };
+
+        let light = uncached.lights[0].as_ref();
+        let points: Vec<_> = (0..20)
+            .flat_map(|i| (0..20).map(move |j| (i, j)))
+            .map(|(i, j)| point(i as f64 * 0.5 - 5.0, j as f64 * 0.5 - 5.0, 3.0))
+            .collect();
+
+        // the second pass is answered entirely from the cache
+        for _ in 0..2 {
+            for p in &points {
+                assert_eq!(
+                    cached.is_shadowed(*p, light),
+                    uncached.is_shadowed(*p, light)
+                );
+            }
+        }
+        assert_eq!(cached.shadow_cache.as_ref().unwrap().len(), points.len());
+        assert!(points.iter().any(|p| uncached.is_shadowed(*p, light)));
+
+        cached.clear_shadow_cache();
+        assert!(cached.shadow_cache.as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn jittered_lights_bypass_the_shadow_cache() {
+        let light = AreaLight::new(
+            point(-1.0, 5.0, -1.0),
+            vector(2.0, 0.0, 0.0),
+            2,
+            vector(0.0, 0.0, 2.0),
+            2,
+            Colour::white(),
+        );
+        let world = World {
+            shadow_cache: Some(ShadowCache::new(0.5)),
+            ..World::default()
+        };
+
+        world.light_intensity(point(0.0, 2.0, 0.0), &light.clone().with_jitter(7));
+        assert!(world.shadow_cache.as_ref().unwrap().is_empty());
+        world.light_intensity(point(0.0, 2.0, 0.0), &light);
+        assert_eq!(world.shadow_cache.as_ref().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn light_without_samples_gives_no_light() {
+        let light = AreaLight::new(
+            point(-1.0, 5.0, -1.0),
+            vector(2.0, 0.0, 0.0),
+            0,
+            vector(0.0, 0.0, 2.0),
+            4,
+            Colour::white(),
+        );
+        assert_eq!(
+            World::default().light_intensity(point(0.0, 2.0, 0.0), &light),
+            0.0
+        );
+    }
+
+    fn glass() -> Material {
+        Material::builder()
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .build()
+    }
+
+    #[test]
+    fn refracted_colour_of_an_opaque_surface_is_black() {
+        let world = World::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let shape = world.objects[0].to_trait_ref();
+        let xs = [
+            Intersection::new(4.0, shape.clone()),
+            Intersection::new(6.0, shape),
+        ];
+        let comps = r.prep_comp(&xs[0], &xs.iter().collect()).unwrap();
+        assert_eq!(world.refracted_colour(&comps, 5), Colour::black());
+    }
+
+    #[test]
+    fn refracted_colour_at_the_maximum_depth_is_black() {
+        let world = World::new(
+            vec![Sphere::builder().with_material(glass()).build_trait()],
+            PointLight::default(),
+        );
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = world.objects[0].intersect(&r);
+        let comps = r.prep_comp(&xs[0], &xs.iter().collect()).unwrap();
+        assert_eq!(world.refracted_colour(&comps, 0), Colour::black());
+    }
+
+    #[test]
+    fn refracted_colour_under_total_internal_reflection_is_black() {
+        let world = World::new(
+            vec![Sphere::builder().with_material(glass()).build_trait()],
+            PointLight::default(),
+        );
+        let r = Ray::new(point(0.0, 0.0, 2.0_f64.sqrt() / 2.0), vector(0.0, 1.0, 0.0));
+        let xs = world.objects[0].intersect(&r);
+        // inside the sphere, so the hit which matters is the second
+        let comps = r.prep_comp(&xs[1], &xs.iter().collect()).unwrap();
+        assert_eq!(world.refracted_colour(&comps, 5), Colour::black());
+    }
+
+    #[test]
+    fn refraction_shows_what_is_behind_a_glass_sphere() {
+        let wall = || {
+            Plane::builder()
+                .with_material(Material::with_colour(Colour::new(1.0, 0.0, 0.0)))
+                .with_transform(
+                    Matrix::ident()
+                        .rotate(Axis::X, PI / 2.0)
+                        .translate(0.0, 0.0, 5.0),
+                )
+                .build_trait()
+        };
+        // the glass itself adds nothing, so only the wall seen through it remains
+        let clear_glass = Material::builder()
+            .with_colour(Colour::black())
+            .with_ambient(0.0)
+            .with_specular(0.0)
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .build();
+        let with_glass = World::new(
+            vec![
+                wall(),
+                Sphere::builder().with_material(clear_glass).build_trait(),
+            ],
+            PointLight::default(),
+        );
+        let without_glass = World::new(vec![wall()], PointLight::default());
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let sut = with_glass.color_at(&r, 5);
+        assert_ne!(sut, Colour::black());
+        sut.approx_eq(without_glass.color_at(&r, 5));
+    }
+
+    #[test]
+    fn absorbing_glass_tints_what_is_behind_it() {
+        let wall = Plane::builder()
+            .with_transform(
+                Matrix::ident()
+                    .rotate(Axis::X, PI / 2.0)
+                    .translate(0.0, 0.0, 5.0),
+            )
+            .build_trait();
+        let tinted_glass = Material::builder()
+            .with_colour(Colour::black())
+            .with_ambient(0.0)
+            .with_specular(0.0)
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .with_absorption(Colour::new(0.0, 1.0, 1.0))
+            .build();
+        let world = World::new(
+            vec![
+                wall,
+                Sphere::builder().with_material(tinted_glass).build_trait(),
+            ],
+            PointLight::default(),
+        );
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        // two units of glass leave exp(-2) of the green and blue
+        let sut = world.color_at(&r, 5);
+        sut.green.approx_eq(sut.red * (-2.0_f64).exp());
+        sut.blue.approx_eq(sut.red * (-2.0_f64).exp());
+    }
+
+    #[test]
+    fn reflective_glass_splits_light_by_reflectance() {
+        let floor = Plane::builder()
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .with_material(
+                Material::builder()
+                    .with_reflectivity(0.5)
+                    .with_transparency(0.5)
+                    .with_refractive_index(1.5)
+                    .build(),
+            )
+            .build_trait();
+        let ball = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, -3.5, -0.5))
+            .with_material(
+                Material::builder()
+                    .with_colour(Colour::new(1.0, 0.0, 0.0))
+                    .with_ambient(0.5)
+                    .build(),
+            )
+            .build_trait();
+        let world = World::new(vec![floor, ball], PointLight::default());
+        let root2 = 2.0_f64.sqrt() / 2.0;
+        let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -root2, root2));
+
+        let sut = world.color_at(&r, 5);
+        sut.approx_eq(world.color_at_iterative(&r, 5));
+
+        let (_, comps) = world.hit_comps(&r).unwrap();
+        let reflectance = comps.schlick();
+        assert!(reflectance > 0.0 && reflectance < 1.0);
+    }
+
+    #[test]
+    fn iterative_colour_matches_recursive_colour_with_bounce_and_roulette() {
+        let mirror = || {
+            Material::builder()
+                .with_reflectivity(0.8)
+                .with_colour(Colour::new(0.2, 0.6, 0.9))
+                .build()
+        };
+        let floor = Plane::builder()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let ceiling = Plane::builder()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .build_trait();
+        let light = PointLight::new(point(0.0, 0.0, 5.0), Colour::white());
+        let mut world = World::new(vec![floor, ceiling], light);
+        world.diffuse_bounce_samples = 8;
+        world.roulette_threshold = 0.5;
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, -1.0, 0.2).norm());
+
+        for seed in 0..20 {
+            world.roulette_seed = seed;
+            world
+                .color_at_iterative(&r, 12)
+                .approx_eq(world.color_at(&r, 12));
+        }
+    }
+
+    #[test]
+    fn russian_roulette_converges_on_the_full_depth_colour_with_fewer_rays() {
+        let mirror = || Material::builder().with_reflectivity(0.8).build();
+        let floor = Plane::builder()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build_trait();
+        let ceiling = Plane::builder()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .build_trait();
+        let light = PointLight::new(point(0.0, 0.0, 5.0), Colour::white());
+        let mut world = World::new(vec![floor, ceiling], light);
+        world.reflection_epsilon = 0.0;
+        world.stats.enabled = true;
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, -1.0, 0.2).norm());
+
+        let reference = world.color_at(&r, 12);
+        let full_rays = world.stats.rays_traced();
+
+        world.roulette_threshold = 0.5;
+        world.stats.reset();
+        let passes = 500;
+        let total = (0..passes).fold(Colour::black(), |acc, seed| {
+            world.roulette_seed = seed;
+            acc + world.color_at(&r, 12)
+        });
+        let sut = total * (1.0 / passes as f64);
+
+        // within 2% of the reference
+        assert!((sut.red - reference.red).abs() < 0.02 * reference.red);
+        assert!(world.stats.rays_traced() < full_rays * passes as usize);
+    }
+
+    #[test]
+    fn area_light_intensity_is_the_unoccluded_fraction_of_samples() {
+        let world = World::default();
+        let light = AreaLight::new(
+            point(-0.5, -0.5, -5.0),
+            vector(1.0, 0.0, 0.0),
+            2,
+            vector(0.0, 1.0, 0.0),
+            2,
+            Colour::white(),
+        );
+        for (p, expected) in [
+            (point(0.0, 0.0, 2.0), 0.0),
+            (point(1.0, -1.0, 2.0), 0.25),
+            (point(1.5, 0.0, 2.0), 0.5),
+            (point(1.25, 1.25, 3.0), 0.75),
+            (point(0.0, 0.0, -2.0), 1.0),
+        ] {
+            assert_eq!(world.light_intensity(p, &light), expected);
+        }
+    }
+
+    #[test]
+    fn world_ambient_brightens_fully_shadowed_points() {
+        let floor = Plane::builder().build_trait();
+        let ball = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 2.0, 0.0))
+            .build_trait();
+        let mut world = World::new(
+            vec![floor, ball],
+            PointLight::new(point(0.0, 10.0, 0.0), Colour::white()),
+        );
+        let r = Ray::new(point(0.0, 0.5, 0.0), vector(0.0, -1.0, 0.0));
+        world.color_at(&r, 1).approx_eq(Colour::new(0.1, 0.1, 0.1));
+
+        world.ambient = Colour::new(0.2, 0.2, 0.2);
+        world.color_at(&r, 1).approx_eq(Colour::new(0.3, 0.3, 0.3));
+    }
+
+    #[test]
+    fn directional_light_shades_separate_spheres_alike() {
+        let spheres = || {
+            [-3.0, 3.0].map(|x| {
+                Sphere::builder()
+                    .with_transform(Matrix::translation(x, 0.0, 0.0))
+                    .build_trait()
+            })
+        };
+        // the same spot on each sphere, facing the camera
+        let shade = |world: &World| {
+            [-3.0, 3.0].map(|x| {
+                let r = Ray::new(point(x, 0.3, -5.0), vector(0.0, 0.0, 1.0));
+                world.color_at(&r, 1)
+            })
+        };
+        let sun = DirectionalLight::new(vector(1.0, -1.0, 1.0), Colour::white());
+        let [left, right] = shade(&World::new(spheres().into(), sun));
+        left.approx_eq(right);
+        assert!(left.red > 0.1);
+
+        // whereas a nearby point light reaches each from a different direction
+        let lamp = PointLight::new(point(-2.0, 5.0, -5.0), Colour::white());
+        let [left, right] = shade(&World::new(spheres().into(), lamp));
+        assert!((left.red - right.red).abs() > 0.01);
+    }
+
+    #[test]
+    fn directional_light_casts_shadows_from_any_distance() {
+        let floor = Plane::builder().build_trait();
+        let ball = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 1000.0, 0.0))
+            .build_trait();
+        let world = World::new(
+            vec![floor, ball],
+            DirectionalLight::new(vector(0.0, -1.0, 0.0), Colour::white()),
+        );
+        let light = world.lights[0].as_ref();
+        assert!(world.is_shadowed(point(0.0, 0.0, 0.0), light));
+        assert!(!world.is_shadowed(point(5.0, 0.0, 0.0), light));
+    }
+
+    #[test]
+    fn area_lights_soften_shadow_edges() {
+        let floor = Plane::builder().build_trait();
+        let ball = Sphere::builder()
+            .with_transform(Matrix::translation(0.0, 2.0, 0.0))
+            .build_trait();
+        let light = AreaLight::new(
+            point(-1.0, 5.0, -1.0),
+            vector(2.0, 0.0, 0.0),
+            4,
+            vector(0.0, 0.0, 2.0),
+            4,
+            Colour::white(),
+        )
+        .with_jitter(3);
+        let world = World::new(vec![floor, ball], light);
+
+        // looking straight down at the floor under the ball, at the edge of its shadow and clear
+        // of it
+        let shade = |x: f64| {
+            let r = Ray::new(point(x, 0.5, 0.0), vector(0.0, -1.0, 0.0));
+            world.color_at(&r, 1).red
+        };
+        let (umbra, penumbra, lit) = (shade(0.0), shade(1.1), shade(3.0));
+        umbra.approx_eq(0.1);
+        assert!(penumbra > umbra && penumbra < lit);
+    }
+
+    #[test]
+    fn half_opaque_red_card_over_blue_shows_purple() {
+        let flat = |colour| {
+            Material::builder()
+                .with_colour(colour)
+                .with_ambient(1.0)
+                .with_diffuse(0.0)
+                .with_specular(0.0)
+        };
+        let card = Triangle::builder(
+            point(-1.0, -1.0, 0.0),
+            point(1.0, -1.0, 0.0),
+            point(0.0, 1.0, 0.0),
+        )
+        .with_material(flat(Colour::new(1.0, 0.0, 0.0)).with_opacity(0.5).build())
+        .build_trait();
+        let background = Plane::builder()
+            .with_transform(
+                Matrix::ident()
+                    .rotate(Axis::X, PI / 2.0)
+                    .translate(0.0, 0.0, 5.0),
+            )
+            .with_material(flat(Colour::new(0.0, 0.0, 1.0)).build())
+            .build_trait();
+        // off to the side so the card casts no shadow on the background behind it
+        let light = PointLight::new(point(10.0, 0.0, -10.0), Colour::white());
+        let world = World::new(vec![card, background], light);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        world.color_at(&r, 5).approx_eq(Colour::new(0.5, 0.0, 0.5));
+        world
+            .color_at_iterative(&r, 5)
+            .approx_eq(Colour::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn casting_into_the_default_world_records_the_nearest_hit() {
+        let world = World::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let sut = world.cast(&r).unwrap();
+        assert_eq!(
+            sut,
+            HitRecord {
+                object_id: world.objects[0].id(),
+                point: point(0.0, 0.0, -1.0),
+                normal: vector(0.0, 0.0, -1.0),
+                t: 4.0,
+                colour: Colour::new(0.8, 1.0, 0.6),
+            }
+        );
+        let miss = Ray::new(point(0.0, 5.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(world.cast(&miss), None);
+    }
+
+    #[test]
+    fn reflected_colour_at_no_remaining_depth_is_black() {
+        let mut world = World::default();
+        world.objects.push(
+            Plane::builder()
+                .with_material(Material::builder().with_reflectivity(0.5).build())
+                .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+                .build_trait(),
+        );
+        let root2 = 2.0_f64.sqrt() / 2.0;
+        let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -root2, root2));
+        let i = Intersection::new(2.0_f64.sqrt(), world.objects[2].to_trait_ref());
+        let comps = r.prep_comp(&i, &vec![&i]).unwrap();
+        assert_eq!(world.reflected_colour(&comps, 0, 1.0), Colour::black());
+    }
+
+    #[test]
+    fn colour_at_zero_depth_is_the_surface_alone() {
+        let mut world = World::default();
+        world.objects.push(
+            Plane::builder()
+                .with_material(Material::builder().with_reflectivity(0.5).build())
+                .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+                .build_trait(),
+        );
+        let root2 = 2.0_f64.sqrt() / 2.0;
+        let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -root2, root2));
+        let i = Intersection::new(2.0_f64.sqrt(), world.objects[2].to_trait_ref());
+        let surface = r
+            .prep_comp(&i, &vec![&i])
+            .unwrap()
+            .shade_hit(world.lights[0].as_ref(), 1.0);
+
+        world.color_at(&r, 0).approx_eq(surface);
+        world.color_at_iterative(&r, 0).approx_eq(surface);
+        assert_ne!(world.color_at(&r, 5), surface);
+    }
+
+    #[test]
+    fn default_depth_matches_the_constant() {
+        let world = World::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(
+            world.color_at_default(&r),
+            world.color_at(&r, DEFAULT_RECURSION_DEPTH)
+        );
+    }
+
+    #[test]
+    fn default_world_survives_a_json_round_trip() {
+        let world = World::default();
+        let sut = World::from_json(&world.to_json().unwrap()).unwrap();
+        assert_eq!(sut.objects.len(), 2);
+        assert_eq!(sut.lights.len(), 1);
+        assert_eq!(sut.objects[0].material().colour, Colour::new(0.8, 1.0, 0.6));
+        assert_eq!(sut.objects[0].material().diffuse, 0.7);
+        assert_eq!(sut.objects[1].transform(), &Matrix::scaling(0.5, 0.5, 0.5));
+        assert_eq!(sut.lights[0].position(), point(-10.0, 10.0, -10.0));
+    }
+
+    #[test]
+    fn round_tripped_scene_renders_the_same() {
+        let striped = Material::builder()
+            .with_pattern(Box::new(Perturb::new(
+                Box::new(Stripe::new(
+                    Colour::new(0.9, 0.2, 0.1),
+                    Colour::white(),
+                    Matrix::scaling(0.2, 0.2, 0.2),
+                )),
+                0.3,
+                7,
+                Matrix::ident(),
+            )))
+            .with_reflectivity(0.3)
+            .build();
+        let hollowed = Csg::builder(
+            CsgOperation::Difference,
+            Sphere::builder().with_material(striped).build_trait(),
+            Cylinder::builder()
+                .with_transform(Matrix::scaling(0.5, 1.0, 0.5))
+                .build_trait(),
+        )
+        .build_trait();
+        let group = Group::builder()
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .with_child(hollowed)
+            .with_child(
+                Cylinder::builder()
+                    .with_transform(Matrix::translation(3.0, 0.0, 2.0))
+                    .with_material(Material::builder().with_pattern(marble()).build())
+                    .build_trait(),
+            )
+            .build_trait();
+        let world = World {
+            background: Colour::new(0.1, 0.2, 0.3),
+            ..World::with_lights(
+                vec![Box::new(Plane::default()), group],
+                vec![
+                    Box::new(PointLight::default()),
+                    Box::new(DirectionalLight::new(
+                        vector(1.0, -1.0, 0.5),
+                        Colour::new(0.3, 0.3, 0.3),
+                    )),
+                ],
+            )
+        };
+
+        let sut = World::from_json(&world.to_json().unwrap()).unwrap();
+        let eye = point(0.0, 2.0, -6.0);
+        for target in [
+            point(0.0, 1.0, 0.0),
+            point(0.0, 1.5, -0.9),
+            point(3.0, 1.0, 2.0),
+            point(-2.0, 0.0, 1.0),
+            point(0.0, 5.0, 0.0),
+        ] {
+            let r = Ray::new(eye, target.sub(eye).norm());
+            sut.color_at_default(&r)
+                .approx_eq(world.color_at_default(&r));
+        }
+    }
+
+    #[test]
+    fn hits_account_for_intersections_behind_the_ray_origin() {
+        let glass = Sphere::builder()
+            .with_material(
+                Material::builder()
+                    .with_transparency(1.0)
+                    .with_refractive_index(1.5)
+                    .build(),
+            )
+            .build_trait();
+        let w = World::new(vec![glass], PointLight::default());
+        // starting inside the sphere, the intersection behind the origin is where the ray entered
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let (at, comps) = w.hit_comps(&r).unwrap();
+        assert_eq!(at, 1.0);
+        assert_eq!(comps.n1, 1.5);
+        assert_eq!(comps.n2, 1.0);
+    }
 }